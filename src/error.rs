@@ -0,0 +1,119 @@
+use std::fmt::{Display, Formatter};
+use std::num::{ParseFloatError, ParseIntError};
+use std::{fmt, io};
+
+/*
+    The one error type returned by everything in the crate that can fail, so that callers (e.g. run)
+    can pass any of them up with ? and report them the same way.
+*/
+#[derive(Debug)]
+pub enum RaytracerError {
+    SingularMatrix,           // Inverting a matrix with a zero determinant
+    MatrixSize(&'static str), // An operation the matrix isn't the right shape for
+    Grid(array2d::Error),     // Out of bounds access, or rows/columns of uneven lengths
+    Parse(String),            // Malformed input, e.g. an OBJ or PPM file, or the arguments
+    Io(io::Error),            // Reading or writing a file
+    Format(fmt::Error),       // Writing out an image's contents
+    Invalid(String),          // Arguments or a scene that fail their checks
+}
+
+impl Display for RaytracerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RaytracerError::SingularMatrix => {
+                write!(f, "matrix is not invertible due to a zero determinant")
+            }
+            RaytracerError::MatrixSize(message) => write!(f, "{}", message),
+            RaytracerError::Grid(error) => write!(f, "{}", error),
+            RaytracerError::Parse(message) => write!(f, "{}", message),
+            RaytracerError::Io(error) => write!(f, "{}", error),
+            RaytracerError::Format(error) => write!(f, "{}", error),
+            RaytracerError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for RaytracerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RaytracerError::Grid(error) => Some(error),
+            RaytracerError::Io(error) => Some(error),
+            RaytracerError::Format(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<array2d::Error> for RaytracerError {
+    fn from(error: array2d::Error) -> Self {
+        RaytracerError::Grid(error)
+    }
+}
+
+impl From<io::Error> for RaytracerError {
+    fn from(error: io::Error) -> Self {
+        RaytracerError::Io(error)
+    }
+}
+
+impl From<fmt::Error> for RaytracerError {
+    fn from(error: fmt::Error) -> Self {
+        RaytracerError::Format(error)
+    }
+}
+
+impl From<ParseIntError> for RaytracerError {
+    fn from(error: ParseIntError) -> Self {
+        RaytracerError::Parse(error.to_string())
+    }
+}
+
+impl From<ParseFloatError> for RaytracerError {
+    fn from(error: ParseFloatError) -> Self {
+        RaytracerError::Parse(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::RaytracerError;
+    use crate::matrices::matrix::Matrix;
+    use crate::scene::obj_file_parser::ObjFileParser;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn given_a_singular_matrix_when_inverting_should_return_a_singular_matrix_error() {
+        // Arrange
+        let matrix = Matrix::from_rows(&[
+            vec![-4.0, 2.0, -2.0, -3.0],
+            vec![9.0, 6.0, 2.0, 6.0],
+            vec![0.0, -5.0, 1.0, -5.0],
+            vec![0.0, 0.0, 0.0, 0.0],
+        ])
+        .unwrap();
+
+        // Act
+        let result = matrix.inverse();
+
+        // Assert
+        assert!(matches!(result, Err(RaytracerError::SingularMatrix)));
+    }
+
+    #[test]
+    fn given_a_missing_obj_file_when_parsing_should_return_an_io_error_naming_the_file() {
+        // Arrange
+        let file_path = "tests/obj_files/does_not_exist.obj".to_string();
+
+        // Act
+        let result = ObjFileParser::parse_obj_file_to_mesh(file_path);
+
+        // Assert
+        match result {
+            Err(RaytracerError::Io(error)) => {
+                assert_eq!(ErrorKind::NotFound, error.kind());
+                assert!(error.to_string().contains("does_not_exist.obj"));
+            }
+            _ => panic!("expected an IO error"),
+        }
+    }
+}
@@ -3,9 +3,12 @@ pub mod csg;
 pub mod cube;
 pub mod cylinder;
 pub mod group;
+pub mod instance;
 pub mod plane;
 pub mod shape;
+pub mod slab;
 pub mod smooth_triangle;
 pub mod sphere;
 pub mod test_shape;
 pub mod triangle;
+pub mod triangle_mesh;
@@ -10,6 +10,8 @@ use crate::tuples::point_light::PointLight;
 use crate::tuples::ray::Ray;
 use crate::tuples::tuple::Tuple;
 use crate::EPSILON;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::Uuid;
 
@@ -18,7 +20,7 @@ pub struct Cone {
     transform: Arc<Matrix>,
     material: Arc<dyn Material>,
     parent: RwLock<Weak<dyn Shape>>,
-    casts_shadow: bool,
+    casts_shadow: AtomicBool,
     minimum: f64,
     maximum: f64,
     closed: bool,
@@ -31,7 +33,7 @@ impl Cone {
             transform: Arc::new(Matrix::identity(4)),
             material: Arc::new(Phong::default()),
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow: true,
+            casts_shadow: AtomicBool::new(true),
             minimum: f64::NEG_INFINITY,
             maximum: f64::INFINITY,
             closed: false,
@@ -51,13 +53,32 @@ impl Cone {
             transform,
             material,
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow,
+            casts_shadow: AtomicBool::new(casts_shadow),
             minimum,
             maximum,
             closed,
         }
     }
 
+    // A cone with its tip at one point, opening out to the given radius at the other
+    pub fn between(
+        tip: Tuple,
+        base: Tuple,
+        radius: f64,
+        material: Arc<dyn Material>,
+        casts_shadow: bool,
+        closed: bool,
+    ) -> Cone {
+        Cone::new(
+            Arc::new(Matrix::span_transform(tip, base, radius)),
+            material,
+            casts_shadow,
+            0.0,
+            1.0,
+            closed,
+        )
+    }
+
     // Checks to see if the intersection at 't', is within a radius of y from the y-axis. This is
     // because a cone's radius at any given y is the absolute value of that y.
     fn check_cap(ray: &Ray, y: f64, t: f64) -> bool {
@@ -188,18 +209,29 @@ impl Shape for Cone {
     }
 
     fn casts_shadow(&self) -> bool {
-        self.casts_shadow
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
     }
 
     fn local_normal_at(&self, local_point: Tuple, _: &Intersection) -> Tuple {
         // Compute the square distance from the y-axis
         let dist = local_point.x * local_point.x + local_point.z * local_point.z;
 
-        if dist < 1.0 && local_point.y >= self.maximum - EPSILON {
+        /*
+            The caps have the same radius as the cone at their height, i.e. |y|. As with cylinders,
+            points within EPSILON of the rim are always treated as being on the wall so that the
+            normal there doesn't flip between the two from one hit to the next.
+        */
+        let on_cap = dist < local_point.y * local_point.y - EPSILON;
+
+        if on_cap && local_point.y >= self.maximum - EPSILON {
             return Tuple::vector(0.0, 1.0, 0.0);
         }
 
-        if dist < 1.0 && local_point.y <= self.minimum + EPSILON {
+        if on_cap && local_point.y <= self.minimum + EPSILON {
             return Tuple::vector(0.0, -1.0, 0.0);
         }
 
@@ -212,6 +244,15 @@ impl Shape for Cone {
         }
     }
 
+    fn local_contains_point(&self, local_point: Tuple) -> bool {
+        // Open cones are treated as if they were capped
+        let distance = local_point.x * local_point.x + local_point.z * local_point.z;
+
+        distance <= local_point.y * local_point.y
+            && local_point.y >= self.minimum
+            && local_point.y <= self.maximum
+    }
+
     fn bounds(&self) -> BoundingBox {
         let a = self.minimum.abs();
         let b = self.maximum.abs();
@@ -235,6 +276,10 @@ impl Shape for Cone {
         panic!("Error: edge_vectors function is not implemented for this shape")
     }
 
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn divide(self: Arc<Self>, _: usize) {}
 
     fn light_material(
@@ -378,4 +423,48 @@ mod tests {
             assert_eq!(expected_normals[i].1, normal);
         }
     }
+
+    #[test]
+    fn given_a_capped_cone_when_computing_normals_near_the_rim_should_use_the_cap_radius_at_that_height(
+    ) {
+        // Arrange
+        let cone = Arc::new(Cone::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            true,
+            0.0,
+            2.0,
+            true,
+        ));
+
+        let hit = Intersection::new(1.0, cone.clone());
+
+        // Act
+        let on_cap = cone.local_normal_at(Tuple::point(1.5, 2.0, 0.0), &hit);
+        let on_rim = cone.local_normal_at(Tuple::point(2.0, 2.0, 0.0), &hit);
+
+        // Assert
+        assert_eq!(Tuple::vector(0.0, 1.0, 0.0), on_cap);
+        assert_eq!(Tuple::vector(2.0, -2.0, 0.0), on_rim);
+        assert!(!on_rim.x.is_nan() && !on_rim.y.is_nan() && !on_rim.z.is_nan());
+    }
+
+    #[test]
+    fn given_a_tip_and_a_base_when_building_a_cone_between_them_should_open_out_towards_the_base() {
+        // Arrange
+        let tip = Tuple::point(1.0, 0.0, 0.0);
+        let base = Tuple::point(5.0, 0.0, 0.0);
+
+        // Act
+        let cone = Cone::between(tip, base, 2.0, Arc::new(Phong::default()), true, true);
+
+        // Assert
+        let bounds = cone.parent_space_bounds_of();
+
+        assert!(cone.contains_point(Tuple::point(4.9, 1.9, 0.0)));
+        assert!(!cone.contains_point(Tuple::point(1.5, 1.0, 0.0)));
+        assert!((bounds.min().x - 1.0).abs() < EPSILON);
+        assert!((bounds.max().x - 5.0).abs() < EPSILON);
+        assert!((bounds.max().y - 2.0).abs() < EPSILON);
+    }
 }
@@ -1,5 +1,5 @@
 use crate::geometry::group::Group;
-use crate::geometry::shape::Shape;
+use crate::geometry::shape::{bump_bounds_generation, Shape};
 use crate::materials::material::Material;
 use crate::materials::phong::Phong;
 use crate::matrices::matrix::Matrix;
@@ -9,6 +9,9 @@ use crate::tuples::intersection::Intersection;
 use crate::tuples::point_light::PointLight;
 use crate::tuples::ray::Ray;
 use crate::tuples::tuple::Tuple;
+use crate::EPSILON;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::Uuid;
 
@@ -20,10 +23,11 @@ pub struct CSG {
     left: Arc<dyn Shape>,
     right: Arc<dyn Shape>,
     parent: RwLock<Weak<dyn Shape>>,
-    casts_shadow: bool,
+    casts_shadow: AtomicBool,
     bounds: RwLock<Option<BoundingBox>>,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Operation {
     Difference,
     Intersection,
@@ -46,7 +50,7 @@ impl CSG {
             left: left.clone(),
             right: right.clone(),
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow: false,
+            casts_shadow: AtomicBool::new(false),
             bounds: Default::default(),
         });
 
@@ -76,43 +80,220 @@ impl CSG {
         self.right.clone()
     }
 
+    /*
+        Long chains of unions, e.g. union(a, union(b, union(c, ...))), end up testing the ray against
+        every operand whose bounds contain it, since each level only culls its own right subtree.
+        Optimizing collapses the chain into a flat list of operands and then rebuilds it as a
+        balanced tree, splitting the operands in half along the longest axis each time, so that the
+        cached bounds of each level behave like a bounding volume hierarchy.
+
+        Only unions are rearranged (since they're associative and commutative), nested unions are
+        only flattened when they have no transform of their own. Other operations keep their
+        structure but still have their operands optimized.
+
+        Note: the operands get re-parented onto the new tree, so the original shouldn't be used
+        afterwards.
+    */
+    pub fn optimize(&self) -> Arc<CSG> {
+        if self.operation != Operation::Union {
+            return CSG::new(
+                self.transform.clone(),
+                self.material.clone(),
+                self.operation,
+                CSG::optimize_operand(self.left()),
+                CSG::optimize_operand(self.right()),
+            );
+        }
+
+        let mut operands = Vec::new();
+        CSG::collect_union_operands(self.left(), &mut operands);
+        CSG::collect_union_operands(self.right(), &mut operands);
+
+        let right = CSG::split_operands(&mut operands);
+
+        CSG::new(
+            self.transform.clone(),
+            self.material.clone(),
+            Operation::Union,
+            CSG::build_union(operands),
+            CSG::build_union(right),
+        )
+    }
+
+    fn optimize_operand(shape: Arc<dyn Shape>) -> Arc<dyn Shape> {
+        match shape.as_any().downcast_ref::<CSG>() {
+            Some(csg) => csg.optimize(),
+            None => shape,
+        }
+    }
+
+    fn collect_union_operands(shape: Arc<dyn Shape>, operands: &mut Vec<Arc<dyn Shape>>) {
+        if let Some(csg) = shape.as_any().downcast_ref::<CSG>() {
+            if csg.operation == Operation::Union && *csg.transform == Matrix::identity(4) {
+                CSG::collect_union_operands(csg.left(), operands);
+                CSG::collect_union_operands(csg.right(), operands);
+                return;
+            }
+        }
+
+        operands.push(CSG::optimize_operand(shape));
+    }
+
+    fn build_union(mut operands: Vec<Arc<dyn Shape>>) -> Arc<dyn Shape> {
+        if operands.len() == 1 {
+            return operands[0].clone();
+        }
+
+        let right = CSG::split_operands(&mut operands);
+
+        CSG::default(
+            Operation::Union,
+            CSG::build_union(operands),
+            CSG::build_union(right),
+        )
+    }
+
+    // Sorts the operands by the center of their bounds along the axis they're most spread out on,
+    // then splits off the upper half
+    fn split_operands(operands: &mut Vec<Arc<dyn Shape>>) -> Vec<Arc<dyn Shape>> {
+        let centroid = |shape: &Arc<dyn Shape>| {
+            let bounds = shape.parent_space_bounds_of();
+            (bounds.min() + bounds.max()) * 0.5
+        };
+
+        let mut extents = BoundingBox::empty();
+        for shape in operands.iter() {
+            extents = extents.add_point(centroid(shape));
+        }
+
+        let size = extents.max() - extents.min();
+
+        let axis = |point: Tuple| {
+            if size.x >= size.y && size.x >= size.z {
+                point.x
+            } else if size.y >= size.z {
+                point.y
+            } else {
+                point.z
+            }
+        };
+
+        operands.sort_by(|a, b| axis(centroid(a)).total_cmp(&axis(centroid(b))));
+
+        operands.split_off(operands.len() / 2)
+    }
+
     fn find_bounds(&self) -> BoundingBox {
         BoundingBox::empty()
             + self.left().parent_space_bounds_of()
             + self.right().parent_space_bounds_of()
     }
 
+    /*
+        Either operand can be a group (e.g. a group of holes to drill out of a block), in which case
+        a hit on any of its children counts as a hit on that side. Rather than flipping a single
+        flag for each hit, keep track of which solids the ray is currently inside, so that
+        overlapping children of a group don't cancel each other out.
+
+        Where the operands share a surface (e.g. two faces lying in the same plane, or two spheres
+        touching), their hits land within rounding error of each other and would otherwise be
+        handled in whichever order they happened to come out in, which can change from one pixel
+        to the next and show up as speckles. So hits within EPSILON of each other are treated as
+        happening at the same time, and handled in a fixed order: entries before exits, so that
+        touching solids are treated as overlapping, and the right operand before the left, so that
+        a face shared with something being cut away is cut away too.
+    */
     pub fn filter_intersections(&self, intersections: Vec<Intersection>) -> Vec<Intersection> {
         // Begin outside of both children
-        let mut inl = false;
-        let mut inr = false;
+        let mut inside_left: Vec<Uuid> = Vec::new();
+        let mut inside_right: Vec<Uuid> = Vec::new();
 
         // Prepare a list to receive the filtered intersections
         let mut result: Vec<Intersection> = Vec::new();
 
-        for intersection in intersections {
-            // if i.object is part of the "left" child, then lhit is true
-            let lhit = self.left().includes(&intersection.object());
+        let mut remaining = intersections.into_iter().peekable();
 
-            if CSG::intersection_allowed(&self.operation, lhit, inl, inr) {
-                result.push(intersection);
+        while let Some(first) = remaining.next() {
+            let start = first.time();
+            let mut cluster = vec![first];
+
+            while let Some(next) = remaining.next_if(|next| next.time() - start < EPSILON) {
+                cluster.push(next);
             }
 
-            // Depending on which object was hit, toggle either inl or inr
-            if lhit {
-                inl = !inl;
-            } else {
-                inr = !inr;
+            // Tag each hit with which side it's on, the solid it belongs to, and whether the ray
+            // is going into it or out of it
+            let mut cluster: Vec<(Intersection, bool, Uuid, bool)> = cluster
+                .into_iter()
+                .map(|intersection| {
+                    // if i.object is part of the "left" child, then lhit is true
+                    let lhit = self.left().includes(&intersection.object());
+
+                    let (id, inside) = if lhit {
+                        (
+                            CSG::solid_containing(&self.left, &intersection.object()),
+                            &inside_left,
+                        )
+                    } else {
+                        (
+                            CSG::solid_containing(&self.right, &intersection.object()),
+                            &inside_right,
+                        )
+                    };
+
+                    let exiting = inside.contains(&id);
+
+                    (intersection, lhit, id, exiting)
+                })
+                .collect();
+
+            // Stable, so a solid hit twice within the cluster (e.g. a grazing ray) keeps its order
+            cluster.sort_by_key(|(_, lhit, _, exiting)| (*exiting, *lhit));
+
+            for (intersection, lhit, id, _) in cluster {
+                let inl = !inside_left.is_empty();
+                let inr = !inside_right.is_empty();
+
+                if CSG::intersection_allowed(&self.operation, lhit, inl, inr) {
+                    result.push(intersection);
+                }
+
+                // Depending on which object was hit, either enter or exit that child
+                let inside = if lhit {
+                    &mut inside_left
+                } else {
+                    &mut inside_right
+                };
+
+                if let Some(index) = inside.iter().position(|other| *other == id) {
+                    inside.remove(index);
+                } else {
+                    inside.push(id);
+                }
             }
         }
 
         result
     }
 
-    // op is the operation being evaluated
-    // lhit is true if the left shape was hit, and false if the right shape was hit
-    // inl is true if the hit occurs inside the left shape
-    // inr is true if the hit occurs inside the right shape
+    // Finds which solid within the operand the object belongs to. Groups are just collections of
+    // separate solids (possibly split up further into subgroups), so look through them for the
+    // child holding the object. Anything else is a single solid, e.g. a CSG shape only reports
+    // hits on its outer boundary, so it's entered and exited as a whole.
+    fn solid_containing(operand: &Arc<dyn Shape>, object: &Arc<dyn Shape>) -> Uuid {
+        if let Some(group) = operand.as_any().downcast_ref::<Group>() {
+            for i in 0..group.num_of_children() {
+                let child = group.get_child(i).unwrap();
+
+                if child.clone().includes(object) {
+                    return CSG::solid_containing(&child, object);
+                }
+            }
+        }
+
+        operand.id()
+    }
+
     pub fn intersection_allowed(op: &Operation, lhit: bool, inl: bool, inr: bool) -> bool {
         match op {
             Operation::Difference => {
@@ -133,6 +314,13 @@ impl CSG {
             }
         }
     }
+
+    // The child's transform takes it from the CSG's object space into its own
+    fn child_contains_point(child: &Arc<dyn Shape>, local_point: Tuple) -> bool {
+        let inverse_transform = child.get_transform().inverse().unwrap();
+
+        child.local_contains_point((&inverse_transform * &local_point).unwrap())
+    }
 }
 
 impl Shape for CSG {
@@ -180,13 +368,41 @@ impl Shape for CSG {
     }
 
     fn casts_shadow(&self) -> bool {
-        self.casts_shadow
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
+
+        // Children are the ones that actually get hit, so pass it on down to them
+        self.left.set_casts_shadow(casts_shadow);
+        self.right.set_casts_shadow(casts_shadow);
     }
 
     fn local_normal_at(&self, _: Tuple, _: &Intersection) -> Tuple {
         panic!("Error: Can't call local_normal_at on a csg shape")
     }
 
+    fn local_contains_point(&self, local_point: Tuple) -> bool {
+        let inl = CSG::child_contains_point(&self.left, local_point);
+        let inr = CSG::child_contains_point(&self.right, local_point);
+
+        match self.operation {
+            Operation::Difference => inl && !inr,
+            Operation::Intersection => inl && inr,
+            Operation::Union => inl || inr,
+        }
+    }
+
+    fn invalidate_bounds(&self) {
+        *self.bounds.write().unwrap() = None;
+
+        match self.get_parent() {
+            Some(parent) => parent.invalidate_bounds(),
+            None => bump_bounds_generation(),
+        }
+    }
+
     fn bounds(&self) -> BoundingBox {
         {
             // Wrap this in its own scope so the read lock gets dropped before we potentially acquire
@@ -213,6 +429,10 @@ impl Shape for CSG {
         panic!("Error: edge_vectors function is not implemented for this shape")
     }
 
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn divide(self: Arc<Self>, threshold: usize) {
         self.left().divide(threshold);
         self.right().divide(threshold);
@@ -236,6 +456,8 @@ mod tests {
     use crate::geometry::csg::Operation::{Difference, Intersection, Union};
     use crate::geometry::csg::CSG;
     use crate::geometry::cube::Cube;
+    use crate::geometry::cylinder::Cylinder;
+    use crate::geometry::group::Group;
     use crate::geometry::shape::Shape;
     use crate::geometry::sphere::Sphere;
     use crate::geometry::test_shape::TestShape;
@@ -477,4 +699,260 @@ mod tests {
         assert!(left.saved_ray().is_some());
         assert!(right.saved_ray().is_some());
     }
+
+    #[test]
+    fn given_a_cube_with_a_sphere_carved_out_when_checking_points_should_report_the_hole_as_outside(
+    ) {
+        // Arrange
+        let cube: Arc<dyn Shape> = Arc::new(Cube::default());
+        let sphere: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(Matrix::scaling(0.5, 0.5, 0.5)),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        let shape = CSG::default(Difference, cube, sphere);
+
+        // Act
+        let carved_out = shape.contains_point(Tuple::origin());
+        let remaining = shape.contains_point(Tuple::point(0.9, 0.9, 0.9));
+        let beyond = shape.contains_point(Tuple::point(1.5, 0.0, 0.0));
+
+        // Assert
+        assert!(!carved_out);
+        assert!(remaining);
+        assert!(!beyond);
+    }
+
+    #[test]
+    fn given_a_block_minus_a_group_of_cylinders_when_intersecting_should_drill_three_holes() {
+        // Arrange
+        let block: Arc<dyn Shape> = Arc::new(Cube::new(
+            Arc::new(Matrix::scaling(4.0, 1.0, 1.0)),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        // The group's transform moves the holes to x = -2, 0 and 2
+        let holes = Arc::new(Group::new(Arc::new(Matrix::translation(1.0, 0.0, 0.0))));
+
+        for x in [-3.0, -1.0, 1.0] {
+            holes.add_child(Arc::new(Cylinder::new(
+                Arc::new(
+                    (&Matrix::translation(x, 0.0, 0.0) * &Matrix::scaling(0.5, 1.0, 0.5)).unwrap(),
+                ),
+                Arc::new(Phong::default()),
+                true,
+                -2.0,
+                2.0,
+                true,
+            )));
+        }
+
+        let shape = CSG::default(Difference, block, holes);
+
+        let along = Ray::new(Tuple::point(-10.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0));
+        let down_a_hole = Ray::new(Tuple::point(2.0, 5.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        let down_the_block = Ray::new(Tuple::point(3.0, 5.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+
+        // Act
+        let along_hits = shape.clone().intersect(&along);
+        let hole_hits = shape.clone().intersect(&down_a_hole);
+        let block_hits = shape.clone().intersect(&down_the_block);
+
+        // Assert
+        let times: Vec<f64> = along_hits.iter().map(|i| i.time()).collect();
+
+        assert_eq!(vec![6.0, 7.5, 8.5, 9.5, 10.5, 11.5, 12.5, 14.0], times);
+        assert!(hole_hits.is_empty());
+        assert_eq!(2, block_hits.len());
+        assert_eq!(
+            Tuple::vector(-1.0, 0.0, 0.0),
+            along_hits[1]
+                .object()
+                .normal_at(along.position(along_hits[1].time()), &along_hits[1])
+        );
+    }
+
+    #[test]
+    fn given_a_block_minus_a_group_of_overlapping_cylinders_when_intersecting_through_the_overlap_should_find_no_hits(
+    ) {
+        // Arrange
+        let block: Arc<dyn Shape> = Arc::new(Cube::default());
+        let holes = Arc::new(Group::default());
+
+        for x in [0.0, 0.5] {
+            holes.add_child(Arc::new(Cylinder::new(
+                Arc::new(
+                    (&Matrix::translation(x, 0.0, 0.0) * &Matrix::scaling(0.5, 1.0, 0.5)).unwrap(),
+                ),
+                Arc::new(Phong::default()),
+                true,
+                -2.0,
+                2.0,
+                true,
+            )));
+        }
+
+        let shape = CSG::default(Difference, block, holes);
+
+        let ray = Ray::new(Tuple::point(0.25, 5.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+
+        // Act
+        let result = shape.intersect(&ray);
+
+        // Assert
+        assert!(result.is_empty());
+    }
+
+    // Builds union(a, union(b, union(c, ...))) from the given shapes
+    fn union_chain(mut shapes: Vec<Arc<dyn Shape>>) -> Arc<dyn Shape> {
+        let mut result = shapes.pop().unwrap();
+
+        while let Some(shape) = shapes.pop() {
+            result = CSG::default(Union, shape, result);
+        }
+
+        result
+    }
+
+    #[test]
+    fn given_a_chain_of_unions_when_optimizing_should_produce_the_same_intersections() {
+        // Arrange
+        let spheres: Vec<Arc<dyn Shape>> = (0..8)
+            .map(|i| {
+                let shape: Arc<dyn Shape> = Arc::new(Sphere::new(
+                    Arc::new(Matrix::translation(i as f64 * 1.5, 0.0, 0.0)),
+                    Arc::new(Phong::default()),
+                    true,
+                ));
+                shape
+            })
+            .collect();
+
+        let chained = union_chain(spheres);
+
+        let rays = [
+            Ray::new(Tuple::point(-5.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0)),
+            Ray::new(Tuple::point(3.0, -5.0, 0.0), Tuple::vector(0.0, 1.0, 0.0)),
+            Ray::new(
+                Tuple::point(-2.0, -2.0, -5.0),
+                Tuple::vector(1.0, 0.4, 1.0).normalize(),
+            ),
+        ];
+
+        let expected: Vec<Vec<(f64, Arc<dyn Shape>)>> = rays
+            .iter()
+            .map(|ray| {
+                chained
+                    .clone()
+                    .intersect(ray)
+                    .iter()
+                    .map(|i| (i.time(), i.object()))
+                    .collect()
+            })
+            .collect();
+
+        // Act
+        let optimized = chained.as_any().downcast_ref::<CSG>().unwrap().optimize();
+
+        // Assert
+        for (ray, expected) in rays.iter().zip(expected) {
+            let result = optimized.clone().intersect(ray);
+
+            assert!(!expected.is_empty());
+            assert_eq!(expected.len(), result.len());
+
+            for ((time, object), intersection) in expected.iter().zip(result) {
+                assert_eq!(*time, intersection.time());
+                assert!(Arc::ptr_eq(object, &intersection.object()));
+            }
+        }
+    }
+
+    #[test]
+    fn given_a_chain_of_unions_when_optimizing_should_test_fewer_shapes_for_a_missing_ray() {
+        // Arrange
+        let shapes: Vec<Arc<TestShape>> = (0..16)
+            .map(|i| {
+                Arc::new(TestShape::new_with_transform(Arc::new(
+                    Matrix::translation(i as f64 * 3.0, 0.0, 0.0),
+                )))
+            })
+            .collect();
+
+        let chained = union_chain(
+            shapes
+                .iter()
+                .map(|s| {
+                    let shape: Arc<dyn Shape> = s.clone();
+                    shape
+                })
+                .collect(),
+        );
+
+        // Passes through the bounds of the whole union, but in the gap between the last two shapes
+        let ray = Ray::new(Tuple::point(43.5, -5.0, 0.0), Tuple::vector(0.0, 1.0, 0.0));
+        let tests_performed = || shapes.iter().map(|s| s.intersect_count()).sum::<usize>();
+
+        // Act
+        let chained_hits = chained.clone().intersect(&ray);
+        let chained_tests = tests_performed();
+
+        let optimized = chained.as_any().downcast_ref::<CSG>().unwrap().optimize();
+
+        let optimized_hits = optimized.intersect(&ray);
+        let optimized_tests = tests_performed() - chained_tests;
+
+        // Assert
+        assert!(chained_hits.is_empty());
+        assert!(optimized_hits.is_empty());
+
+        assert_eq!(16, chained_tests);
+        assert_eq!(2, optimized_tests);
+    }
+
+    #[test]
+    fn given_the_union_of_two_touching_spheres_when_intersecting_should_consistently_merge_them() {
+        // Arrange
+        let ray = Ray::new(Tuple::point(-5.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0));
+
+        // Nudge the right sphere by less than EPSILON either way, so that which of the two hits
+        // at the touching point comes first flips around
+        let offsets = [-1e-9, 0.0, 1e-9];
+
+        // Act
+        let results: Vec<Vec<f64>> = offsets
+            .iter()
+            .map(|offset| {
+                let left = Arc::new(Sphere::new(
+                    Arc::new(Matrix::translation(-1.0, 0.0, 0.0)),
+                    Arc::new(Phong::default()),
+                    true,
+                ));
+                let right = Arc::new(Sphere::new(
+                    Arc::new(Matrix::translation(1.0 + offset, 0.0, 0.0)),
+                    Arc::new(Phong::default()),
+                    true,
+                ));
+
+                let csg = CSG::new(
+                    Arc::new(Matrix::identity(4)),
+                    Arc::new(Phong::default()),
+                    Union,
+                    left,
+                    right,
+                );
+
+                csg.intersect(&ray).iter().map(|i| i.time()).collect()
+            })
+            .collect();
+
+        // Assert
+        for times in results {
+            assert_eq!(2, times.len());
+            assert!((times[0] - 3.0).abs() < 0.0001);
+            assert!((times[1] - 7.0).abs() < 0.0001);
+        }
+    }
 }
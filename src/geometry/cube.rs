@@ -10,6 +10,8 @@ use crate::tuples::point_light::PointLight;
 use crate::tuples::ray::Ray;
 use crate::tuples::tuple::Tuple;
 use crate::EPSILON;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::Uuid;
 
@@ -18,7 +20,7 @@ pub struct Cube {
     transform: Arc<Matrix>,
     material: Arc<dyn Material>,
     parent: RwLock<Weak<dyn Shape>>,
-    casts_shadow: bool,
+    casts_shadow: AtomicBool,
 }
 
 impl Cube {
@@ -28,7 +30,7 @@ impl Cube {
             transform: Arc::new(Matrix::identity(4)),
             material: Arc::new(Phong::default()),
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow: true,
+            casts_shadow: AtomicBool::new(true),
         }
     }
 
@@ -38,7 +40,7 @@ impl Cube {
             transform,
             material,
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow,
+            casts_shadow: AtomicBool::new(casts_shadow),
         }
     }
 
@@ -67,14 +69,9 @@ impl Cube {
             (tmin, tmax)
         }
     }
-}
-
-impl Shape for Cube {
-    fn id(&self) -> Uuid {
-        self.id.clone()
-    }
 
-    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+    // The times at which the ray enters and leaves the cube, in increasing order
+    fn hit_times(local_ray: &Ray) -> Option<(f64, f64)> {
         let origin = local_ray.origin();
         let direction = local_ray.direction();
 
@@ -88,12 +85,39 @@ impl Shape for Cube {
         let tmax = f64::min(f64::min(xtmax, ytmax), ztmax);
 
         if tmin > tmax {
-            vec![]
+            None
         } else {
-            vec![
+            Some((tmin, tmax))
+        }
+    }
+}
+
+impl Shape for Cube {
+    fn id(&self) -> Uuid {
+        self.id.clone()
+    }
+
+    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+        match Cube::hit_times(local_ray) {
+            Some((tmin, tmax)) => vec![
                 Intersection::new(tmin, self.clone()),
                 Intersection::new(tmax, self),
-            ]
+            ],
+            None => vec![],
+        }
+    }
+
+    fn for_each_local_intersection(
+        self: Arc<Self>,
+        local_ray: &Ray,
+        visit: &mut dyn FnMut(&Intersection) -> bool,
+    ) -> bool {
+        match Cube::hit_times(local_ray) {
+            Some((tmin, tmax)) => {
+                visit(&Intersection::new(tmin, self.clone()))
+                    && visit(&Intersection::new(tmax, self))
+            }
+            None => true,
         }
     }
 
@@ -123,7 +147,11 @@ impl Shape for Cube {
     }
 
     fn casts_shadow(&self) -> bool {
-        self.casts_shadow
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
     }
 
     fn local_normal_at(&self, local_point: Tuple, _: &Intersection) -> Tuple {
@@ -141,6 +169,10 @@ impl Shape for Cube {
         }
     }
 
+    fn local_contains_point(&self, local_point: Tuple) -> bool {
+        local_point.x.abs() <= 1.0 && local_point.y.abs() <= 1.0 && local_point.z.abs() <= 1.0
+    }
+
     fn bounds(&self) -> BoundingBox {
         BoundingBox::new(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
     }
@@ -157,6 +189,10 @@ impl Shape for Cube {
         panic!("Error: edge_vectors function is not implemented for this shape")
     }
 
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn divide(self: Arc<Self>, _: usize) {}
 
     fn light_material(
@@ -176,9 +212,13 @@ impl Shape for Cube {
 mod tests {
     use crate::geometry::cube::Cube;
     use crate::geometry::shape::Shape;
+    use crate::materials::phong::Phong;
+    use crate::matrices::matrix::Matrix;
     use crate::tuples::intersection::Intersection;
     use crate::tuples::ray::Ray;
     use crate::tuples::tuple::Tuple;
+    use crate::EPSILON;
+    use std::f64::consts::PI;
     use std::sync::Arc;
 
     #[test]
@@ -279,4 +319,35 @@ mod tests {
             assert_eq!(expected_normals[i].1, normal);
         }
     }
+
+    #[test]
+    fn given_a_transformed_cube_when_finding_its_bounding_sphere_should_contain_every_corner_of_its_bounds(
+    ) {
+        // Arrange
+        let transform = (&(&Matrix::translation(3.0, -2.0, 1.0) * &Matrix::rotation_y(PI / 5.0))
+            .unwrap()
+            * &Matrix::scaling(1.0, 2.0, 0.5))
+            .unwrap();
+
+        let cube = Cube::new(Arc::new(transform), Arc::new(Phong::default()), true);
+
+        // Act
+        let (center, radius) = cube.bounding_sphere();
+
+        // Assert
+        let bounds = cube.parent_space_bounds_of();
+        let (min, max) = (bounds.min(), bounds.max());
+
+        for corner in 0..8 {
+            let point = Tuple::point(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+
+            assert!((point - center).magnitude() <= radius + EPSILON);
+        }
+
+        assert_eq!(Tuple::point(3.0, -2.0, 1.0), center);
+    }
 }
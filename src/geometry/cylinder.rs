@@ -10,6 +10,8 @@ use crate::tuples::point_light::PointLight;
 use crate::tuples::ray::Ray;
 use crate::tuples::tuple::Tuple;
 use crate::EPSILON;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::Uuid;
 
@@ -18,7 +20,7 @@ pub struct Cylinder {
     transform: Arc<Matrix>,
     material: Arc<dyn Material>,
     parent: RwLock<Weak<dyn Shape>>,
-    casts_shadow: bool,
+    casts_shadow: AtomicBool,
     minimum: f64,
     maximum: f64,
     closed: bool,
@@ -31,7 +33,7 @@ impl Cylinder {
             transform: Arc::new(Matrix::identity(4)),
             material: Arc::new(Phong::default()),
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow: true,
+            casts_shadow: AtomicBool::new(true),
             minimum: f64::NEG_INFINITY,
             maximum: f64::INFINITY,
             closed: false,
@@ -51,13 +53,33 @@ impl Cylinder {
             transform,
             material,
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow,
+            casts_shadow: AtomicBool::new(casts_shadow),
             minimum,
             maximum,
             closed,
         }
     }
 
+    // A cylinder running from one point to the other with the given radius, e.g. for drawing the
+    // struts of a frame or the edges of a mesh
+    pub fn between(
+        from: Tuple,
+        to: Tuple,
+        radius: f64,
+        material: Arc<dyn Material>,
+        casts_shadow: bool,
+        closed: bool,
+    ) -> Cylinder {
+        Cylinder::new(
+            Arc::new(Matrix::span_transform(from, to, radius)),
+            material,
+            casts_shadow,
+            0.0,
+            1.0,
+            closed,
+        )
+    }
+
     // Checks to see if the intersection at 't', is within a radius of 1 from the y-axis.
     fn check_cap(ray: &Ray, t: f64) -> bool {
         let origin = ray.origin();
@@ -178,24 +200,43 @@ impl Shape for Cylinder {
     }
 
     fn casts_shadow(&self) -> bool {
-        self.casts_shadow
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
     }
 
     fn local_normal_at(&self, local_point: Tuple, _: &Intersection) -> Tuple {
         // Compute the square distance from the y-axis
         let dist = local_point.x * local_point.x + local_point.z * local_point.z;
 
-        if dist < 1.0 && local_point.y >= self.maximum - EPSILON {
+        /*
+            Points on the rim lie on both the wall and a cap, and rounding can push a hit from
+            either side a hair inside or outside of the radius. So that the rim doesn't flicker
+            between the two normals (showing up as a seam), anything within EPSILON of the radius
+            is always treated as being on the wall.
+        */
+        let on_cap = dist < 1.0 - EPSILON;
+
+        if on_cap && local_point.y >= self.maximum - EPSILON {
             return Tuple::vector(0.0, 1.0, 0.0);
         }
 
-        if dist < 1.0 && local_point.y <= self.minimum + EPSILON {
+        if on_cap && local_point.y <= self.minimum + EPSILON {
             return Tuple::vector(0.0, -1.0, 0.0);
         }
 
         Tuple::vector(local_point.x, 0.0, local_point.z)
     }
 
+    fn local_contains_point(&self, local_point: Tuple) -> bool {
+        // Open cylinders are treated as if they were capped
+        let distance = local_point.x * local_point.x + local_point.z * local_point.z;
+
+        distance <= 1.0 && local_point.y >= self.minimum && local_point.y <= self.maximum
+    }
+
     fn bounds(&self) -> BoundingBox {
         BoundingBox::new(
             Tuple::point(-1.0, self.minimum, -1.0),
@@ -215,6 +256,10 @@ impl Shape for Cylinder {
         panic!("Error: edge_vectors function is not implemented for this shape")
     }
 
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn divide(self: Arc<Self>, _: usize) {}
 
     fn light_material(
@@ -443,4 +488,54 @@ mod tests {
             assert_eq!(expected_normals[i].1, normal);
         }
     }
+
+    #[test]
+    fn given_a_capped_cylinder_when_computing_the_normal_on_the_rim_should_consistently_use_the_wall(
+    ) {
+        // Arrange
+        let cylinder = Arc::new(Cylinder::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            true,
+            1.0,
+            2.0,
+            true,
+        ));
+
+        let hit = Intersection::new(1.0, cylinder.clone());
+
+        // Act
+        let exactly_on = cylinder.local_normal_at(Tuple::point(1.0, 2.0, 0.0), &hit);
+        let just_inside = cylinder.local_normal_at(Tuple::point(0.9999999, 2.0, 0.0), &hit);
+        let bottom = cylinder.local_normal_at(Tuple::point(0.0, 1.0, -1.0), &hit);
+
+        // Assert
+        for normal in [exactly_on, just_inside, bottom] {
+            assert!(!normal.x.is_nan() && !normal.y.is_nan() && !normal.z.is_nan());
+            assert_eq!(0.0, normal.y);
+        }
+
+        assert_eq!(Tuple::vector(1.0, 0.0, 0.0), exactly_on);
+        assert_eq!(Tuple::vector(0.0, 0.0, -1.0), bottom);
+    }
+
+    #[test]
+    fn given_two_points_one_above_the_other_when_building_a_cylinder_between_them_should_run_along_y(
+    ) {
+        // Arrange
+        let from = Tuple::point(0.0, 0.0, 0.0);
+        let to = Tuple::point(0.0, 3.0, 0.0);
+
+        // Act
+        let cylinder = Cylinder::between(from, to, 0.5, Arc::new(Phong::default()), true, true);
+
+        // Assert
+        let transform = cylinder.get_transform();
+        let axis = (transform.as_ref() * &Tuple::vector(0.0, 1.0, 0.0)).unwrap();
+        let bounds = cylinder.parent_space_bounds_of();
+
+        assert_eq!(Tuple::vector(0.0, 3.0, 0.0), axis);
+        assert_eq!(Tuple::point(-0.5, 0.0, -0.5), bounds.min());
+        assert_eq!(Tuple::point(0.5, 3.0, 0.5), bounds.max());
+    }
 }
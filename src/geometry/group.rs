@@ -1,7 +1,11 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::Uuid;
 
-use crate::geometry::shape::Shape;
+use crate::geometry::cube::Cube;
+use crate::geometry::instance::Instance;
+use crate::geometry::shape::{bump_bounds_generation, Shape};
 use crate::materials::material::Material;
 use crate::materials::phong::Phong;
 use crate::matrices::matrix::Matrix;
@@ -14,39 +18,122 @@ use crate::tuples::tuple::Tuple;
 
 pub struct Group {
     id: Uuid,
-    transform: Arc<Matrix>,
+    transform: RwLock<Arc<Matrix>>,
     material: Arc<dyn Material>,
     children: RwLock<Vec<Arc<dyn Shape>>>,
     parent: RwLock<Weak<dyn Shape>>,
-    casts_shadow: bool,
+    casts_shadow: AtomicBool,
     bounds: RwLock<Option<BoundingBox>>, // Lazy initialisation of bounding box for the group
+    culling_enabled: AtomicBool,
 }
 
 impl Group {
     pub fn default() -> Group {
         Group {
             id: Uuid::new_v4(),
-            transform: Arc::new(Matrix::identity(4)),
+            transform: RwLock::new(Arc::new(Matrix::identity(4))),
             material: Arc::new(Phong::default()),
             children: RwLock::new(Vec::new()),
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow: true,
+            casts_shadow: AtomicBool::new(true),
             bounds: RwLock::new(None),
+            culling_enabled: AtomicBool::new(true),
         }
     }
 
     pub fn new(transform: Arc<Matrix>) -> Group {
         Group {
             id: Uuid::new_v4(),
-            transform,
+            transform: RwLock::new(transform),
             material: Arc::new(Phong::default()),
             children: RwLock::new(Vec::new()),
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow: true,
+            casts_shadow: AtomicBool::new(true),
             bounds: RwLock::new(None),
+            culling_enabled: AtomicBool::new(true),
         }
     }
 
+    // Lays out copies of the prototype in a grid on the xz plane, with the rows running along z
+    // and the columns along x, each one spacing units apart from its neighbours. The copies are
+    // instances so the prototype itself is shared between them.
+    pub fn grid(prototype: Arc<dyn Shape>, rows: usize, cols: usize, spacing: f64) -> Arc<Group> {
+        let result = Arc::new(Group::default());
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let transform =
+                    Matrix::translation(col as f64 * spacing, 0.0, row as f64 * spacing);
+
+                result.add_child(Arc::new(Instance::new(
+                    Arc::new(transform),
+                    prototype.clone(),
+                )));
+            }
+        }
+
+        result
+    }
+
+    /*
+        Builds a wireframe of the given box out of thin cubes, one along each of its twelve edges,
+        e.g. for overlaying the bounding boxes of a scene on a render to see how it's partitioned.
+        The edges don't cast shadows, so they don't darken whatever they're drawn over.
+    */
+    pub fn wire_box(
+        bounds: BoundingBox,
+        thickness: f64,
+        material: Arc<dyn Material>,
+    ) -> Arc<Group> {
+        let result = Arc::new(Group::default());
+
+        let min = bounds.min();
+        let max = bounds.max();
+
+        let center = Tuple::point(
+            (min.x + max.x) / 2.0,
+            (min.y + max.y) / 2.0,
+            (min.z + max.z) / 2.0,
+        );
+        let half_extents = [
+            (max.x - min.x) / 2.0,
+            (max.y - min.y) / 2.0,
+            (max.z - min.z) / 2.0,
+        ];
+
+        for axis in 0..3 {
+            // Each edge runs the full length of the box along one axis, and sits at either the
+            // min or max of the other two
+            for corner in 0..4 {
+                let mut offset = [0.0; 3];
+                let mut scale = [thickness / 2.0; 3];
+
+                scale[axis] += half_extents[axis];
+
+                let (first, second) = ((axis + 1) % 3, (axis + 2) % 3);
+                offset[first] = if corner & 1 == 0 { -1.0 } else { 1.0 } * half_extents[first];
+                offset[second] = if corner & 2 == 0 { -1.0 } else { 1.0 } * half_extents[second];
+
+                let transform = (&Matrix::translation(
+                    center.x + offset[0],
+                    center.y + offset[1],
+                    center.z + offset[2],
+                ) * &Matrix::scaling(scale[0], scale[1], scale[2]))
+                    .unwrap();
+
+                result.add_child(Arc::new(Cube::new(
+                    Arc::new(transform),
+                    material.clone(),
+                    false,
+                )));
+            }
+        }
+
+        result.set_casts_shadow(false);
+
+        result
+    }
+
     pub fn add_children(self: &Arc<Self>, children: Vec<Arc<dyn Shape>>) {
         for child in children {
             self.add_child(child)
@@ -59,8 +146,32 @@ impl Group {
         child.clone().set_parent(&tmp);
         // Add it to the child list
         self.children.write().unwrap().push(child);
-        // Invalidate the stored bounds
-        *self.bounds.write().unwrap() = None;
+        // Invalidate the stored bounds, along with any of the groups this one is in
+        self.invalidate_bounds();
+    }
+
+    // Moves the group and everything in it. The children stay attached, and since the group's own
+    // bounds are in its object space only the ancestors' bounds (or the world's, if it's a top
+    // level group) need working out again.
+    pub fn set_transform(&self, transform: Arc<Matrix>) {
+        *self.transform.write().unwrap() = transform;
+
+        match self.get_parent() {
+            Some(parent) => parent.invalidate_bounds(),
+            None => bump_bounds_generation(),
+        }
+    }
+
+    // With culling disabled the children are always tested, even if the ray misses the group's
+    // bounds. Only useful for debugging, e.g. to check whether an object going missing is down to
+    // the bounds being wrong rather than the object itself.
+    pub fn set_culling_enabled(&self, culling_enabled: bool) {
+        self.culling_enabled
+            .store(culling_enabled, Ordering::SeqCst);
+    }
+
+    pub fn culling_enabled(&self) -> bool {
+        self.culling_enabled.load(Ordering::SeqCst)
     }
 
     fn find_bounds(&self) -> BoundingBox {
@@ -131,7 +242,7 @@ impl Shape for Group {
     }
 
     fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
-        if self.bounds().intersects(local_ray) {
+        if !self.culling_enabled() || self.bounds().intersects(local_ray) {
             let mut result = Vec::new();
 
             let children = self.children.read().unwrap();
@@ -153,8 +264,24 @@ impl Shape for Group {
         }
     }
 
+    fn for_each_local_intersection(
+        self: Arc<Self>,
+        local_ray: &Ray,
+        visit: &mut dyn FnMut(&Intersection) -> bool,
+    ) -> bool {
+        if self.culling_enabled() && !self.bounds().intersects(local_ray) {
+            return true;
+        }
+
+        let children = self.children.read().unwrap();
+
+        children
+            .iter()
+            .all(|child| child.clone().for_each_intersection(local_ray, visit))
+    }
+
     fn get_transform(&self) -> Arc<Matrix> {
-        self.transform.clone()
+        self.transform.read().unwrap().clone()
     }
 
     fn get_material(&self) -> Arc<dyn Material> {
@@ -186,13 +313,35 @@ impl Shape for Group {
     }
 
     fn casts_shadow(&self) -> bool {
-        self.casts_shadow
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
+
+        // Children are the ones that actually get hit, so pass it on down to them
+        for child in self.children.read().unwrap().iter() {
+            child.set_casts_shadow(casts_shadow);
+        }
     }
 
     fn local_normal_at(&self, _: Tuple, _: &Intersection) -> Tuple {
         panic!("Error: Can't call local_normal_at on a group")
     }
 
+    fn local_contains_point(&self, _: Tuple) -> bool {
+        false
+    }
+
+    fn invalidate_bounds(&self) {
+        *self.bounds.write().unwrap() = None;
+
+        match self.get_parent() {
+            Some(parent) => parent.invalidate_bounds(),
+            None => bump_bounds_generation(),
+        }
+    }
+
     fn bounds(&self) -> BoundingBox {
         {
             // Wrap this in its own scope so the read lock gets dropped before we potentially acquire
@@ -219,6 +368,10 @@ impl Shape for Group {
         panic!("Error: edge_vectors function is not implemented for this shape")
     }
 
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     // Recursively splits the bounding box for this group if the number of children passes the
     // threshold
     fn divide(self: Arc<Self>, threshold: usize) {
@@ -265,6 +418,7 @@ mod tests {
     use crate::geometry::test_shape::TestShape;
     use crate::materials::phong::Phong;
     use crate::matrices::matrix::Matrix;
+    use crate::tuples::bounding_box::BoundingBox;
     use crate::tuples::intersection::Intersection;
     use crate::tuples::ray::Ray;
     use crate::tuples::tuple::Tuple;
@@ -315,6 +469,42 @@ mod tests {
         assert_eq!(true, Arc::ptr_eq(&intersects[3].object(), &s1));
     }
 
+    #[test]
+    fn given_a_non_empty_group_when_visiting_each_intersection_should_stop_once_the_visitor_says_so(
+    ) {
+        // Arrange
+        let group = Arc::new(Group::default());
+        let s1: Arc<dyn Shape> = Arc::new(Sphere::unit());
+        let s2: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(Matrix::translation(0.0, 0.0, -3.0)),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        group.add_children(vec![s1, s2]);
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let mut all_visited = 0;
+        let finished = group.clone().for_each_local_intersection(&ray, &mut |_| {
+            all_visited += 1;
+            true
+        });
+
+        let mut visited_before_stopping = 0;
+        let stopped = !group.for_each_local_intersection(&ray, &mut |_| {
+            visited_before_stopping += 1;
+            visited_before_stopping < 3
+        });
+
+        // Assert
+        assert!(finished);
+        assert_eq!(4, all_visited);
+        assert!(stopped);
+        assert_eq!(3, visited_before_stopping);
+    }
+
     #[test]
     fn given_a_ray_when_intersecting_with_a_non_empty_transformed_group_should_reflect_group_transform_in_all_child_intersects(
     ) {
@@ -637,4 +827,97 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn given_a_prototype_when_building_a_2_by_3_grid_should_place_6_instances_at_the_right_spacing()
+    {
+        // Arrange
+        let prototype: Arc<dyn Shape> = Arc::new(Sphere::unit());
+
+        // Act
+        let grid = Group::grid(prototype, 2, 3, 2.5);
+
+        // Assert
+        assert_eq!(6, grid.num_of_children());
+
+        for row in 0..2 {
+            for col in 0..3 {
+                let child = grid.get_child(row * 3 + col).unwrap();
+
+                assert_eq!(
+                    Tuple::point(col as f64 * 2.5, 0.0, row as f64 * 2.5),
+                    child.object_to_world(Tuple::origin())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn given_a_nested_group_when_setting_its_transform_should_move_its_children_and_update_the_bounds(
+    ) {
+        // Arrange
+        let outer = Arc::new(Group::default());
+        let inner = Arc::new(Group::default());
+        let sphere: Arc<dyn Shape> = Arc::new(Sphere::unit());
+
+        inner.add_child(sphere.clone());
+        outer.add_child(inner.clone());
+
+        let ray = Ray::new(Tuple::point(5.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let bounds_before = outer.bounds();
+        let hits_before = outer.clone().intersect(&ray);
+
+        // Act
+        inner.set_transform(Arc::new(Matrix::translation(5.0, 0.0, 0.0)));
+
+        let bounds_after = outer.bounds();
+        let hits_after = outer.clone().intersect(&ray);
+
+        // Assert
+        assert_eq!(Tuple::point(-1.0, -1.0, -1.0), bounds_before.min());
+        assert!(hits_before.is_empty());
+
+        assert_eq!(Tuple::point(4.0, -1.0, -1.0), bounds_after.min());
+        assert_eq!(Tuple::point(6.0, 1.0, 1.0), bounds_after.max());
+        assert_eq!(2, hits_after.len());
+        assert_eq!(4.0, hits_after[0].time());
+        assert!(Arc::ptr_eq(&sphere, &hits_after[0].object()));
+
+        assert_eq!(1, inner.num_of_children());
+        let inner_as_shape: Arc<dyn Shape> = inner.clone();
+        assert!(Arc::ptr_eq(&inner_as_shape, &sphere.get_parent().unwrap()));
+    }
+
+    #[test]
+    fn given_a_group_with_wrong_bounds_when_culling_is_disabled_should_still_intersect_its_children(
+    ) {
+        // Arrange
+        let group = Arc::new(Group::default());
+        group.add_child(Arc::new(Sphere::new(
+            Arc::new(Matrix::translation(5.0, 0.0, 0.0)),
+            Arc::new(Phong::default()),
+            true,
+        )));
+
+        // Pretend the bounds have gone stale and no longer cover the sphere
+        *group.bounds.write().unwrap() = Some(BoundingBox::new(
+            Tuple::point(-1.0, -1.0, -1.0),
+            Tuple::point(1.0, 1.0, 1.0),
+        ));
+
+        let ray = Ray::new(Tuple::point(5.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let culled = group.clone().intersect(&ray);
+
+        group.set_culling_enabled(false);
+        let unculled = group.clone().intersect(&ray);
+
+        // Assert
+        assert!(culled.is_empty());
+        assert_eq!(2, unculled.len());
+        assert_eq!(4.0, unculled[0].time());
+        assert_eq!(6.0, unculled[1].time());
+    }
 }
@@ -0,0 +1,223 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use uuid::Uuid;
+
+use crate::geometry::group::Group;
+use crate::geometry::shape::Shape;
+use crate::materials::material::Material;
+use crate::matrices::matrix::Matrix;
+use crate::tuples::bounding_box::BoundingBox;
+use crate::tuples::color::Color;
+use crate::tuples::intersection::Intersection;
+use crate::tuples::point_light::PointLight;
+use crate::tuples::ray::Ray;
+use crate::tuples::tuple::Tuple;
+
+/*
+    Places a copy of a prototype shape into the scene with its own transform, without duplicating
+    the prototype itself. Any number of instances can share the one prototype, since unlike adding
+    it to a group the prototype never gets re-parented. Intersections with the prototype are
+    reported against the instance, so that normals take the instance's transform into account.
+
+    Note: only primitive prototypes are supported (i.e. not groups or CSG shapes), since the
+    normal is worked out by asking the prototype directly.
+*/
+pub struct Instance {
+    id: Uuid,
+    transform: Arc<Matrix>,
+    prototype: Arc<dyn Shape>,
+    parent: RwLock<Weak<dyn Shape>>,
+    casts_shadow: AtomicBool,
+}
+
+impl Instance {
+    pub fn new(transform: Arc<Matrix>, prototype: Arc<dyn Shape>) -> Instance {
+        let casts_shadow = prototype.casts_shadow();
+
+        Instance {
+            id: Uuid::new_v4(),
+            transform,
+            prototype,
+            parent: RwLock::new(Weak::<Group>::new()),
+            casts_shadow: AtomicBool::new(casts_shadow),
+        }
+    }
+
+    pub fn prototype(&self) -> Arc<dyn Shape> {
+        self.prototype.clone()
+    }
+}
+
+impl Shape for Instance {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+        let tmp: Arc<dyn Shape> = self.clone();
+
+        self.prototype
+            .clone()
+            .intersect(local_ray)
+            .iter()
+            .map(|i| Intersection::new_with_uv(i.time(), tmp.clone(), i.u(), i.v()))
+            .collect()
+    }
+
+    fn get_transform(&self) -> Arc<Matrix> {
+        self.transform.clone()
+    }
+
+    fn get_material(&self) -> Arc<dyn Material> {
+        self.prototype.get_material()
+    }
+
+    fn get_parent(&self) -> Option<Arc<dyn Shape>> {
+        self.parent.read().unwrap().upgrade()
+    }
+
+    fn set_parent(&self, parent: &Arc<dyn Shape>) {
+        *self.parent.write().unwrap() = Arc::downgrade(parent);
+    }
+
+    fn includes(self: Arc<Self>, other: &Arc<dyn Shape>) -> bool {
+        let tmp: Arc<dyn Shape> = self;
+        Arc::ptr_eq(&tmp, other)
+    }
+
+    fn num_of_children(&self) -> usize {
+        0
+    }
+
+    fn casts_shadow(&self) -> bool {
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
+    }
+
+    // The local point is in the instance's space, which is the prototype's parent space, so take
+    // it the rest of the way into the prototype's object space and then bring the normal back out
+    fn local_normal_at(&self, local_point: Tuple, hit: &Intersection) -> Tuple {
+        let inverse_transform = self.prototype.get_transform().inverse().unwrap();
+
+        let object_point = (&inverse_transform * &local_point).unwrap();
+        let object_normal = self.prototype.local_normal_at(object_point, hit);
+
+        let mut result = (&inverse_transform.transpose() * &object_normal).unwrap();
+        result.w = 0.0;
+
+        result.normalize()
+    }
+
+    fn local_contains_point(&self, local_point: Tuple) -> bool {
+        let inverse_transform = self.prototype.get_transform().inverse().unwrap();
+
+        self.prototype
+            .local_contains_point((&inverse_transform * &local_point).unwrap())
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.prototype.parent_space_bounds_of()
+    }
+
+    fn points(&self) -> (Tuple, Tuple, Tuple) {
+        panic!("Error: points function is not implemented for this shape")
+    }
+
+    fn normals(&self) -> (Tuple, Tuple, Tuple) {
+        panic!("Error: normals function is not implemented for this shape")
+    }
+
+    fn edge_vectors(&self) -> (Tuple, Tuple) {
+        panic!("Error: edge_vectors function is not implemented for this shape")
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn divide(self: Arc<Self>, _: usize) {}
+
+    fn light_material(
+        self: Arc<Self>,
+        world_point: Tuple,
+        light: PointLight,
+        eyev: Tuple,
+        normalv: Tuple,
+        in_shadow: bool,
+    ) -> Color {
+        self.get_material()
+            .lighting(self, light, world_point, eyev, normalv, in_shadow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::instance::Instance;
+    use crate::geometry::shape::Shape;
+    use crate::geometry::sphere::Sphere;
+    use crate::materials::phong::Phong;
+    use crate::matrices::matrix::Matrix;
+    use crate::tuples::ray::Ray;
+    use crate::tuples::tuple::Tuple;
+    use std::sync::Arc;
+
+    #[test]
+    fn given_two_instances_of_a_sphere_when_intersecting_should_report_hits_against_each_instance()
+    {
+        // Arrange
+        let prototype: Arc<dyn Shape> = Arc::new(Sphere::unit());
+
+        let first: Arc<dyn Shape> = Arc::new(Instance::new(
+            Arc::new(Matrix::translation(-3.0, 0.0, 0.0)),
+            prototype.clone(),
+        ));
+        let second: Arc<dyn Shape> = Arc::new(Instance::new(
+            Arc::new(Matrix::translation(3.0, 0.0, 0.0)),
+            prototype.clone(),
+        ));
+
+        let ray = Ray::new(Tuple::point(3.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let first_hits = first.clone().intersect(&ray);
+        let second_hits = second.clone().intersect(&ray);
+
+        // Assert
+        assert!(first_hits.is_empty());
+        assert_eq!(2, second_hits.len());
+        assert_eq!(4.0, second_hits[0].time());
+        assert!(Arc::ptr_eq(&second, &second_hits[0].object()));
+        assert!(prototype.get_parent().is_none());
+    }
+
+    #[test]
+    fn given_an_instance_of_a_scaled_sphere_when_calculating_the_normal_should_include_both_transforms(
+    ) {
+        // Arrange
+        let prototype: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(Matrix::scaling(1.0, 0.5, 1.0)),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        let instance: Arc<dyn Shape> = Arc::new(Instance::new(
+            Arc::new(Matrix::translation(0.0, 1.0, 0.0)),
+            prototype.clone(),
+        ));
+
+        let ray = Ray::new(Tuple::point(0.0, 5.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        let hits = instance.clone().intersect(&ray);
+
+        // Act
+        let point = ray.position(hits[0].time());
+        let normal = instance.normal_at(point, &hits[0]);
+
+        // Assert
+        assert_eq!(3.5, hits[0].time());
+        assert_eq!(Tuple::vector(0.0, 1.0, 0.0), normal);
+    }
+}
@@ -10,6 +10,8 @@ use crate::tuples::point_light::PointLight;
 use crate::tuples::ray::Ray;
 use crate::tuples::tuple::Tuple;
 use crate::EPSILON;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::Uuid;
 
@@ -18,7 +20,7 @@ pub struct Plane {
     transform: Arc<Matrix>, // Used to translate a point from object space to world space
     material: Arc<dyn Material>,
     parent: RwLock<Weak<dyn Shape>>,
-    casts_shadow: bool,
+    casts_shadow: AtomicBool,
 }
 
 impl Plane {
@@ -28,7 +30,7 @@ impl Plane {
             transform,
             material,
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow,
+            casts_shadow: AtomicBool::new(casts_shadow),
         }
     }
 
@@ -38,17 +40,12 @@ impl Plane {
             transform: Arc::new(Matrix::identity(4)),
             material: Arc::new(Phong::default()),
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow: true,
+            casts_shadow: AtomicBool::new(true),
         }
     }
-}
 
-impl Shape for Plane {
-    fn id(&self) -> Uuid {
-        self.id.clone()
-    }
-
-    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+    // The time at which the ray crosses the plane, if it ever does
+    fn hit_time(local_ray: &Ray) -> Option<f64> {
         let origin = local_ray.origin();
         let direction = local_ray.direction();
 
@@ -64,13 +61,37 @@ impl Shape for Plane {
 
         if direction.y.abs() < EPSILON {
             // Plane is in xz therefore if there's no y slope it's parallel
-            return vec![]; // No intersections in this case
+            return None; // No intersections in this case
         }
 
         // Ray is either above or below the plane so calculate the intersection time
         let time = -origin.y / direction.y;
 
-        vec![Intersection::new(time, self)]
+        Some(time)
+    }
+}
+
+impl Shape for Plane {
+    fn id(&self) -> Uuid {
+        self.id.clone()
+    }
+
+    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+        match Plane::hit_time(local_ray) {
+            Some(time) => vec![Intersection::new(time, self)],
+            None => vec![],
+        }
+    }
+
+    fn for_each_local_intersection(
+        self: Arc<Self>,
+        local_ray: &Ray,
+        visit: &mut dyn FnMut(&Intersection) -> bool,
+    ) -> bool {
+        match Plane::hit_time(local_ray) {
+            Some(time) => visit(&Intersection::new(time, self)),
+            None => true,
+        }
     }
 
     fn get_transform(&self) -> Arc<Matrix> {
@@ -99,13 +120,21 @@ impl Shape for Plane {
     }
 
     fn casts_shadow(&self) -> bool {
-        self.casts_shadow
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
     }
 
     fn local_normal_at(&self, _: Tuple, _: &Intersection) -> Tuple {
         (&self.transform.inverse().unwrap() * &Tuple::vector(0.0, 1.0, 0.0)).unwrap()
     }
 
+    fn local_contains_point(&self, _: Tuple) -> bool {
+        false
+    }
+
     fn bounds(&self) -> BoundingBox {
         BoundingBox::new(
             Tuple::point(f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY),
@@ -124,6 +153,11 @@ impl Shape for Plane {
     fn edge_vectors(&self) -> (Tuple, Tuple) {
         panic!("Error: edge_vectors function is not implemented for this shape")
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn divide(self: Arc<Self>, _: usize) {}
 
     fn light_material(
@@ -1,15 +1,31 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::tuples::bounding_box::BoundingBox;
 use crate::tuples::color::Color;
 use crate::tuples::point_light::PointLight;
+use crate::tuples::sampling::SeededRng;
 use crate::{
     materials::material::Material,
     matrices::matrix::Matrix,
     tuples::{intersection::Intersection, ray::Ray, tuple::Tuple},
 };
 
+// Goes up whenever the bounds of a top level shape change, e.g. a group being moved or having a
+// child added to it. Anything caching bounds worked out from shapes it doesn't control (i.e. the
+// world) can compare against it to tell when they've gone stale.
+static BOUNDS_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+pub fn bounds_generation() -> usize {
+    BOUNDS_GENERATION.load(Ordering::Acquire)
+}
+
+pub fn bump_bounds_generation() {
+    BOUNDS_GENERATION.fetch_add(1, Ordering::Release);
+}
+
 pub trait Shape: Sync + Send {
     fn id(&self) -> Uuid;
 
@@ -30,6 +46,30 @@ pub trait Shape: Sync + Send {
 
     fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection>;
 
+    // Same as intersect, but hands each intersection to the visitor as it's found instead of
+    // gathering them up, in no particular order. The visitor returns whether to keep going, and
+    // this returns false if it was told to stop.
+    fn for_each_intersection(
+        self: Arc<Self>,
+        world_ray: &Ray,
+        visit: &mut dyn FnMut(&Intersection) -> bool,
+    ) -> bool {
+        let inverse_transform = self.get_transform().inverse().unwrap();
+
+        let local_ray = world_ray.transform(inverse_transform);
+
+        self.for_each_local_intersection(&local_ray, visit)
+    }
+
+    // Falls back on local_intersect, shapes that can find their hits without allocating override it
+    fn for_each_local_intersection(
+        self: Arc<Self>,
+        local_ray: &Ray,
+        visit: &mut dyn FnMut(&Intersection) -> bool,
+    ) -> bool {
+        self.local_intersect(local_ray).iter().all(visit)
+    }
+
     // Transformation matrix transforms points from object space to world space, and the inverse goes the other way.
     fn get_transform(&self) -> Arc<Matrix>;
 
@@ -47,6 +87,10 @@ pub trait Shape: Sync + Send {
 
     fn casts_shadow(&self) -> bool;
 
+    // Uses interior mutability so shadows can be switched on and off for a shape after it's been
+    // added to a scene, e.g. to see what the scene looks like without a particular shadow
+    fn set_casts_shadow(&self, casts_shadow: bool);
+
     // Assumes that the point will always be on the shape, also the hits are only used for smooth
     // triangles
     fn normal_at(&self, world_point: Tuple, hit: &Intersection) -> Tuple {
@@ -60,6 +104,16 @@ pub trait Shape: Sync + Send {
 
     fn local_normal_at(&self, local_point: Tuple, hit: &Intersection) -> Tuple;
 
+    // Checks whether the point lies inside the shape, treating it as a solid. Shapes that don't
+    // enclose a volume (e.g. planes, triangles and groups) never contain any points.
+    fn contains_point(&self, world_point: Tuple) -> bool {
+        assert!(world_point.is_point());
+
+        self.local_contains_point(self.world_to_object(world_point))
+    }
+
+    fn local_contains_point(&self, local_point: Tuple) -> bool;
+
     // Converts a point from world space to object space, recursively taking into consideration any
     // parent objects between the two spaces
     fn world_to_object(&self, point: Tuple) -> Tuple {
@@ -78,8 +132,22 @@ pub trait Shape: Sync + Send {
         (&inverse_transform * &object_point).unwrap()
     }
 
+    // Converts a point from object space to world space, the reverse of world_to_object
+    fn object_to_world(&self, point: Tuple) -> Tuple {
+        assert!(point.is_point());
+
+        let parent_point = (self.get_transform().as_ref() * &point).unwrap();
+
+        match self.get_parent() {
+            None => parent_point,
+            Some(shape) => shape.object_to_world(parent_point),
+        }
+    }
+
     // Converts a normal vector from object space to world space, recursively taking into
-    // consideration any parent objects between the two spaces
+    // consideration any parent objects between the two spaces. Mirroring transforms (i.e. with a
+    // negative determinant) don't need any special handling, since the inverse transpose flips the
+    // normal in step with the surface.
     fn normal_to_world(&self, normal: Tuple) -> Tuple {
         assert!(normal.is_vector());
 
@@ -104,12 +172,58 @@ pub trait Shape: Sync + Send {
         self.bounds().transform(self.get_transform().as_ref())
     }
 
+    // The sphere around the shape's bounding box (in its parent's space), as a center and radius.
+    // Looser than the box, but it's quicker to test against and doesn't change as the shape turns.
+    // Unbounded shapes (e.g. planes) get an infinite radius.
+    fn bounding_sphere(&self) -> (Tuple, f64) {
+        let bounds = self.parent_space_bounds_of();
+        let (min, max) = (bounds.min(), bounds.max());
+
+        if !min.is_finite() || !max.is_finite() {
+            return (Tuple::origin(), f64::INFINITY);
+        }
+
+        let center = Tuple::point(
+            (min.x + max.x) / 2.0,
+            (min.y + max.y) / 2.0,
+            (min.z + max.z) / 2.0,
+        );
+
+        (center, (max - center).magnitude())
+    }
+
+    // Drops any bounds cached for the shape, and then for each of its ancestors in turn since
+    // theirs are worked out from it. Only groups and CSG shapes cache their bounds, and once the
+    // top level shape is reached the world's are dropped too (see bounds_generation).
+    fn invalidate_bounds(&self) {
+        match self.get_parent() {
+            Some(parent) => parent.invalidate_bounds(),
+            None => bump_bounds_generation(),
+        }
+    }
+
+    // The texture coordinates at the hit, for shapes that have been given them (e.g. mesh faces
+    // read in with their UVs), so that an image can be wrapped over the surface
+    fn texture_uv_at(&self, _hit: &Intersection) -> Option<(f64, f64)> {
+        None
+    }
+
     fn points(&self) -> (Tuple, Tuple, Tuple);
 
     fn normals(&self) -> (Tuple, Tuple, Tuple);
 
     fn edge_vectors(&self) -> (Tuple, Tuple);
 
+    // Picks a random point on the surface of the shape, returning it along with the surface normal
+    // at that point (both in world space). Allows a shape to be sampled as if it were a light, so
+    // it's None for shapes that don't know how to pick a point uniformly over their surface.
+    fn sample_surface(&self, _rng: &mut SeededRng) -> Option<(Tuple, Tuple)> {
+        None
+    }
+
+    // Allows callers to downcast to the concrete shape type, e.g. to look inside a CSG tree
+    fn as_any(&self) -> &dyn Any;
+
     fn divide(self: Arc<Self>, threshold: usize);
 
     fn light_material(
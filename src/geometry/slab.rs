@@ -0,0 +1,253 @@
+use crate::geometry::group::Group;
+use crate::geometry::shape::Shape;
+use crate::materials::material::Material;
+use crate::matrices::matrix::Matrix;
+use crate::tuples::bounding_box::BoundingBox;
+use crate::tuples::color::Color;
+use crate::tuples::intersection::Intersection;
+use crate::tuples::point_light::PointLight;
+use crate::tuples::ray::Ray;
+use crate::tuples::tuple::Tuple;
+use crate::EPSILON;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use uuid::Uuid;
+
+/*
+    A plane with some thickness to it, i.e. the space between two parallel planes. The top face is
+    at y = 0 like a plane's, and the bottom one is the thickness below it. Unlike a plane it has
+    an inside, so a transparent floor made from one refracts light on the way in and out again.
+*/
+pub struct Slab {
+    id: Uuid,
+    transform: Arc<Matrix>, // Used to translate a point from object space to world space
+    material: Arc<dyn Material>,
+    parent: RwLock<Weak<dyn Shape>>,
+    casts_shadow: AtomicBool,
+    thickness: f64,
+}
+
+impl Slab {
+    pub fn new(
+        transform: Arc<Matrix>,
+        material: Arc<dyn Material>,
+        casts_shadow: bool,
+        thickness: f64,
+    ) -> Slab {
+        assert!(thickness > 0.0);
+
+        Slab {
+            id: Uuid::new_v4(),
+            transform,
+            material,
+            parent: RwLock::new(Weak::<Group>::new()),
+            casts_shadow: AtomicBool::new(casts_shadow),
+            thickness,
+        }
+    }
+
+    pub fn thickness(&self) -> f64 {
+        self.thickness
+    }
+
+    // The times at which the ray crosses the two faces, in increasing order
+    fn hit_times(&self, local_ray: &Ray) -> Option<(f64, f64)> {
+        let origin = local_ray.origin();
+        let direction = local_ray.direction();
+
+        // Same as for a plane, a ray with no y slope runs parallel to both faces and misses
+        if direction.y.abs() < EPSILON {
+            return None;
+        }
+
+        let top = -origin.y / direction.y;
+        let bottom = (-self.thickness - origin.y) / direction.y;
+
+        Some((top.min(bottom), top.max(bottom)))
+    }
+}
+
+impl Shape for Slab {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+        match self.hit_times(local_ray) {
+            Some((t1, t2)) => vec![
+                Intersection::new(t1, self.clone()),
+                Intersection::new(t2, self),
+            ],
+            None => vec![],
+        }
+    }
+
+    fn for_each_local_intersection(
+        self: Arc<Self>,
+        local_ray: &Ray,
+        visit: &mut dyn FnMut(&Intersection) -> bool,
+    ) -> bool {
+        match self.hit_times(local_ray) {
+            Some((t1, t2)) => {
+                visit(&Intersection::new(t1, self.clone())) && visit(&Intersection::new(t2, self))
+            }
+            None => true,
+        }
+    }
+
+    fn get_transform(&self) -> Arc<Matrix> {
+        self.transform.clone()
+    }
+
+    fn get_material(&self) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+
+    fn get_parent(&self) -> Option<Arc<dyn Shape>> {
+        self.parent.read().unwrap().upgrade()
+    }
+
+    fn includes(self: Arc<Self>, other: &Arc<dyn Shape>) -> bool {
+        let tmp: Arc<dyn Shape> = self;
+        Arc::ptr_eq(&tmp, other)
+    }
+
+    fn num_of_children(&self) -> usize {
+        0
+    }
+
+    fn set_parent(&self, parent: &Arc<dyn Shape>) {
+        *self.parent.write().unwrap() = Arc::downgrade(parent);
+    }
+
+    fn casts_shadow(&self) -> bool {
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
+    }
+
+    // Whichever face the point is nearer to decides which way the normal points
+    fn local_normal_at(&self, local_point: Tuple, _: &Intersection) -> Tuple {
+        if local_point.y > -self.thickness / 2.0 {
+            Tuple::vector(0.0, 1.0, 0.0)
+        } else {
+            Tuple::vector(0.0, -1.0, 0.0)
+        }
+    }
+
+    fn local_contains_point(&self, local_point: Tuple) -> bool {
+        local_point.y <= 0.0 && local_point.y >= -self.thickness
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(
+            Tuple::point(f64::NEG_INFINITY, -self.thickness, f64::NEG_INFINITY),
+            Tuple::point(f64::INFINITY, 0.0, f64::INFINITY),
+        )
+    }
+
+    fn points(&self) -> (Tuple, Tuple, Tuple) {
+        panic!("Error: points function is not implemented for this shape")
+    }
+
+    fn normals(&self) -> (Tuple, Tuple, Tuple) {
+        panic!("Error: normals function is not implemented for this shape")
+    }
+
+    fn edge_vectors(&self) -> (Tuple, Tuple) {
+        panic!("Error: edge_vectors function is not implemented for this shape")
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn divide(self: Arc<Self>, _: usize) {}
+
+    fn light_material(
+        self: Arc<Self>,
+        world_point: Tuple,
+        light: PointLight,
+        eyev: Tuple,
+        normalv: Tuple,
+        in_shadow: bool,
+    ) -> Color {
+        self.get_material()
+            .lighting(self, light, world_point, eyev, normalv, in_shadow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::shape::Shape;
+    use crate::geometry::slab::Slab;
+    use crate::materials::phong::Phong;
+    use crate::matrices::matrix::Matrix;
+    use crate::tuples::intersection::Intersection;
+    use crate::tuples::ray::Ray;
+    use crate::tuples::tuple::Tuple;
+    use std::sync::Arc;
+
+    #[test]
+    fn given_a_slab_when_intersecting_a_ray_passing_through_should_hit_both_faces() {
+        // Arrange
+        let slab: Arc<dyn Shape> = Arc::new(Slab::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            true,
+            2.0,
+        ));
+
+        let ray = Ray::new(Tuple::point(0.0, 3.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+
+        // Act
+        let result = slab.clone().local_intersect(&ray);
+
+        // Assert
+        assert_eq!(2, result.len());
+        assert_eq!(3.0, result[0].time());
+        assert_eq!(5.0, result[1].time());
+        assert!(Arc::ptr_eq(&slab, &result[0].object()));
+    }
+
+    #[test]
+    fn given_a_slab_when_intersecting_a_ray_parallel_to_the_faces_should_not_hit() {
+        // Arrange
+        let slab: Arc<dyn Shape> = Arc::new(Slab::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            true,
+            1.0,
+        ));
+        let ray = Ray::new(Tuple::point(0.0, -0.5, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let result = slab.local_intersect(&ray);
+
+        // Assert
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn given_a_slab_when_calculating_the_normal_should_point_out_of_the_nearest_face() {
+        // Arrange
+        let slab = Arc::new(Slab::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            true,
+            1.0,
+        ));
+        let hit = Intersection::new(1.0, slab.clone());
+
+        // Act
+        let top = slab.local_normal_at(Tuple::point(5.0, 0.0, -3.0), &hit);
+        let bottom = slab.local_normal_at(Tuple::point(-2.0, -1.0, 7.0), &hit);
+
+        // Assert
+        assert_eq!(Tuple::vector(0.0, 1.0, 0.0), top);
+        assert_eq!(Tuple::vector(0.0, -1.0, 0.0), bottom);
+    }
+}
@@ -1,3 +1,5 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::Uuid;
 
@@ -11,6 +13,7 @@ use crate::tuples::color::Color;
 use crate::tuples::intersection::Intersection;
 use crate::tuples::point_light::PointLight;
 use crate::tuples::ray::Ray;
+use crate::tuples::sampling::SeededRng;
 use crate::tuples::tuple::Tuple;
 use crate::EPSILON;
 
@@ -19,7 +22,7 @@ pub struct SmoothTriangle {
     transform: Arc<Matrix>,
     material: Arc<dyn Material>,
     parent: RwLock<Weak<dyn Shape>>,
-    casts_shadow: bool,
+    casts_shadow: AtomicBool,
     p1: Tuple,
     p2: Tuple,
     p3: Tuple,
@@ -28,6 +31,8 @@ pub struct SmoothTriangle {
     n1: Tuple,
     n2: Tuple,
     n3: Tuple,
+    // Texture coordinates at each of the corners, if any
+    texture_coords: Option<[(f64, f64); 3]>,
 }
 
 impl SmoothTriangle {
@@ -57,7 +62,7 @@ impl SmoothTriangle {
             transform,
             material,
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow,
+            casts_shadow: AtomicBool::new(casts_shadow),
             p1,
             p2,
             p3,
@@ -66,6 +71,7 @@ impl SmoothTriangle {
             n1,
             n2,
             n3,
+            texture_coords: None,
         }
     }
 
@@ -89,22 +95,38 @@ impl SmoothTriangle {
             true,
         )
     }
-}
 
-impl Shape for SmoothTriangle {
-    fn id(&self) -> Uuid {
-        self.id
+    // Gives each corner a texture coordinate, which are blended across the face like the normals
+    // of a smooth triangle
+    pub fn with_texture_coords(
+        mut self,
+        uv1: (f64, f64),
+        uv2: (f64, f64),
+        uv3: (f64, f64),
+    ) -> SmoothTriangle {
+        self.texture_coords = Some([uv1, uv2, uv3]);
+        self
     }
 
-    // Implementation of the Möller–Trumbore intersection algorithm which preserves u & v in the
-    // intersection
-    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+    // The area and centroid are both in object space, i.e. before the transform is applied
+    pub fn area(&self) -> f64 {
+        Tuple::cross(self.e1, self.e2).magnitude() / 2.0
+    }
+
+    pub fn centroid(&self) -> Tuple {
+        let sum = self.p1 + self.p2 + self.p3;
+
+        Tuple::point(sum.x / 3.0, sum.y / 3.0, sum.z / 3.0)
+    }
+
+    // The time and the u/v coordinates at which the ray hits the triangle, if it does
+    fn hit(&self, local_ray: &Ray) -> Option<(f64, f64, f64)> {
         let dir_cross_e2 = Tuple::cross(local_ray.direction(), self.e2);
         let det = Tuple::dot(self.e1, dir_cross_e2);
 
         // If the determinant is close to zero, then the ray is parallel to the triangle and misses
         if det.abs() < EPSILON {
-            return vec![];
+            return None;
         }
 
         let f = 1.0 / det;
@@ -113,19 +135,45 @@ impl Shape for SmoothTriangle {
         let u = f * Tuple::dot(p1_to_origin, dir_cross_e2);
 
         if u < 0.0 || u > 1.0 {
-            return vec![];
+            return None;
         }
 
         let origin_cross_e1 = Tuple::cross(p1_to_origin, self.e1);
         let v = f * Tuple::dot(local_ray.direction(), origin_cross_e1);
 
         if v < 0.0 || (u + v) > 1.0 {
-            return vec![];
+            return None;
         }
 
         let t = f * Tuple::dot(self.e2, origin_cross_e1);
 
-        vec![Intersection::new_with_uv(t, self, u, v)]
+        Some((t, u, v))
+    }
+}
+
+impl Shape for SmoothTriangle {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    // Implementation of the Möller–Trumbore intersection algorithm which preserves u & v in the
+    // intersection
+    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+        match self.hit(local_ray) {
+            Some((t, u, v)) => vec![Intersection::new_with_uv(t, self, u, v)],
+            None => vec![],
+        }
+    }
+
+    fn for_each_local_intersection(
+        self: Arc<Self>,
+        local_ray: &Ray,
+        visit: &mut dyn FnMut(&Intersection) -> bool,
+    ) -> bool {
+        match self.hit(local_ray) {
+            Some((t, u, v)) => visit(&Intersection::new_with_uv(t, self, u, v)),
+            None => true,
+        }
     }
 
     fn get_transform(&self) -> Arc<Matrix> {
@@ -154,13 +202,21 @@ impl Shape for SmoothTriangle {
     }
 
     fn casts_shadow(&self) -> bool {
-        self.casts_shadow
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
     }
 
     fn local_normal_at(&self, _: Tuple, hit: &Intersection) -> Tuple {
         (self.n2 * hit.u()) + (self.n3 * hit.v()) + (self.n1 * (1.0 - hit.u() - hit.v()))
     }
 
+    fn local_contains_point(&self, _: Tuple) -> bool {
+        false
+    }
+
     fn bounds(&self) -> BoundingBox {
         let result = BoundingBox::empty();
 
@@ -170,6 +226,16 @@ impl Shape for SmoothTriangle {
             .add_point(self.p3)
     }
 
+    fn texture_uv_at(&self, hit: &Intersection) -> Option<(f64, f64)> {
+        let [uv1, uv2, uv3] = self.texture_coords?;
+        let (u, v) = (hit.u(), hit.v());
+
+        Some((
+            uv2.0 * u + uv3.0 * v + uv1.0 * (1.0 - u - v),
+            uv2.1 * u + uv3.1 * v + uv1.1 * (1.0 - u - v),
+        ))
+    }
+
     fn points(&self) -> (Tuple, Tuple, Tuple) {
         (self.p1, self.p2, self.p3)
     }
@@ -182,6 +248,27 @@ impl Shape for SmoothTriangle {
         (self.e1, self.e2)
     }
 
+    // Same as for a flat triangle, except that the normal is interpolated across the surface
+    fn sample_surface(&self, rng: &mut SeededRng) -> Option<(Tuple, Tuple)> {
+        let r1 = rng.next_f64().sqrt();
+        let r2 = rng.next_f64();
+
+        let u = r1 * (1.0 - r2);
+        let v = r1 * r2;
+
+        let local_point = self.p1 + (self.e1 * u) + (self.e2 * v);
+        let local_normal = (self.n2 * u) + (self.n3 * v) + (self.n1 * (1.0 - u - v));
+
+        Some((
+            self.object_to_world(local_point),
+            self.normal_to_world(local_normal),
+        ))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn divide(self: Arc<Self>, _: usize) {}
 
     fn light_material(
@@ -249,4 +336,25 @@ mod tests {
         // Assert
         assert_eq!(Tuple::vector(-0.5547, 0.83205, 0.0), normal);
     }
+
+    #[test]
+    fn given_a_smooth_triangle_when_calculating_its_area_and_centroid_should_ignore_the_normals() {
+        // Arrange
+        let triangle = SmoothTriangle::default(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(2.0, 0.0, 0.0),
+            Tuple::point(0.0, 0.0, 3.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+            Tuple::vector(1.0, 0.0, 0.0),
+            Tuple::vector(-1.0, 0.0, 0.0),
+        );
+
+        // Act
+        let area = triangle.area();
+        let centroid = triangle.centroid();
+
+        // Assert
+        assert_eq!(3.0, area);
+        assert_eq!(Tuple::point(2.0 / 3.0, 0.0, 1.0), centroid);
+    }
 }
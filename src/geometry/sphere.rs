@@ -1,3 +1,6 @@
+use std::any::Any;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::Uuid;
 
@@ -6,6 +9,7 @@ use crate::geometry::group::Group;
 use crate::tuples::bounding_box::BoundingBox;
 use crate::tuples::color::Color;
 use crate::tuples::point_light::PointLight;
+use crate::tuples::sampling::SeededRng;
 use crate::{
     materials::{material::Material, phong::Phong},
     matrices::matrix::Matrix,
@@ -19,7 +23,7 @@ pub struct Sphere {
     // Using RwLock for thread safe interior mutability of a weak reference to the parent which
     // prevents reference cycles
     parent: RwLock<Weak<dyn Shape>>,
-    casts_shadow: bool,
+    casts_shadow: AtomicBool,
 }
 
 impl Sphere {
@@ -29,7 +33,7 @@ impl Sphere {
             transform: Arc::new(Matrix::identity(4)),
             material: Arc::new(Phong::default()),
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow: true,
+            casts_shadow: AtomicBool::new(true),
         }
     }
 
@@ -39,17 +43,12 @@ impl Sphere {
             transform,
             material,
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow,
+            casts_shadow: AtomicBool::new(casts_shadow),
         }
     }
-}
-
-impl Shape for Sphere {
-    fn id(&self) -> Uuid {
-        self.id.clone()
-    }
 
-    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+    // The times at which the ray enters and leaves the unit sphere, in increasing order
+    fn hit_times(local_ray: &Ray) -> Option<(f64, f64)> {
         let geometric_origin = Tuple::origin();
         let ray_direction = local_ray.direction();
         let sphere_to_ray = local_ray.origin() - geometric_origin;
@@ -61,19 +60,45 @@ impl Shape for Sphere {
         let discriminant = (b * b) - (4.0 * a * c);
 
         if discriminant < 0.0 {
-            return Vec::new();
+            return None;
         }
 
         let t1 = (-b + discriminant.sqrt()) / (a * 2.0);
         let t2 = (-b - discriminant.sqrt()) / (a * 2.0);
 
-        let i1 = Intersection::new(t1, self.clone());
-        let i2 = Intersection::new(t2, self);
-
         if t1 < t2 {
-            vec![i1, i2]
+            Some((t1, t2))
         } else {
-            vec![i2, i1]
+            Some((t2, t1))
+        }
+    }
+}
+
+impl Shape for Sphere {
+    fn id(&self) -> Uuid {
+        self.id.clone()
+    }
+
+    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+        match Sphere::hit_times(local_ray) {
+            Some((t1, t2)) => vec![
+                Intersection::new(t1, self.clone()),
+                Intersection::new(t2, self),
+            ],
+            None => Vec::new(),
+        }
+    }
+
+    fn for_each_local_intersection(
+        self: Arc<Self>,
+        local_ray: &Ray,
+        visit: &mut dyn FnMut(&Intersection) -> bool,
+    ) -> bool {
+        match Sphere::hit_times(local_ray) {
+            Some((t1, t2)) => {
+                visit(&Intersection::new(t1, self.clone())) && visit(&Intersection::new(t2, self))
+            }
+            None => true,
         }
     }
 
@@ -103,13 +128,25 @@ impl Shape for Sphere {
     }
 
     fn casts_shadow(&self) -> bool {
-        self.casts_shadow
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
     }
 
     fn local_normal_at(&self, local_point: Tuple, _: &Intersection) -> Tuple {
         local_point - Tuple::origin()
     }
 
+    fn local_contains_point(&self, local_point: Tuple) -> bool {
+        let x = local_point.x;
+        let y = local_point.y;
+        let z = local_point.z;
+
+        x * x + y * y + z * z <= 1.0
+    }
+
     fn bounds(&self) -> BoundingBox {
         BoundingBox::new(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
     }
@@ -126,6 +163,26 @@ impl Shape for Sphere {
         panic!("Error: edge_vectors function is not implemented for this shape")
     }
 
+    // Picks a point uniformly over the surface of the sphere, by choosing a uniform height and then
+    // a uniform angle around the y axis. By Archimedes' hat-box theorem this gives an even spread.
+    fn sample_surface(&self, rng: &mut SeededRng) -> Option<(Tuple, Tuple)> {
+        let y = 1.0 - 2.0 * rng.next_f64();
+        let radius = (1.0 - y * y).sqrt();
+        let phi = 2.0 * PI * rng.next_f64();
+
+        let local_point = Tuple::point(radius * phi.cos(), y, radius * phi.sin());
+        let local_normal = local_point - Tuple::origin();
+
+        Some((
+            self.object_to_world(local_point),
+            self.normal_to_world(local_normal),
+        ))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn divide(self: Arc<Self>, _: usize) {}
 
     fn light_material(
@@ -143,6 +200,7 @@ impl Shape for Sphere {
 
 #[cfg(test)]
 mod tests {
+    use crate::geometry::cube::Cube;
     use crate::patterns::solid::Solid;
     use crate::tuples::color::Color;
     use std::f64::consts;
@@ -401,6 +459,48 @@ mod tests {
         assert_eq!(expected, normal);
     }
 
+    #[test]
+    fn given_a_mirrored_sphere_when_calculating_the_normal_at_points_on_it_should_still_face_outwards(
+    ) {
+        // A negative scale turns the sphere inside out in object space, but the inverse transpose
+        // mirrors the normal right along with it, so it comes out facing away from the centre
+        let transform = &Matrix::translation(1.0, 2.0, 3.0) * &Matrix::scaling(-2.0, 1.0, 1.0);
+
+        let sphere = Arc::new(Sphere::new(
+            Arc::new(transform.unwrap()),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        let hit = Intersection::new(1.0, sphere.clone());
+
+        let centre = Tuple::point(1.0, 2.0, 3.0);
+        let points = [
+            Tuple::point(3.0, 2.0, 3.0),
+            Tuple::point(-1.0, 2.0, 3.0),
+            Tuple::point(1.0, 3.0, 3.0),
+            Tuple::point(1.0, 2.0, 2.0),
+            Tuple::point(1.0 + consts::SQRT_2, 2.0, 3.0 + consts::SQRT_2 / 2.0),
+        ];
+
+        assert!(sphere.get_transform().determinant().unwrap() < 0.0);
+
+        for point in points {
+            let normal = sphere.normal_at(point, &hit);
+
+            assert!(Tuple::dot(normal, point - centre) > 0.0);
+        }
+
+        assert_eq!(
+            Tuple::vector(1.0, 0.0, 0.0),
+            sphere.normal_at(points[0], &hit)
+        );
+        assert_eq!(
+            Tuple::vector(-1.0, 0.0, 0.0),
+            sphere.normal_at(points[1], &hit)
+        );
+    }
+
     #[test]
     fn given_a_unit_sphere_when_assigning_material_to_it_should_expect_material_to_be_set() {
         let material: Arc<dyn Material> = Arc::new(Phong::new(
@@ -420,4 +520,90 @@ mod tests {
 
         assert!(Arc::ptr_eq(&material, &result));
     }
+
+    #[test]
+    fn given_a_unit_sphere_when_sampling_its_surface_should_return_points_spread_evenly_over_it() {
+        // Arrange
+        let sphere = Sphere::unit();
+        let mut rng = SeededRng::new(1234);
+        let count = 5000;
+
+        // Act
+        let samples: Vec<(Tuple, Tuple)> = (0..count)
+            .map(|_| sphere.sample_surface(&mut rng).unwrap())
+            .collect();
+
+        // Assert
+        let mut total = Tuple::vector(0.0, 0.0, 0.0);
+
+        for (point, normal) in samples {
+            let offset = point - Tuple::origin();
+
+            assert!((offset.magnitude() - 1.0).abs() < 0.00001);
+            assert_eq!(offset, normal);
+
+            total = total + offset;
+        }
+
+        let average = total / count as f64;
+
+        assert!(average.magnitude() < 0.05);
+    }
+
+    #[test]
+    fn given_a_transformed_sphere_when_sampling_its_surface_should_return_points_in_world_space() {
+        // Arrange
+        let sphere = Sphere::new(
+            Arc::new(
+                (&Matrix::translation(0.0, 3.0, 0.0) * &Matrix::scaling(2.0, 2.0, 2.0)).unwrap(),
+            ),
+            Arc::new(Phong::default()),
+            true,
+        );
+        let mut rng = SeededRng::new(99);
+
+        // Act
+        let (point, normal) = sphere.sample_surface(&mut rng).unwrap();
+
+        // Assert
+        let offset = point - Tuple::point(0.0, 3.0, 0.0);
+
+        assert!((offset.magnitude() - 2.0).abs() < 0.00001);
+        assert_eq!(offset.normalize(), normal);
+    }
+
+    #[test]
+    fn given_a_unit_sphere_when_checking_the_origin_should_only_contain_it_if_untransformed() {
+        // Arrange
+        let sphere = Sphere::unit();
+        let translated = Sphere::new(
+            Arc::new(Matrix::translation(5.0, 0.0, 0.0)),
+            Arc::new(Phong::default()),
+            true,
+        );
+
+        // Act
+        let inside = sphere.contains_point(Tuple::origin());
+        let outside = translated.contains_point(Tuple::origin());
+
+        // Assert
+        assert!(inside);
+        assert!(!outside);
+        assert!(translated.contains_point(Tuple::point(5.5, 0.5, 0.0)));
+    }
+
+    #[test]
+    fn given_a_sphere_behind_the_trait_when_downcasting_should_only_recover_a_sphere() {
+        // Arrange
+        let shape: Arc<dyn Shape> = Arc::new(Sphere::unit());
+
+        // Act
+        let sphere = shape.as_any().downcast_ref::<Sphere>();
+        let cube = shape.as_any().downcast_ref::<Cube>();
+
+        // Assert
+        assert!(sphere.is_some());
+        assert_eq!(shape.id(), sphere.unwrap().id());
+        assert!(cube.is_none());
+    }
 }
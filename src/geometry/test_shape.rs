@@ -9,25 +9,42 @@ use crate::tuples::intersection::Intersection;
 use crate::tuples::point_light::PointLight;
 use crate::tuples::ray::Ray;
 use crate::tuples::tuple::Tuple;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::{uuid, Uuid};
 
 pub struct TestShape {
+    transform: Arc<Matrix>,
     parent: RwLock<Weak<dyn Shape>>,
     saved_ray: RwLock<Option<Ray>>,
+    intersect_count: AtomicUsize,
+    casts_shadow: AtomicBool,
 }
 
 impl TestShape {
     pub fn new() -> TestShape {
+        TestShape::new_with_transform(Arc::new(Matrix::identity(4)))
+    }
+
+    pub fn new_with_transform(transform: Arc<Matrix>) -> TestShape {
         TestShape {
+            transform,
             parent: RwLock::new(Weak::<Group>::new()),
             saved_ray: RwLock::new(None),
+            intersect_count: AtomicUsize::new(0),
+            casts_shadow: AtomicBool::new(true),
         }
     }
 
     pub fn saved_ray(&self) -> Option<Ray> {
         *self.saved_ray.read().unwrap()
     }
+
+    // Number of times the shape has been tested against a ray
+    pub fn intersect_count(&self) -> usize {
+        self.intersect_count.load(Ordering::SeqCst)
+    }
 }
 
 impl Shape for TestShape {
@@ -37,11 +54,12 @@ impl Shape for TestShape {
 
     fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
         *self.saved_ray.write().unwrap() = Some(*local_ray);
+        self.intersect_count.fetch_add(1, Ordering::SeqCst);
         vec![]
     }
 
     fn get_transform(&self) -> Arc<Matrix> {
-        Arc::new(Matrix::identity(4))
+        self.transform.clone()
     }
 
     fn get_material(&self) -> Arc<dyn Material> {
@@ -66,13 +84,21 @@ impl Shape for TestShape {
     }
 
     fn casts_shadow(&self) -> bool {
-        true
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
     }
 
     fn local_normal_at(&self, _: Tuple, _: &Intersection) -> Tuple {
         panic!("Error: can't take normal of test shape ")
     }
 
+    fn local_contains_point(&self, _: Tuple) -> bool {
+        false
+    }
+
     fn bounds(&self) -> BoundingBox {
         BoundingBox::new(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0))
     }
@@ -89,6 +115,10 @@ impl Shape for TestShape {
         panic!("Error: edge_vectors function is not implemented for this shape")
     }
 
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn divide(self: Arc<Self>, _: usize) {}
 
     fn light_material(
@@ -8,8 +8,11 @@ use crate::tuples::color::Color;
 use crate::tuples::intersection::Intersection;
 use crate::tuples::point_light::PointLight;
 use crate::tuples::ray::Ray;
+use crate::tuples::sampling::SeededRng;
 use crate::tuples::tuple::Tuple;
 use crate::EPSILON;
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock, Weak};
 use uuid::Uuid;
 
@@ -18,7 +21,7 @@ pub struct Triangle {
     transform: Arc<Matrix>,
     material: Arc<dyn Material>,
     parent: RwLock<Weak<dyn Shape>>,
-    casts_shadow: bool,
+    casts_shadow: AtomicBool,
     // Three corners that make up the triangle in object space, transforming unit triangles is hard
     p1: Tuple,
     p2: Tuple,
@@ -28,6 +31,8 @@ pub struct Triangle {
     e2: Tuple,
     // Used as the normal at every point of intersection
     normal: Tuple,
+    // Texture coordinates at each of the corners, if any
+    texture_coords: Option<[(f64, f64); 3]>,
 }
 
 impl Triangle {
@@ -52,13 +57,14 @@ impl Triangle {
             transform,
             material,
             parent: RwLock::new(Weak::<Group>::new()),
-            casts_shadow,
+            casts_shadow: AtomicBool::new(casts_shadow),
             p1,
             p2,
             p3,
             e1,
             e2,
             normal,
+            texture_coords: None,
         }
     }
 
@@ -72,21 +78,38 @@ impl Triangle {
             true,
         )
     }
-}
 
-impl Shape for Triangle {
-    fn id(&self) -> Uuid {
-        self.id
+    // Gives each corner a texture coordinate, which are blended across the face like the normals
+    // of a smooth triangle
+    pub fn with_texture_coords(
+        mut self,
+        uv1: (f64, f64),
+        uv2: (f64, f64),
+        uv3: (f64, f64),
+    ) -> Triangle {
+        self.texture_coords = Some([uv1, uv2, uv3]);
+        self
     }
 
-    // Implementation of the Möller–Trumbore intersection algorithm
-    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+    // The area and centroid are both in object space, i.e. before the transform is applied
+    pub fn area(&self) -> f64 {
+        Tuple::cross(self.e1, self.e2).magnitude() / 2.0
+    }
+
+    pub fn centroid(&self) -> Tuple {
+        let sum = self.p1 + self.p2 + self.p3;
+
+        Tuple::point(sum.x / 3.0, sum.y / 3.0, sum.z / 3.0)
+    }
+
+    // The time and the u/v coordinates at which the ray hits the triangle, if it does
+    fn hit(&self, local_ray: &Ray) -> Option<(f64, f64, f64)> {
         let dir_cross_e2 = Tuple::cross(local_ray.direction(), self.e2);
         let det = Tuple::dot(self.e1, dir_cross_e2);
 
         // If the determinant is close to zero, then the ray is parallel to the triangle and misses
         if det.abs() < EPSILON {
-            return vec![];
+            return None;
         }
 
         let f = 1.0 / det;
@@ -95,19 +118,45 @@ impl Shape for Triangle {
         let u = f * Tuple::dot(p1_to_origin, dir_cross_e2);
 
         if u < 0.0 || u > 1.0 {
-            return vec![];
+            return None;
         }
 
         let origin_cross_e1 = Tuple::cross(p1_to_origin, self.e1);
         let v = f * Tuple::dot(local_ray.direction(), origin_cross_e1);
 
         if v < 0.0 || (u + v) > 1.0 {
-            return vec![];
+            return None;
         }
 
         let t = f * Tuple::dot(self.e2, origin_cross_e1);
 
-        vec![Intersection::new(t, self)]
+        Some((t, u, v))
+    }
+}
+
+impl Shape for Triangle {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    // Implementation of the Möller–Trumbore intersection algorithm, the barycentric u & v of the hit
+    // are kept on the intersection so that per-vertex attributes can be mapped across the surface
+    fn local_intersect(self: Arc<Self>, local_ray: &Ray) -> Vec<Intersection> {
+        match self.hit(local_ray) {
+            Some((t, u, v)) => vec![Intersection::new_with_uv(t, self, u, v)],
+            None => vec![],
+        }
+    }
+
+    fn for_each_local_intersection(
+        self: Arc<Self>,
+        local_ray: &Ray,
+        visit: &mut dyn FnMut(&Intersection) -> bool,
+    ) -> bool {
+        match self.hit(local_ray) {
+            Some((t, u, v)) => visit(&Intersection::new_with_uv(t, self, u, v)),
+            None => true,
+        }
     }
 
     fn get_transform(&self) -> Arc<Matrix> {
@@ -136,13 +185,21 @@ impl Shape for Triangle {
     }
 
     fn casts_shadow(&self) -> bool {
-        self.casts_shadow
+        self.casts_shadow.load(Ordering::SeqCst)
+    }
+
+    fn set_casts_shadow(&self, casts_shadow: bool) {
+        self.casts_shadow.store(casts_shadow, Ordering::SeqCst);
     }
 
     fn local_normal_at(&self, _: Tuple, _: &Intersection) -> Tuple {
         self.normal
     }
 
+    fn local_contains_point(&self, _: Tuple) -> bool {
+        false
+    }
+
     fn bounds(&self) -> BoundingBox {
         let result = BoundingBox::empty();
 
@@ -152,6 +209,16 @@ impl Shape for Triangle {
             .add_point(self.p3)
     }
 
+    fn texture_uv_at(&self, hit: &Intersection) -> Option<(f64, f64)> {
+        let [uv1, uv2, uv3] = self.texture_coords?;
+        let (u, v) = (hit.u(), hit.v());
+
+        Some((
+            uv2.0 * u + uv3.0 * v + uv1.0 * (1.0 - u - v),
+            uv2.1 * u + uv3.1 * v + uv1.1 * (1.0 - u - v),
+        ))
+    }
+
     fn points(&self) -> (Tuple, Tuple, Tuple) {
         (self.p1, self.p2, self.p3)
     }
@@ -164,6 +231,27 @@ impl Shape for Triangle {
         (self.e1, self.e2)
     }
 
+    // Picks a point uniformly over the triangle using barycentric coordinates. Taking the square
+    // root of the first random number stops the samples bunching up towards p1.
+    fn sample_surface(&self, rng: &mut SeededRng) -> Option<(Tuple, Tuple)> {
+        let r1 = rng.next_f64().sqrt();
+        let r2 = rng.next_f64();
+
+        let u = r1 * (1.0 - r2);
+        let v = r1 * r2;
+
+        let local_point = self.p1 + (self.e1 * u) + (self.e2 * v);
+
+        Some((
+            self.object_to_world(local_point),
+            self.normal_to_world(self.normal),
+        ))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn divide(self: Arc<Self>, _: usize) {}
 
     fn light_material(
@@ -185,7 +273,9 @@ mod tests {
     use crate::geometry::triangle::Triangle;
     use crate::tuples::intersection::Intersection;
     use crate::tuples::ray::Ray;
+    use crate::tuples::sampling::SeededRng;
     use crate::tuples::tuple::Tuple;
+    use crate::EPSILON;
     use std::sync::Arc;
 
     #[test]
@@ -322,4 +412,71 @@ mod tests {
         assert_eq!(1, intersects.len());
         assert_eq!(2.0, intersects[0].time());
     }
+
+    #[test]
+    fn given_a_triangle_when_sampling_its_surface_should_return_points_inside_it() {
+        // Arrange
+        let triangle = Triangle::default(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        );
+        let mut rng = SeededRng::new(5);
+
+        // Act
+        let samples: Vec<(Tuple, Tuple)> = (0..1000)
+            .map(|_| triangle.sample_surface(&mut rng).unwrap())
+            .collect();
+
+        // Assert
+        for (point, normal) in samples {
+            assert_eq!(0.0, point.z);
+            assert!(point.y >= 0.0);
+            assert!(point.y <= 1.0 - point.x.abs() + EPSILON);
+            assert_eq!(Tuple::vector(0.0, 0.0, -1.0), normal);
+        }
+    }
+
+    #[test]
+    fn given_a_ray_that_hits_the_centroid_when_calculating_intersections_should_store_the_u_and_v()
+    {
+        // Arrange
+        let triangle = Arc::new(Triangle::default(
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::point(-1.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+        ));
+
+        let ray = Ray::new(
+            Tuple::point(0.0, 1.0 / 3.0, -2.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+        );
+
+        // Act
+        let intersects = triangle.local_intersect(&ray);
+
+        // Assert
+        assert_eq!(1, intersects.len());
+        assert!((intersects[0].u() - 1.0 / 3.0).abs() < EPSILON);
+        assert!((intersects[0].v() - 1.0 / 3.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn given_a_unit_right_triangle_when_calculating_its_area_and_centroid_should_be_half_and_the_average_of_its_points(
+    ) {
+        // Arrange
+        let triangle = Triangle::default(
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::point(1.0, 0.0, 0.0),
+            Tuple::point(0.0, 1.0, 0.0),
+        );
+
+        // Act
+        let area = triangle.area();
+        let centroid = triangle.centroid();
+
+        // Assert
+        assert_eq!(0.5, area);
+        assert_eq!(Tuple::point(1.0 / 3.0, 1.0 / 3.0, 0.0), centroid);
+    }
 }
@@ -0,0 +1,130 @@
+use crate::tuples::tuple::Tuple;
+
+/*
+    Compact storage for large triangle meshes, e.g. models loaded from OBJ files. Rather than
+    allocating a separate Triangle shape for every face, the vertices and normals are kept in
+    flat buffers and each triangle is just three indices into them. This keeps the memory used by
+    multi-million triangle models down to a fraction of what a Group of triangles would need.
+
+    Note: this only stores the geometry, it isn't a Shape in its own right.
+*/
+pub struct TriangleMesh {
+    vertices: Vec<Tuple>,
+    normals: Vec<Tuple>,
+    // Three entries per triangle, indexing into vertices
+    indices: Vec<usize>,
+    // Three entries per triangle, indexing into normals. Left empty for meshes with flat faces.
+    normal_indices: Vec<usize>,
+}
+
+impl TriangleMesh {
+    // Reserves space up front, so that the buffers don't have to keep growing (and being copied)
+    // as a large mesh gets filled in
+    pub fn with_capacity(vertices: usize, normals: usize, triangles: usize) -> TriangleMesh {
+        TriangleMesh {
+            vertices: Vec::with_capacity(vertices),
+            normals: Vec::with_capacity(normals),
+            indices: Vec::with_capacity(triangles * 3),
+            normal_indices: Vec::with_capacity(if normals > 0 { triangles * 3 } else { 0 }),
+        }
+    }
+
+    pub fn add_vertex(&mut self, vertex: Tuple) {
+        assert!(vertex.is_point());
+
+        self.vertices.push(vertex);
+    }
+
+    pub fn add_normal(&mut self, normal: Tuple) {
+        assert!(normal.is_vector());
+
+        self.normals.push(normal);
+    }
+
+    // Indices are zero based
+    pub fn add_triangle(&mut self, indices: [usize; 3]) {
+        self.indices.extend_from_slice(&indices);
+    }
+
+    pub fn add_smooth_triangle(&mut self, indices: [usize; 3], normal_indices: [usize; 3]) {
+        self.indices.extend_from_slice(&indices);
+        self.normal_indices.extend_from_slice(&normal_indices);
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn normal_count(&self) -> usize {
+        self.normals.len()
+    }
+
+    // A mesh is only smooth if every one of its triangles has normals
+    pub fn is_smooth(&self) -> bool {
+        !self.indices.is_empty() && self.normal_indices.len() == self.indices.len()
+    }
+
+    pub fn triangle(&self, index: usize) -> (Tuple, Tuple, Tuple) {
+        let i = index * 3;
+
+        (
+            self.vertices[self.indices[i]],
+            self.vertices[self.indices[i + 1]],
+            self.vertices[self.indices[i + 2]],
+        )
+    }
+
+    pub fn triangle_normals(&self, index: usize) -> Option<(Tuple, Tuple, Tuple)> {
+        if !self.is_smooth() {
+            return None;
+        }
+
+        let i = index * 3;
+
+        Some((
+            self.normals[self.normal_indices[i]],
+            self.normals[self.normal_indices[i + 1]],
+            self.normals[self.normal_indices[i + 2]],
+        ))
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::triangle_mesh::TriangleMesh;
+    use crate::tuples::tuple::Tuple;
+
+    #[test]
+    fn given_a_mesh_with_two_triangles_when_getting_each_one_should_look_up_the_shared_vertices() {
+        // Arrange
+        let mut mesh = TriangleMesh::with_capacity(4, 0, 2);
+
+        mesh.add_vertex(Tuple::point(-1.0, 1.0, 0.0));
+        mesh.add_vertex(Tuple::point(-1.0, 0.0, 0.0));
+        mesh.add_vertex(Tuple::point(1.0, 0.0, 0.0));
+        mesh.add_vertex(Tuple::point(1.0, 1.0, 0.0));
+
+        // Act
+        mesh.add_triangle([0, 1, 2]);
+        mesh.add_triangle([0, 2, 3]);
+
+        // Assert
+        assert_eq!(2, mesh.triangle_count());
+        assert!(!mesh.is_smooth());
+        assert!(mesh.triangle_normals(0).is_none());
+
+        let (p1, p2, p3) = mesh.triangle(1);
+
+        assert_eq!(Tuple::point(-1.0, 1.0, 0.0), p1);
+        assert_eq!(Tuple::point(1.0, 0.0, 0.0), p2);
+        assert_eq!(Tuple::point(1.0, 1.0, 0.0), p3);
+    }
+}
@@ -1,27 +1,45 @@
+use crate::error::RaytracerError;
 use crate::geometry::plane::Plane;
 use crate::materials::phong::Phong;
 use crate::matrices::matrix::Matrix;
+use crate::patterns::gradient::Gradient;
 use crate::patterns::solid::Solid;
 use crate::scene::camera::Camera;
+use crate::scene::render_stats::RenderStats;
 use crate::scene::world::World;
-use crate::tuples::color::Color;
+use crate::tuples::color::{Color, ColorSpace};
 use crate::tuples::point_light::PointLight;
+use crate::tuples::sampling::{SamplePattern, SeededRng};
 use crate::tuples::tuple::Tuple;
 use crate::window::canvas::Canvas;
 
+use crate::geometry::cone::Cone;
+use crate::geometry::csg::{Operation, CSG};
 use crate::geometry::cube::Cube;
+use crate::geometry::cylinder::Cylinder;
 use crate::geometry::group::Group;
+use crate::geometry::instance::Instance;
 use crate::geometry::shape::Shape;
+use crate::geometry::slab::Slab;
+use crate::geometry::smooth_triangle::SmoothTriangle;
 use crate::geometry::sphere::Sphere;
-use std::error::Error;
+use crate::geometry::triangle::Triangle;
+use crate::materials::material::Material;
 use std::f64::consts::PI;
-use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Instant;
 
 static MAX_RAY_RECURSION_DEPTH: usize = 5;
 static EPSILON: f64 = 0.00001;
-static NUM_OF_THREADS: usize = 12;
+static TILE_SIZE: usize = 32;
+static GLOSSY_REFLECTION_SAMPLES: usize = 8;
+static GI_SAMPLES_PER_PIXEL: usize = 64;
+static MAX_INTERSECTIONS_PER_RAY: usize = 10000;
+static ADAPTIVE_AA_THRESHOLD: f64 = 0.1;
 
+pub mod error;
 pub mod geometry;
 pub mod materials;
 pub mod matrices;
@@ -34,31 +52,168 @@ pub struct Config {
     pub file_path: String,
     pub width: usize,
     pub height: usize,
+    pub global_illumination: bool,
+    pub max_intersections: usize,
+    pub anti_aliasing: AntiAliasing,
+    pub aa_threshold: f64, // See render_adaptive, which is used instead when aa_max_depth is above 0
+    pub aa_max_depth: usize,
+    pub threads: usize,
+    pub clear_color: Option<Color>, // What any pixel that never gets rendered is left as, black if not set
+}
+
+// How many rays are traced through each pixel and averaged to smooth out jagged edges, as an N by
+// N grid with each one jittered within its cell (see SamplePattern::Jittered). One per axis traces
+// a single ray through the center of each pixel, without any antialiasing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AntiAliasing {
+    pub samples_per_axis: usize,
+}
+
+impl Default for AntiAliasing {
+    fn default() -> Self {
+        AntiAliasing {
+            samples_per_axis: 1,
+        }
+    }
 }
 
 impl Config {
-    pub fn new(args: &[String]) -> Result<Config, &'static str> {
+    pub fn new(args: &[String]) -> Result<Config, RaytracerError> {
         if args.len() < 4 {
-            return Err("not enough arguments");
+            return Err(RaytracerError::Invalid("not enough arguments".to_string()));
         }
 
         let file_path = args[1].clone();
-        let width = args[2].clone().parse().unwrap();
-        let height = args[3].clone().parse().unwrap();
+        let width = args[2].parse()?;
+        let height = args[3].parse()?;
+
+        // Path traced global illumination is much slower, so it has to be asked for
+        let global_illumination = args[4..].iter().any(|arg| arg == "--gi");
+
+        // Only needs raising for scenes that genuinely have that many surfaces along a single ray
+        let max_intersections = match args[4..]
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--max-intersections="))
+        {
+            Some(value) => value.parse().map_err(|_| {
+                RaytracerError::Parse("max intersections must be a whole number".to_string())
+            })?,
+            None => MAX_INTERSECTIONS_PER_RAY,
+        };
+
+        let anti_aliasing = match args[4..].iter().find_map(|arg| arg.strip_prefix("--aa=")) {
+            Some(value) => AntiAliasing {
+                samples_per_axis: value
+                    .parse()
+                    .ok()
+                    .filter(|samples_per_axis| *samples_per_axis > 0)
+                    .ok_or_else(|| {
+                        RaytracerError::Parse(
+                            "antialiasing samples per axis must be a positive whole number"
+                                .to_string(),
+                        )
+                    })?,
+            },
+            None => AntiAliasing::default(),
+        };
+
+        let aa_threshold = match args[4..]
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--aa-threshold="))
+        {
+            Some(value) => value.parse().map_err(|_| {
+                RaytracerError::Parse("antialiasing threshold must be a number".to_string())
+            })?,
+            None => ADAPTIVE_AA_THRESHOLD,
+        };
+
+        let aa_max_depth = match args[4..]
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--aa-max-depth="))
+        {
+            Some(value) => value.parse().map_err(|_| {
+                RaytracerError::Parse("antialiasing max depth must be a whole number".to_string())
+            })?,
+            None => 0,
+        };
+
+        // Either straight after the height, or anywhere as --threads N
+        let threads_arg = match args.get(4) {
+            Some(arg) if !arg.starts_with("--") => Some(arg),
+            _ => args[4..]
+                .iter()
+                .position(|arg| arg == "--threads")
+                .map(|i| {
+                    args.get(4 + i + 1).ok_or_else(|| {
+                        RaytracerError::Parse("--threads needs a number after it".to_string())
+                    })
+                })
+                .transpose()?,
+        };
+
+        let threads = match threads_arg {
+            Some(value) => value
+                .parse()
+                .ok()
+                .filter(|threads| *threads > 0)
+                .ok_or_else(|| {
+                    RaytracerError::Parse("threads must be a positive whole number".to_string())
+                })?,
+            None => default_thread_count(),
+        };
+
+        // e.g. a loud color like 1,0,1 when debugging, so that any pixel the threads never got
+        // round to stands out instead of passing for a shadow
+        let clear_color = args[4..]
+            .iter()
+            .find_map(|arg| arg.strip_prefix("--clear-color="))
+            .map(|value| {
+                let channels = value
+                    .split(',')
+                    .map(|channel| channel.trim().parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .ok()
+                    .filter(|channels| channels.len() == 3)
+                    .ok_or_else(|| {
+                        RaytracerError::Parse(
+                            "clear color must be three numbers, e.g. 1,0,1".to_string(),
+                        )
+                    })?;
+
+                Ok::<Color, RaytracerError>(Color::new(channels[0], channels[1], channels[2]))
+            })
+            .transpose()?;
 
         Ok(Config {
             file_path,
             width,
             height,
+            global_illumination,
+            max_intersections,
+            anti_aliasing,
+            aa_threshold,
+            aa_max_depth,
+            threads,
+            clear_color,
         })
     }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let world = Arc::new(build_world());
+pub fn run(config: Config) -> Result<(), RaytracerError> {
+    let mut world = build_world();
+    world.set_max_intersections(config.max_intersections);
+
+    if let Err(problems) = world.validate() {
+        return Err(RaytracerError::Invalid(format!(
+            "invalid scene:\n{}",
+            problems.join("\n")
+        )));
+    }
+
+    let world = Arc::new(world);
     let camera = Arc::new(Camera::new(
-        config.height,
         config.width,
+        config.height,
         0.785,
         Matrix::view_transform(
             Tuple::point(-6.0, 6.0, -10.0),
@@ -67,68 +222,528 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
         ),
     ));
 
-    let canvas = render(world, camera);
+    // Adaptive antialiasing traces a different number of rays through each pixel, so they're
+    // counted up as it goes
+    let rays_traced = Arc::new(AtomicUsize::new(0));
+
+    let started = Instant::now();
+
+    let threads = config.threads;
+    let clear_color = config.clear_color;
 
-    canvas.write_to_file(config.file_path)?;
+    let mut canvas = if config.global_illumination {
+        render_pixels_with_threads(
+            world,
+            camera.clone(),
+            threads,
+            clear_color,
+            gi_pixel(GI_SAMPLES_PER_PIXEL),
+        )
+    } else if config.aa_max_depth > 0 {
+        render_pixels_with_threads(
+            world,
+            camera.clone(),
+            threads,
+            clear_color,
+            adaptive_pixel(
+                config.aa_threshold,
+                config.aa_max_depth,
+                rays_traced.clone(),
+            ),
+        )
+    } else {
+        render_pixels_with_threads(
+            world,
+            camera.clone(),
+            threads,
+            clear_color,
+            antialiased_pixel(config.anti_aliasing),
+        )
+    };
+    canvas.set_warn_on_invalid_pixels(true);
+
+    let samples_per_pixel = if config.global_illumination {
+        GI_SAMPLES_PER_PIXEL as f64
+    } else if config.aa_max_depth > 0 {
+        rays_traced.load(Ordering::Relaxed) as f64 / (config.width * config.height) as f64
+    } else {
+        config.anti_aliasing.samples_per_axis.pow(2) as f64
+    };
+
+    let stats = RenderStats::new(
+        &camera,
+        samples_per_pixel,
+        MAX_RAY_RECURSION_DEPTH,
+        started.elapsed(),
+    );
+
+    canvas.write_to_file(config.file_path.clone())?;
+    stats.write_sidecar(&config)?;
 
     Ok(())
 }
 
+// One thread per core, or just the one if the number of cores can't be found out
+pub fn default_thread_count() -> usize {
+    thread::available_parallelism().map_or(1, |count| count.get())
+}
+
 pub fn render(world: Arc<World>, camera: Arc<Camera>) -> Canvas {
-    // Initialise sending channels for producer consumer
-    let (send_channel, receive_channel) = mpsc::channel();
+    render_with_threads(world, camera, default_thread_count())
+}
 
-    let width = camera.width();
-    let height = camera.height();
+// Same as render, but shared out between the given number of threads rather than one per core
+pub fn render_with_threads(world: Arc<World>, camera: Arc<Camera>, threads: usize) -> Canvas {
+    render_pixels_with_threads(world, camera, threads, None, render_pixel)
+}
 
-    let mut handles = Vec::new();
-    for i in 0..NUM_OF_THREADS {
-        // Clone send channel and scene info across to thread
-        let thread_send_channel = send_channel.clone();
-        let thread_world = world.clone();
-        let thread_camera = camera.clone();
-        let thread_number = i;
-
-        let handle = thread::spawn(move || {
-            // Set x to remainder and y to quotient
-            let mut x = thread_number % width;
-            let mut y = thread_number / width;
-
-            // Stop if we've gone past the bottom of the canvas
-            while y < height {
-                let ray = thread_camera.ray_for_pixel(x, y);
-
-                // Send back color information to main thread to then write out to canvas
-                thread_send_channel
-                    .send((x, y, thread_world.color_at(&ray, MAX_RAY_RECURSION_DEPTH)))
-                    .unwrap();
-
-                // Increment x by the num of threads and loop if we're past the end of the row
-                x += NUM_OF_THREADS;
-                if x >= width {
-                    x = x % width;
-                    y = y + 1;
-                }
-            }
+fn render_pixel(world: &World, camera: &Camera, x: usize, y: usize) -> Color {
+    let (ray, rx, ry) = camera.ray_differentials_for_pixel(x, y);
+
+    world.color_at_with_differentials(&ray, &rx, &ry, MAX_RAY_RECURSION_DEPTH)
+}
+
+// Same as render, but traces the grid of rays given by the antialiasing through each pixel and
+// averages them. The jitter is seeded from the pixel, so it comes out the same every run.
+pub fn render_with_anti_aliasing(
+    world: Arc<World>,
+    camera: Arc<Camera>,
+    anti_aliasing: AntiAliasing,
+) -> Canvas {
+    render_pixels(world, camera, antialiased_pixel(anti_aliasing))
+}
+
+fn antialiased_pixel(
+    anti_aliasing: AntiAliasing,
+) -> impl Fn(&World, &Camera, usize, usize) -> Color + Send + Sync + 'static {
+    assert!(anti_aliasing.samples_per_axis > 0);
+
+    move |world, camera, x, y| {
+        if anti_aliasing.samples_per_axis == 1 {
+            return render_pixel(world, camera, x, y);
+        }
+
+        let seed = SeededRng::from_tuple(Tuple::point(x as f64, y as f64, 0.0)).next_u64();
+        let samples = anti_aliasing.samples_per_axis * anti_aliasing.samples_per_axis;
+
+        let rays = camera.rays_for_pixel(x, y, SamplePattern::Jittered(seed), samples);
+
+        let total = rays.iter().fold(Color::black(), |total, ray| {
+            total + world.color_at(ray, MAX_RAY_RECURSION_DEPTH)
         });
 
-        handles.push(handle);
+        total / rays.len() as f64
+    }
+}
+
+/*
+    Antialiases by only tracing more rays through the pixels that need them. Each pixel starts off
+    with rays through its four corners and center, and if any channel of those colors differs by
+    more than the threshold (e.g. at an object's edge) it's split into quarters, each sampled the
+    same way, down to at most max_depth splits. Flat areas only ever get the first five rays.
+*/
+pub fn render_adaptive(
+    world: Arc<World>,
+    camera: Arc<Camera>,
+    threshold: f64,
+    max_depth: usize,
+) -> Canvas {
+    render_pixels(
+        world,
+        camera,
+        adaptive_pixel(threshold, max_depth, Arc::new(AtomicUsize::new(0))),
+    )
+}
+
+// Adds the number of rays traced for each pixel onto the given count
+fn adaptive_pixel(
+    threshold: f64,
+    max_depth: usize,
+    rays_traced: Arc<AtomicUsize>,
+) -> impl Fn(&World, &Camera, usize, usize) -> Color + Send + Sync + 'static {
+    move |world, camera, x, y| {
+        let (color, rays) = sample_adaptively(world, camera, x, y, threshold, max_depth);
+        rays_traced.fetch_add(rays, Ordering::Relaxed);
+
+        color
+    }
+}
+
+// Works out the pixel's color for render_adaptive, along with how many rays it took
+fn sample_adaptively(
+    world: &World,
+    camera: &Camera,
+    x: usize,
+    y: usize,
+    threshold: f64,
+    max_depth: usize,
+) -> (Color, usize) {
+    sample_region(world, camera, (x, y), (0.0, 0.0), 1.0, threshold, max_depth)
+}
+
+// Samples the square within the pixel with the given top left corner (as an offset from the
+// pixel's own) and size, splitting it up while its samples differ and there's depth remaining
+fn sample_region(
+    world: &World,
+    camera: &Camera,
+    pixel: (usize, usize),
+    corner: (f64, f64),
+    size: f64,
+    threshold: f64,
+    remaining: usize,
+) -> (Color, usize) {
+    let (x, y) = pixel;
+    let (left, top) = corner;
+    let offsets = [
+        (left, top),
+        (left + size, top),
+        (left, top + size),
+        (left + size, top + size),
+        (left + size / 2.0, top + size / 2.0),
+    ];
+
+    let colors: Vec<Color> = camera
+        .rays_through_pixel(x, y, &offsets)
+        .iter()
+        .map(|ray| world.color_at(ray, MAX_RAY_RECURSION_DEPTH))
+        .collect();
+
+    let channel_range = |channel: fn(&Color) -> f64| {
+        let values = colors.iter().map(channel);
+        let max = values.clone().fold(f64::MIN, f64::max);
+        let min = values.fold(f64::MAX, f64::min);
+
+        max - min
+    };
+
+    let difference = channel_range(|color| color.red)
+        .max(channel_range(|color| color.green))
+        .max(channel_range(|color| color.blue));
+
+    if difference <= threshold || remaining == 0 {
+        let total = colors
+            .iter()
+            .fold(Color::black(), |total, color| total + *color);
+
+        return (total / colors.len() as f64, colors.len());
+    }
+
+    let half = size / 2.0;
+    let quarters = [
+        (left, top),
+        (left + half, top),
+        (left, top + half),
+        (left + half, top + half),
+    ];
+
+    quarters
+        .iter()
+        .fold((Color::black(), colors.len()), |(total, rays), quarter| {
+            let (color, quarter_rays) = sample_region(
+                world,
+                camera,
+                pixel,
+                *quarter,
+                half,
+                threshold,
+                remaining - 1,
+            );
+
+            (total + color / 4.0, rays + quarter_rays)
+        })
+}
+
+// Renders with path traced global illumination (see World::color_at_gi), averaging the given
+// number of paths for each pixel. Every pixel gets its own seeded generator so the noise comes out
+// the same from one run to the next.
+pub fn render_gi(world: Arc<World>, camera: Arc<Camera>, samples: usize) -> Canvas {
+    render_pixels(world, camera, gi_pixel(samples))
+}
+
+fn gi_pixel(
+    samples: usize,
+) -> impl Fn(&World, &Camera, usize, usize) -> Color + Send + Sync + 'static {
+    move |world, camera, x, y| {
+        let ray = camera.ray_for_pixel(x, y);
+        let mut rng = SeededRng::from_tuple(Tuple::point(x as f64, y as f64, 0.0));
+
+        let mut total = Color::black();
+
+        for _ in 0..samples {
+            total = total + world.color_at_gi(&ray, MAX_RAY_RECURSION_DEPTH, &mut rng);
+        }
+
+        total / samples as f64
+    }
+}
+
+// Renders through the camera's lens (see Camera::with_depth_of_field), averaging the given number
+// of rays from across it for each pixel, so that things away from the focal distance are blurred
+pub fn render_depth_of_field(world: Arc<World>, camera: Arc<Camera>, samples: usize) -> Canvas {
+    assert!(samples > 0);
+
+    render_pixels(world, camera, move |world, camera, x, y| {
+        let mut total = Color::black();
+
+        for sample in 0..samples {
+            let ray = camera.ray_through_lens(x, y, sample);
+            total = total + world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
+        }
+
+        total / samples as f64
+    })
+}
+
+/*
+    Renders a turntable sequence for showing off a model, with the camera circling the target once
+    at the given distance and elevation (in radians, see Camera::orbit). The frames are evenly
+    spaced around the circle, starting from in front of the target along -z, and each one is the
+    size and field of view of the given camera.
+*/
+pub fn render_turntable(
+    world: Arc<World>,
+    camera: &Camera,
+    target: Tuple,
+    radius: f64,
+    elevation: f64,
+    frames: usize,
+) -> Vec<Canvas> {
+    (0..frames)
+        .map(|frame| {
+            let azimuth = 2.0 * PI * frame as f64 / frames as f64;
+            let orbiting = camera.orbit(target, radius, azimuth, elevation);
+
+            render(world.clone(), Arc::new(orbiting))
+        })
+        .collect()
+}
+
+// Writes the frames out as numbered PPMs, e.g. turntable_000.ppm, turntable_001.ppm and so on for
+// a prefix of "turntable"
+pub fn write_frames(frames: &[Canvas], prefix: &str) -> Result<(), RaytracerError> {
+    for (number, frame) in frames.iter().enumerate() {
+        frame.write_to_file(format!("{}_{:03}.ppm", prefix, number))?;
     }
 
+    Ok(())
+}
+
+/*
+    Averages the given number of renders, with every point light moved to a random spot within its
+    jitter radius (see PointLight::with_jitter_radius) for each of them. The shadows come out with
+    soft edges, since points near an edge are only in shadow for some of the light's positions.
+    Lights with no jitter radius stay put, so without any this matches a plain render.
+*/
+pub fn render_soft_shadows(world: World, camera: Arc<Camera>, samples: usize) -> Canvas {
+    assert!(samples > 0);
+
+    let lights: Vec<PointLight> = world.lights().iter().map(|light| **light).collect();
+    let mut world = Arc::new(world);
+
     let mut canvas = Canvas::new(camera.width(), camera.height());
+    canvas.set_color_space(ColorSpace::Linear);
 
-    // Expect width * height number of messages from threads
-    for _ in 0..(camera.width() * camera.height()) {
-        let received = receive_channel.recv().unwrap();
-        canvas
-            .write_pixel(received.0, received.1, received.2)
-            .unwrap();
+    for sample in 0..samples {
+        let mut rng = SeededRng::new(sample as u64);
+
+        // The threads from the last pass have all finished, so nothing else holds the world
+        Arc::get_mut(&mut world)
+            .expect("the world is only shared while rendering")
+            .set_lights(
+                lights
+                    .iter()
+                    .map(|light| Arc::new(light.jittered(&mut rng)))
+                    .collect(),
+            );
+
+        let pass = render(world.clone(), camera.clone());
+
+        // Keep a running average, the same way as RenderAccumulator
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                let previous = *canvas.pixel_at(x, y).unwrap();
+                let sample_color = *pass.pixel_at(x, y).unwrap();
+
+                let updated = previous + (sample_color - previous) / (sample + 1) as f64;
+
+                canvas.write_pixel(x, y, updated).unwrap();
+            }
+        }
     }
 
-    for handle in handles {
-        handle.join().unwrap();
+    canvas
+}
+
+/*
+    A quick preview render, which only traces every step-th pixel across and down and fills in the
+    rest of each step by step block with the same color, so it takes around 1 / step^2 of the time
+    of a full render. The canvas is still full size.
+*/
+pub fn render_coarse(world: Arc<World>, camera: Arc<Camera>, step: usize) -> Canvas {
+    render_coarse_pixels(world, camera, step, |world, camera, x, y| {
+        let (ray, rx, ry) = camera.ray_differentials_for_pixel(x, y);
+
+        world.color_at_with_differentials(&ray, &rx, &ry, MAX_RAY_RECURSION_DEPTH)
+    })
+}
+
+fn render_coarse_pixels<F>(
+    world: Arc<World>,
+    camera: Arc<Camera>,
+    step: usize,
+    color_pixel: F,
+) -> Canvas
+where
+    F: Fn(&World, &Camera, usize, usize) -> Color + Send + Sync + 'static,
+{
+    assert!(step > 0);
+
+    let width = camera.width();
+    let height = camera.height();
+
+    // The pixels at the top left of each block, i.e. the ones that are actually traced
+    let coarse = render_grid(
+        world,
+        camera,
+        width.div_ceil(step),
+        height.div_ceil(step),
+        move |world, camera, x, y| color_pixel(world, camera, x * step, y * step),
+    );
+
+    let mut canvas = Canvas::new(width, height);
+    canvas.set_color_space(ColorSpace::Linear);
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = *coarse.pixel_at(x / step, y / step).unwrap();
+
+            canvas.write_pixel(x, y, color).unwrap();
+        }
+    }
+
+    canvas
+}
+
+// Shares the pixels out between the threads, coloring each one with the given function
+fn render_pixels<F>(world: Arc<World>, camera: Arc<Camera>, color_pixel: F) -> Canvas
+where
+    F: Fn(&World, &Camera, usize, usize) -> Color + Send + Sync + 'static,
+{
+    render_pixels_with_threads(world, camera, default_thread_count(), None, color_pixel)
+}
+
+fn render_pixels_with_threads<F>(
+    world: Arc<World>,
+    camera: Arc<Camera>,
+    threads: usize,
+    clear_color: Option<Color>,
+    color_pixel: F,
+) -> Canvas
+where
+    F: Fn(&World, &Camera, usize, usize) -> Color + Send + Sync + 'static,
+{
+    let width = camera.width();
+    let height = camera.height();
+
+    render_tiles(
+        world,
+        camera,
+        width,
+        height,
+        threads,
+        clear_color,
+        color_pixel,
+    )
+}
+
+// Same as render_pixels, but for a grid of the given size rather than the camera's
+fn render_grid<F>(
+    world: Arc<World>,
+    camera: Arc<Camera>,
+    width: usize,
+    height: usize,
+    color_pixel: F,
+) -> Canvas
+where
+    F: Fn(&World, &Camera, usize, usize) -> Color + Send + Sync + 'static,
+{
+    render_tiles(
+        world,
+        camera,
+        width,
+        height,
+        default_thread_count(),
+        None,
+        color_pixel,
+    )
+}
+
+/*
+    Splits the grid up into square tiles and has the given number of threads work through them,
+    each taking the next tile nobody has started on yet until they've all been taken. Every tile
+    is handed its own parts of the pixel rows up front, so the threads write their colors straight
+    into the image without a channel or lock to contend over, or a copy of it to join back up.
+    Pixels start off as the clear color, or black if there isn't one.
+*/
+fn render_tiles<F>(
+    world: Arc<World>,
+    camera: Arc<Camera>,
+    width: usize,
+    height: usize,
+    threads: usize,
+    clear_color: Option<Color>,
+    color_pixel: F,
+) -> Canvas
+where
+    F: Fn(&World, &Camera, usize, usize) -> Color + Send + Sync + 'static,
+{
+    assert!(threads > 0);
+
+    let tiles_across = width.div_ceil(TILE_SIZE);
+    let tile_count = tiles_across * height.div_ceil(TILE_SIZE);
+
+    let mut pixels = vec![clear_color.unwrap_or(Color::black()); width * height];
+
+    // The parts of each row that fall within each tile, which are narrower along the right edge.
+    // Each tile is only ever locked by the one thread that takes it, so the locks are never waited
+    // on, they just let the tiles be shared out between the threads.
+    let mut tile_rows: Vec<Vec<&mut [Color]>> = (0..tile_count).map(|_| Vec::new()).collect();
+
+    for (y, row) in pixels.chunks_mut(width.max(1)).enumerate() {
+        for (column, part) in row.chunks_mut(TILE_SIZE).enumerate() {
+            tile_rows[(y / TILE_SIZE) * tiles_across + column].push(part);
+        }
     }
 
+    let tiles: Vec<Mutex<Vec<&mut [Color]>>> = tile_rows.into_iter().map(Mutex::new).collect();
+    let next_tile = AtomicUsize::new(0);
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+
+                if tile >= tile_count {
+                    return;
+                }
+
+                let left = (tile % tiles_across) * TILE_SIZE;
+                let top = (tile / tiles_across) * TILE_SIZE;
+
+                for (i, row) in tiles[tile].lock().unwrap().iter_mut().enumerate() {
+                    for (j, pixel) in row.iter_mut().enumerate() {
+                        *pixel = color_pixel(&world, &camera, left + j, top + i);
+                    }
+                }
+            });
+        }
+    });
+
+    // The lighting calculations are all done in linear space, so tag the canvas to make sure it
+    // gets gamma encoded when written out
+    let mut canvas = Canvas::from_row_major(width, height, pixels).unwrap();
+    canvas.set_color_space(ColorSpace::Linear);
+
     canvas
 }
 
@@ -307,17 +922,838 @@ pub fn build_world() -> World {
 
     group.clone().divide(1);
 
-    World::new(
+    let mut world = World::new(
         vec![plane, group.clone()],
         vec![
             Arc::new(PointLight::new(
                 Tuple::point(50.0, 100.0, -50.0),
                 Color::new(1.0, 1.0, 1.0),
             )),
-            Arc::new(PointLight::new(
-                Tuple::point(-400.0, 50.0, -10.0),
-                Color::new(0.2, 0.2, 0.2),
-            )),
+            // A dim fill light off to the side, which shouldn't add highlights of its own
+            Arc::new(
+                PointLight::new(Tuple::point(-400.0, 50.0, -10.0), Color::new(0.2, 0.2, 0.2))
+                    .with_specular_enabled(false),
+            ),
         ],
-    )
+    );
+
+    // Give any rays that miss a subtle sky, fading from a pale haze at the bottom up to blue at the
+    // top. The gradient runs along x, so rotate it onto y and then stretch it so that it covers the
+    // range of y values in the ray directions, i.e. -1 to 1. Can be overridden via set_background.
+    world.set_background(Some(Box::new(Gradient::new(
+        Box::new(Solid::new(Color::new(0.85, 0.9, 0.95))),
+        Box::new(Solid::new(Color::new(0.45, 0.65, 0.9))),
+        Arc::new(
+            (&(&Matrix::translation(0.0, -1.0, 0.0) * &Matrix::scaling(1.0, 2.0, 1.0)).unwrap()
+                * &Matrix::rotation_z(PI / 2.0))
+                .unwrap(),
+        ),
+    ))));
+
+    world
+}
+
+/*
+    Lines up one of each kind of shape along a floor, in front of a backdrop, so that a single
+    render touches all of the geometry code. The floor is a slab and the backdrop a plane, then
+    from left to right there's a sphere, cube, cylinder, cone, triangle, smooth triangle, CSG
+    shape (a cube with a sphere cut out of it), group (of two spheres), and an instance of a
+    sphere. Each one gets its own color so they're easy to tell apart.
+*/
+pub fn all_shapes_demo() -> (World, Camera) {
+    let material = |color: Color| -> Arc<dyn Material> {
+        Arc::new(Phong::new(
+            Box::new(Solid::new(color)),
+            0.1,
+            0.7,
+            0.3,
+            200.0,
+            0.0,
+            0.0,
+            1.0,
+        ))
+    };
+
+    // Where each shape sits along the row, and how big it is
+    let place = |x: f64, size: f64| -> Arc<Matrix> {
+        Arc::new((&Matrix::translation(x, size, 0.0) * &Matrix::scaling(size, size, size)).unwrap())
+    };
+
+    let floor = Arc::new(Slab::new(
+        Arc::new(Matrix::identity(4)),
+        material(Color::new(0.8, 0.8, 0.8)),
+        true,
+        0.5,
+    ));
+
+    let backdrop = Arc::new(Plane::new(
+        Arc::new((&Matrix::translation(0.0, 0.0, 10.0) * &Matrix::rotation_x(PI / 2.0)).unwrap()),
+        material(Color::new(0.6, 0.6, 0.7)),
+        true,
+    ));
+
+    let sphere = Arc::new(Sphere::new(
+        place(-6.0, 0.6),
+        material(Color::new(0.9, 0.2, 0.2)),
+        true,
+    ));
+
+    let cube = Arc::new(Cube::new(
+        place(-4.5, 0.5),
+        material(Color::new(0.9, 0.5, 0.1)),
+        true,
+    ));
+
+    let cylinder = Arc::new(Cylinder::new(
+        place(-3.0, 0.5),
+        material(Color::new(0.9, 0.9, 0.2)),
+        true,
+        -1.0,
+        1.0,
+        true,
+    ));
+
+    let cone = Arc::new(Cone::new(
+        place(-1.5, 0.6),
+        material(Color::new(0.4, 0.9, 0.2)),
+        true,
+        -1.0,
+        0.0,
+        true,
+    ));
+
+    let triangle = Arc::new(Triangle::new(
+        Tuple::point(-0.6, 0.0, 0.0),
+        Tuple::point(0.6, 0.0, 0.0),
+        Tuple::point(0.0, 1.2, 0.0),
+        Arc::new(Matrix::identity(4)),
+        material(Color::new(0.2, 0.9, 0.6)),
+        true,
+    ));
+
+    let smooth_triangle = Arc::new(SmoothTriangle::new(
+        Tuple::point(0.9, 0.0, 0.0),
+        Tuple::point(2.1, 0.0, 0.0),
+        Tuple::point(1.5, 1.2, 0.0),
+        Tuple::vector(-0.5, 0.0, -1.0),
+        Tuple::vector(0.5, 0.0, -1.0),
+        Tuple::vector(0.0, 0.5, -1.0),
+        Arc::new(Matrix::identity(4)),
+        material(Color::new(0.2, 0.8, 0.9)),
+        true,
+    ));
+
+    let csg = CSG::new(
+        place(3.0, 0.5),
+        material(Color::new(0.2, 0.4, 0.9)),
+        Operation::Difference,
+        Arc::new(Cube::new(
+            Arc::new(Matrix::identity(4)),
+            material(Color::new(0.2, 0.4, 0.9)),
+            true,
+        )),
+        Arc::new(Sphere::new(
+            Arc::new(Matrix::scaling(1.3, 1.3, 1.3)),
+            material(Color::new(0.9, 0.9, 0.9)),
+            true,
+        )),
+    );
+
+    let group = Arc::new(Group::new(place(4.5, 0.4)));
+    group.add_children(vec![
+        Arc::new(Sphere::new(
+            Arc::new(Matrix::identity(4)),
+            material(Color::new(0.5, 0.2, 0.9)),
+            true,
+        )),
+        Arc::new(Sphere::new(
+            Arc::new(Matrix::translation(0.0, 1.8, 0.0)),
+            material(Color::new(0.5, 0.2, 0.9)),
+            true,
+        )),
+    ]);
+
+    let prototype: Arc<dyn Shape> = Arc::new(Sphere::new(
+        Arc::new(Matrix::scaling(0.6, 0.6, 0.6)),
+        material(Color::new(0.9, 0.3, 0.7)),
+        true,
+    ));
+    let instance = Arc::new(Instance::new(
+        Arc::new(Matrix::translation(6.0, 0.6, 0.0)),
+        prototype,
+    ));
+
+    let world = World::new(
+        vec![
+            floor,
+            backdrop,
+            sphere,
+            cube,
+            cylinder,
+            cone,
+            triangle,
+            smooth_triangle,
+            csg,
+            group,
+            instance,
+        ],
+        vec![Arc::new(PointLight::new(
+            Tuple::point(-10.0, 10.0, -10.0),
+            Color::white(),
+        ))],
+    );
+
+    let camera = Camera::new(
+        64,
+        64,
+        PI / 3.0,
+        Matrix::view_transform(
+            Tuple::point(0.0, 4.0, -12.0),
+            Tuple::point(0.0, 0.5, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ),
+    );
+
+    (world, camera)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::plane::Plane;
+    use crate::geometry::shape::Shape;
+    use crate::geometry::sphere::Sphere;
+    use crate::materials::material::Material;
+    use crate::materials::phong::Phong;
+    use crate::matrices::matrix::Matrix;
+    use crate::patterns::solid::Solid;
+    use crate::scene::camera::Camera;
+    use crate::scene::render_stats::RenderStats;
+    use crate::scene::world::World;
+    use crate::tuples::color::Color;
+    use crate::tuples::intersection::Intersection;
+    use crate::tuples::point_light::PointLight;
+    use crate::tuples::ray::Ray;
+    use crate::tuples::tuple::Tuple;
+    use crate::window::canvas::Canvas;
+    use crate::{
+        all_shapes_demo, build_world, render, render_coarse, render_coarse_pixels,
+        render_depth_of_field, render_soft_shadows, render_tiles, render_turntable,
+        render_with_anti_aliasing, render_with_threads, run, sample_adaptively, AntiAliasing,
+        Config,
+    };
+    use crate::{default_thread_count, MAX_INTERSECTIONS_PER_RAY, MAX_RAY_RECURSION_DEPTH};
+    use std::f64::consts::PI;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn given_the_demo_world_when_a_ray_misses_everything_should_return_the_background_sky() {
+        // Arrange
+        let world = build_world();
+
+        let ray = Ray::new(
+            Tuple::point(0.0, 100.0, -100.0),
+            Tuple::vector(0.0, 1.0, -1.0),
+        );
+
+        // Act
+        let result = world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        assert!(world.background().is_some());
+        assert_ne!(Color::black(), result);
+    }
+
+    #[test]
+    fn given_the_all_shapes_demo_when_rendering_should_show_every_object() {
+        // Arrange
+        let (world, camera) = all_shapes_demo();
+        let world = Arc::new(world);
+        let camera = Arc::new(camera);
+
+        // Act
+        let canvas = render(world.clone(), camera.clone());
+
+        // Assert
+        let objects = world.objects();
+        let mut lit_pixels = vec![0; objects.len()];
+
+        // Work out which of the top level objects each pixel is looking at, and count up the ones
+        // that came out lit
+        for y in 0..camera.height() {
+            for x in 0..camera.width() {
+                let ray = camera.ray_for_pixel(x, y);
+                let intersections = world.intersect_world(&ray);
+
+                let Some((i, _)) = Intersection::hit(&intersections) else {
+                    continue;
+                };
+
+                let hit = intersections[i].object();
+
+                if let Some(index) = objects
+                    .iter()
+                    .position(|object| object.clone().includes(&hit))
+                {
+                    if *canvas.pixel_at(x, y).unwrap() != Color::black() {
+                        lit_pixels[index] += 1;
+                    }
+                }
+            }
+        }
+
+        assert_eq!(11, objects.len());
+        assert!(
+            lit_pixels.iter().all(|count| *count > 0),
+            "some objects didn't show up: {:?}",
+            lit_pixels
+        );
+    }
+
+    #[test]
+    fn given_an_asymmetric_scene_when_rendering_a_turntable_should_render_each_frame_from_around_it(
+    ) {
+        // Arrange
+        let (world, camera) = all_shapes_demo();
+
+        let camera = Camera::new(16, 16, camera.field_of_view(), Matrix::identity(4));
+
+        // Act
+        let frames = render_turntable(
+            Arc::new(world),
+            &camera,
+            Tuple::point(0.0, 1.0, 0.0),
+            12.0,
+            0.3,
+            4,
+        );
+
+        // Assert
+        assert_eq!(4, frames.len());
+        assert!(frames.iter().all(|frame| frame.width() == 16));
+
+        let (differing_pixels, _, _) = frames[0].diff(&frames[2]).unwrap();
+        assert!(differing_pixels > 0);
+    }
+
+    #[test]
+    fn given_a_step_of_two_when_rendering_coarsely_should_trace_a_quarter_of_the_pixels_and_fill_in_the_rest(
+    ) {
+        // Arrange
+        let world = Arc::new(build_world());
+        let camera = Arc::new(Camera::new(
+            8,
+            8,
+            PI / 3.0,
+            Matrix::view_transform(
+                Tuple::point(0.0, 1.5, -5.0),
+                Tuple::point(0.0, 1.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            ),
+        ));
+
+        let traced = Arc::new(AtomicUsize::new(0));
+        let counter = traced.clone();
+
+        // Act
+        let canvas = render_coarse_pixels(
+            world.clone(),
+            camera.clone(),
+            2,
+            move |world, camera, x, y| {
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                world.color_at(&camera.ray_for_pixel(x, y), MAX_RAY_RECURSION_DEPTH)
+            },
+        );
+
+        let coarse = render_coarse(world.clone(), camera.clone(), 2);
+
+        // Assert
+        assert_eq!(16, traced.load(Ordering::SeqCst));
+        assert_eq!(8, canvas.width());
+        assert_eq!(8, canvas.height());
+
+        // Every pixel takes the color traced at the top left of its block
+        for y in 0..8 {
+            for x in 0..8 {
+                let traced_color = world.color_at(
+                    &camera.ray_for_pixel(x - x % 2, y - y % 2),
+                    MAX_RAY_RECURSION_DEPTH,
+                );
+
+                assert_eq!(traced_color, *canvas.pixel_at(x, y).unwrap());
+                assert_eq!(coarse.pixel_at(x - x % 2, y - y % 2), coarse.pixel_at(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn given_a_canvas_narrower_than_the_thread_count_when_rendering_should_write_every_pixel() {
+        // Arrange
+        let world = Arc::new(World::new(vec![], vec![]));
+        let camera = Arc::new(Camera::new(5, 5, PI / 2.0, Matrix::identity(4)));
+
+        // Act
+        let canvas = render(world, camera);
+
+        // Assert
+        for y in 0..5 {
+            for x in 0..5 {
+                assert_eq!(Color::black(), *canvas.pixel_at(x, y).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn given_a_jittered_light_when_rendering_soft_shadows_should_lighten_the_edges_of_the_hard_shadow(
+    ) {
+        // Arrange
+        let matte = || -> Arc<dyn Material> {
+            Arc::new(Phong::new(
+                Box::new(Solid::new(Color::white())),
+                0.0,
+                1.0,
+                0.0,
+                200.0,
+                0.0,
+                0.0,
+                1.0,
+            ))
+        };
+
+        let scene = |jitter_radius: f64| {
+            let floor: Arc<dyn Shape> =
+                Arc::new(Plane::new(Arc::new(Matrix::identity(4)), matte(), true));
+            let blocker: Arc<dyn Shape> = Arc::new(Sphere::new(
+                Arc::new(
+                    (&Matrix::translation(0.0, 1.0, 0.0) * &Matrix::scaling(0.5, 0.5, 0.5))
+                        .unwrap(),
+                ),
+                matte(),
+                true,
+            ));
+
+            let light = PointLight::new(Tuple::point(2.0, 4.0, 0.0), Color::white())
+                .with_jitter_radius(jitter_radius);
+
+            World::new(vec![floor, blocker], vec![Arc::new(light)])
+        };
+
+        let camera = Arc::new(Camera::new(
+            16,
+            16,
+            PI / 4.0,
+            Matrix::view_transform(
+                Tuple::point(0.0, 10.0, 0.0),
+                Tuple::origin(),
+                Tuple::vector(0.0, 0.0, 1.0),
+            ),
+        ));
+
+        // Act
+        let hard = render(Arc::new(scene(0.0)), camera.clone());
+        let unjittered = render_soft_shadows(scene(0.0), camera.clone(), 4);
+        let soft = render_soft_shadows(scene(1.0), camera.clone(), 16);
+
+        // Assert
+        let pixels = (0..16).flat_map(|y| (0..16).map(move |x| (x, y)));
+        let in_shadow = |canvas: &Canvas, (x, y): (usize, usize)| {
+            *canvas.pixel_at(x, y).unwrap() == Color::black()
+        };
+
+        assert_eq!(0, hard.diff(&unjittered).unwrap().0);
+
+        let hard_shadow = pixels
+            .clone()
+            .filter(|pixel| in_shadow(&hard, *pixel))
+            .count();
+        let soft_shadow = pixels
+            .clone()
+            .filter(|pixel| in_shadow(&soft, *pixel))
+            .count();
+        let penumbra = pixels
+            .filter(|pixel| in_shadow(&hard, *pixel) && !in_shadow(&soft, *pixel))
+            .count();
+
+        assert!(hard_shadow > 0);
+        assert!(soft_shadow < hard_shadow);
+        assert!(penumbra > 0);
+    }
+
+    #[test]
+    fn given_a_small_render_when_running_should_write_a_sidecar_matching_the_config_and_stats() {
+        // Arrange
+        let directory = std::env::temp_dir();
+        let image_path = directory.join("raytracer_sidecar_test.ppm");
+        let sidecar_path = directory.join("raytracer_sidecar_test.meta.json");
+
+        let args: Vec<String> = ["raytracer", image_path.to_str().unwrap(), "6", "4"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let config = Config::new(&args).unwrap();
+
+        // Act
+        run(config).unwrap();
+
+        let sidecar = fs::read_to_string(&sidecar_path).unwrap();
+        fs::remove_file(&image_path).unwrap();
+        fs::remove_file(&sidecar_path).unwrap();
+
+        // Assert
+        assert_eq!(
+            RenderStats::sidecar_path(image_path.to_str().unwrap()),
+            sidecar_path.to_str().unwrap()
+        );
+
+        assert!(sidecar.contains("\"width\": 6,"));
+        assert!(sidecar.contains("\"height\": 4,"));
+        assert!(sidecar.contains("\"global_illumination\": false,"));
+        assert!(sidecar.contains(&format!(
+            "\"max_intersections\": {},",
+            MAX_INTERSECTIONS_PER_RAY
+        )));
+        assert!(sidecar.contains("\"field_of_view\": 0.785,"));
+        assert!(sidecar.contains("\"samples_per_pixel\": 1,"));
+        assert!(sidecar.contains(&format!(
+            "\"max_recursion_depth\": {},",
+            MAX_RAY_RECURSION_DEPTH
+        )));
+        assert!(sidecar.contains("\"render_time_seconds\": "));
+    }
+
+    #[test]
+    fn given_adaptive_antialiasing_when_running_should_record_the_average_rays_traced_per_pixel() {
+        // Arrange
+        let directory = std::env::temp_dir();
+        let image_path = directory.join("raytracer_adaptive_sidecar_test.ppm");
+        let sidecar_path = directory.join("raytracer_adaptive_sidecar_test.meta.json");
+
+        let args: Vec<String> = [
+            "raytracer",
+            image_path.to_str().unwrap(),
+            "6",
+            "4",
+            "--aa-max-depth=2",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        let config = Config::new(&args).unwrap();
+
+        // Act
+        run(config).unwrap();
+
+        let sidecar = fs::read_to_string(&sidecar_path).unwrap();
+        fs::remove_file(&image_path).unwrap();
+        fs::remove_file(&sidecar_path).unwrap();
+
+        // Assert
+        let samples_per_pixel: f64 = sidecar
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("\"samples_per_pixel\": "))
+            .unwrap()
+            .trim_end_matches(',')
+            .parse()
+            .unwrap();
+
+        // Every pixel gets at least its first five rays
+        assert!(samples_per_pixel >= 5.0);
+    }
+
+    #[test]
+    fn given_a_lens_when_rendering_should_keep_a_sphere_at_the_focal_distance_sharper_than_one_beyond_it(
+    ) {
+        // Arrange
+        let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
+
+        // The far sphere is five times the size at five times the distance, so it looks the same
+        let world_with = |transform: Matrix| {
+            let sphere: Arc<dyn Shape> = Arc::new(Sphere::new(
+                Arc::new(transform),
+                Arc::new(Phong::default()),
+                true,
+            ));
+
+            Arc::new(World::new(vec![sphere], vec![Arc::new(light)]))
+        };
+        let near = world_with(Matrix::identity(4));
+        let far = world_with(
+            (&Matrix::translation(0.0, 0.0, 20.0) * &Matrix::scaling(5.0, 5.0, 5.0)).unwrap(),
+        );
+
+        let pinhole = Camera::new(
+            9,
+            9,
+            PI / 3.0,
+            Matrix::view_transform(
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let lens = Arc::new(
+            Camera::new(9, 9, PI / 3.0, pinhole.transform().clone()).with_depth_of_field(0.5, 4.0),
+        );
+        let pinhole = Arc::new(pinhole);
+
+        // Act
+        let near_blur = render(near.clone(), pinhole.clone())
+            .diff(&render_depth_of_field(near, lens.clone(), 16))
+            .unwrap();
+        let far_blur = render(far.clone(), pinhole)
+            .diff(&render_depth_of_field(far, lens, 16))
+            .unwrap();
+
+        // Assert
+        assert!(near_blur.1 < far_blur.1);
+    }
+
+    #[test]
+    fn given_two_samples_per_axis_when_rendering_should_blend_the_colors_on_either_side_of_an_edge()
+    {
+        // Arrange
+        let red: Arc<dyn Material> = Arc::new(Phong::new(
+            Box::new(Solid::new(Color::red())),
+            1.0,
+            0.0,
+            0.0,
+            200.0,
+            0.0,
+            0.0,
+            1.0,
+        ));
+        let sphere: Arc<dyn Shape> =
+            Arc::new(Sphere::new(Arc::new(Matrix::identity(4)), red, true));
+
+        let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
+        let mut world = World::new(vec![sphere], vec![Arc::new(light)]);
+        world.set_background(Some(Box::new(Solid::new(Color::blue()))));
+        let world = Arc::new(world);
+
+        let camera = Arc::new(Camera::new(
+            8,
+            8,
+            PI / 3.0,
+            Matrix::view_transform(
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            ),
+        ));
+
+        let is_blended = |color: &Color| {
+            color.red > 0.0 && color.red < 1.0 && color.blue > 0.0 && color.blue < 1.0
+        };
+
+        // Act
+        let aliased = render(world.clone(), camera.clone());
+        let smoothed = render_with_anti_aliasing(
+            world,
+            camera.clone(),
+            AntiAliasing {
+                samples_per_axis: 2,
+            },
+        );
+
+        // Assert
+        let pixels = |canvas: &Canvas| -> Vec<Color> {
+            (0..camera.height())
+                .flat_map(|y| (0..camera.width()).map(move |x| (x, y)))
+                .map(|(x, y)| *canvas.pixel_at(x, y).unwrap())
+                .collect()
+        };
+
+        assert!(pixels(&aliased)
+            .iter()
+            .all(|color| *color == Color::red() || *color == Color::blue()));
+        assert!(pixels(&smoothed).iter().any(is_blended));
+    }
+
+    #[test]
+    fn given_an_antialiasing_argument_when_creating_a_config_should_parse_the_samples_per_axis() {
+        // Arrange
+        let args = |extra: &str| -> Vec<String> {
+            ["raytracer", "out.ppm", "10", "10", extra]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect()
+        };
+
+        // Act
+        let parsed = Config::new(&args("--aa=3")).unwrap();
+        let default = Config::new(&args("--gi")).unwrap();
+        let invalid = Config::new(&args("--aa=0"));
+
+        // Assert
+        assert_eq!(3, parsed.anti_aliasing.samples_per_axis);
+        assert_eq!(AntiAliasing::default(), default.anti_aliasing);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn given_the_default_world_when_sampling_adaptively_should_only_subdivide_pixels_on_a_silhouette(
+    ) {
+        // Arrange
+        let world = World::default();
+
+        // The sphere's lit edge falls across pixel (2, 10), whereas (10, 10) is in its middle
+        let camera = Camera::new(
+            21,
+            21,
+            PI / 6.0,
+            Matrix::view_transform(
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            ),
+        );
+
+        // Act
+        let (_, interior_rays) = sample_adaptively(&world, &camera, 10, 10, 0.1, 3);
+        let (_, silhouette_rays) = sample_adaptively(&world, &camera, 2, 10, 0.1, 3);
+
+        // Assert
+        assert_eq!(5, interior_rays);
+        assert!(silhouette_rays > 5);
+    }
+
+    #[test]
+    fn given_adaptive_antialiasing_arguments_when_creating_a_config_should_parse_them() {
+        // Arrange
+        let args: Vec<String> = [
+            "raytracer",
+            "out.ppm",
+            "10",
+            "10",
+            "--aa-threshold=0.25",
+            "--aa-max-depth=2",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+
+        // Act
+        let config = Config::new(&args).unwrap();
+
+        // Assert
+        assert_eq!(0.25, config.aa_threshold);
+        assert_eq!(2, config.aa_max_depth);
+    }
+
+    #[test]
+    fn given_a_clear_color_argument_when_creating_a_config_should_parse_it_or_default_to_none() {
+        // Arrange
+        let args = |extra: &str| -> Vec<String> {
+            ["raytracer", "out.ppm", "10", "10", extra]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect()
+        };
+
+        // Act
+        let parsed = Config::new(&args("--clear-color=1,0,1")).unwrap();
+        let default = Config::new(&args("--gi")).unwrap();
+        let invalid = Config::new(&args("--clear-color=1,0"));
+
+        // Assert
+        assert_eq!(Some(Color::new(1.0, 0.0, 1.0)), parsed.clear_color);
+        assert_eq!(None, default.clear_color);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn given_the_default_world_when_rendering_with_different_thread_counts_should_give_identical_pixels(
+    ) {
+        // Arrange
+        let world = Arc::new(World::default());
+
+        // Not a whole number of tiles either way, so there are partial tiles along two edges
+        let camera = Arc::new(Camera::new(
+            40,
+            40,
+            PI / 3.0,
+            Matrix::view_transform(
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            ),
+        ));
+
+        let render_with = |threads: usize| {
+            render_tiles(
+                world.clone(),
+                camera.clone(),
+                camera.width(),
+                camera.height(),
+                threads,
+                None,
+                |world, camera, x, y| {
+                    world.color_at(&camera.ray_for_pixel(x, y), MAX_RAY_RECURSION_DEPTH)
+                },
+            )
+        };
+
+        // Act
+        let single = render_with(1);
+        let several = render_with(3);
+
+        // Assert
+        let (differing_pixels, _, _) = single.diff(&several).unwrap();
+
+        assert_eq!(0, differing_pixels);
+        assert_eq!(
+            render(world.clone(), camera.clone()).pixel_at(20, 20),
+            single.pixel_at(20, 20)
+        );
+    }
+
+    #[test]
+    fn given_an_explicit_thread_count_when_creating_a_config_and_rendering_should_still_render_every_pixel(
+    ) {
+        // Arrange
+        let args = |extra: &[&str]| -> Vec<String> {
+            ["raytracer", "out.ppm", "12", "7"]
+                .iter()
+                .chain(extra)
+                .map(|arg| arg.to_string())
+                .collect()
+        };
+
+        let positional = Config::new(&args(&["3"])).unwrap();
+        let flagged = Config::new(&args(&["--gi", "--threads", "5"])).unwrap();
+        let default = Config::new(&args(&[])).unwrap();
+
+        let camera = Arc::new(Camera::new(
+            positional.width,
+            positional.height,
+            PI / 3.0,
+            Matrix::view_transform(
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            ),
+        ));
+
+        // Act
+        let canvas = render_with_threads(
+            Arc::new(World::default()),
+            camera.clone(),
+            positional.threads,
+        );
+
+        // Assert
+        assert_eq!(3, positional.threads);
+        assert_eq!(5, flagged.threads);
+        assert_eq!(default_thread_count(), default.threads);
+        assert!(Config::new(&args(&["--threads", "0"])).is_err());
+
+        assert_eq!((12, 7), (canvas.width(), canvas.height()));
+        assert_eq!(
+            render(Arc::new(World::default()), camera).pixel_at(6, 3),
+            canvas.pixel_at(6, 3)
+        );
+    }
 }
@@ -1,2 +1,3 @@
 pub mod material;
 pub mod phong;
+pub mod shadow_catcher;
@@ -1,7 +1,21 @@
 use crate::geometry::shape::Shape;
+use crate::scene::computations::Computations;
 use crate::tuples::{color::Color, point_light::PointLight, tuple::Tuple};
+use std::any::Any;
 use std::sync::Arc;
 
+/*
+    How a surface that's both reflective and transparent splits the light between the reflected
+    and refracted rays. Fresnel follows physics, reflecting more at grazing angles (see
+    World::schlick), whereas Fixed always reflects the given share and refracts the rest, which
+    can look better in stylized renders.
+*/
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BlendMode {
+    Fresnel,
+    Fixed(f64),
+}
+
 pub trait Material: Send + Sync {
     fn lighting(
         &self,
@@ -13,9 +27,99 @@ pub trait Material: Send + Sync {
         in_shadow: bool,
     ) -> Color;
 
+    // Lights the hit described by the computations. By default this is the same as calling
+    // lighting at the over point, but materials can override it to make use of the extra detail,
+    // e.g. the ray differentials for filtering patterns.
+    fn lighting_at_hit(&self, light: PointLight, comps: &Computations, in_shadow: bool) -> Color {
+        self.lighting(
+            comps.object.clone(),
+            light,
+            comps.over_point,
+            comps.eyev,
+            comps.normalv,
+            in_shadow,
+        )
+    }
+
+    // Lights the point with several lights at once, e.g. every light in the scene, where in_shadow
+    // says whether each one is blocked. Materials with an expensive surface color (e.g. a complex
+    // pattern) can override this to only work the color out once rather than once per light.
+    fn lighting_multi(
+        &self,
+        object: Arc<dyn Shape>,
+        lights: &[PointLight],
+        world_point: Tuple,
+        eyev: Tuple,
+        normalv: Tuple,
+        in_shadow: &[bool],
+    ) -> Color {
+        assert_eq!(lights.len(), in_shadow.len());
+
+        lights
+            .iter()
+            .zip(in_shadow)
+            .fold(Color::black(), |result, (light, shadowed)| {
+                result
+                    + self.lighting(
+                        object.clone(),
+                        *light,
+                        world_point,
+                        eyev,
+                        normalv,
+                        *shadowed,
+                    )
+            })
+    }
+
+    // The several lights at once version of lighting_at_hit
+    fn lighting_multi_at_hit(
+        &self,
+        lights: &[PointLight],
+        comps: &Computations,
+        in_shadow: &[bool],
+    ) -> Color {
+        assert_eq!(lights.len(), in_shadow.len());
+
+        lights
+            .iter()
+            .zip(in_shadow)
+            .fold(Color::black(), |result, (light, shadowed)| {
+                result + self.lighting_at_hit(*light, comps, *shadowed)
+            })
+    }
+
+    // The proportion of each color of light that the surface scatters diffusely at the point, e.g.
+    // for working out how much indirect light bounces off it
+    fn diffuse_albedo(&self, object: Arc<dyn Shape>, world_point: Tuple) -> Color;
+
+    // Derives a copy of the material with its surface replaced by a solid color, leaving every
+    // other property the same. Lets a shared material be recolored without knowing its type.
+    fn clone_with_color(&self, color: Color) -> Arc<dyn Material>;
+
     fn reflective(&self) -> f64;
 
+    fn reflection_roughness(&self) -> f64;
+
     fn transparency(&self) -> f64;
 
     fn refractive_index(&self) -> f64;
+
+    fn opacity(&self) -> f64;
+
+    fn blend_mode(&self) -> BlendMode;
+
+    // Whether the surface is missing at the given texture coordinates (see
+    // Pattern::is_cut_out_at_uv). By default a surface is solid everywhere.
+    fn is_cut_out_at_uv(&self, _u: f64, _v: f64) -> bool {
+        false
+    }
+
+    // How dark shadows get on the surface if it's a shadow catcher (see ShadowCatcher), which the
+    // world draws by darkening whatever is behind it rather than by lighting it
+    fn shadow_catcher_strength(&self) -> Option<f64> {
+        None
+    }
+
+    // Allows callers to downcast to the concrete material type, e.g. to inspect its settings
+    fn as_any(&self) -> &dyn Any;
 }
@@ -1,21 +1,29 @@
-use super::material::Material;
+use super::material::{BlendMode, Material};
+use std::any::Any;
 use std::sync::Arc;
 
 use crate::geometry::shape::Shape;
 use crate::patterns::pattern::Pattern;
 use crate::patterns::solid::Solid;
+use crate::scene::computations::Computations;
 
 use crate::tuples::{color::Color, point_light::PointLight, tuple::Tuple};
 
+#[derive(Clone)]
 pub struct Phong {
-    pattern: Box<dyn Pattern>,
+    pattern: Arc<dyn Pattern>,
     ambient: f64,
     diffuse: f64,
     specular: f64,
     shininess: f64,
     reflective: f64,
+    reflection_roughness: f64,
     transparency: f64,
     refractive_index: f64,
+    opacity: f64,
+    shadow_tint: Color,
+    blend_mode: BlendMode,
+    specular_color: Option<Color>,
 }
 
 impl Phong {
@@ -30,43 +38,100 @@ impl Phong {
         refractive_index: f64,
     ) -> Phong {
         Phong {
-            pattern,
+            pattern: Arc::from(pattern),
             ambient,  // Light reflected from other objects in the scene
             diffuse,  // Light reflected from a matte surface
             specular, // Reflection of the light source itself
             shininess,
             reflective,
+            reflection_roughness: 0.0, // Perfectly sharp reflections unless set otherwise
             transparency,
             refractive_index,
+            opacity: 1.0,                // Fully opaque unless set otherwise
+            shadow_tint: Color::black(), // Shadows only get the ambient light unless set otherwise
+            blend_mode: BlendMode::Fresnel,
+            specular_color: None, // Highlights take the light's color unless set otherwise
         }
     }
 
-    pub fn default() -> Phong {
-        Phong::new(
-            Box::new(Solid::default()),
-            0.1,
-            0.9,
-            0.9,
-            200.0,
-            0.0,
-            0.0,
-            1.0,
+    // Roughness blurs reflections, e.g. for brushed metal. It ranges from 0.0 (a perfect mirror) to
+    // 1.0 (reflected rays are spread out over the whole hemisphere above the surface).
+    pub fn with_reflection_roughness(mut self, reflection_roughness: f64) -> Phong {
+        self.reflection_roughness = reflection_roughness;
+        self
+    }
+
+    // Unlike transparency this doesn't bend the light, it just lets whatever is behind the surface
+    // show through by a proportion of 1 - opacity
+    pub fn with_opacity(mut self, opacity: f64) -> Phong {
+        self.opacity = opacity;
+        self
+    }
+
+    // Fakes the light that would bounce into a shadow from the rest of the scene, by adding the
+    // surface color filtered by the tint on top of the ambient light wherever it's in shadow. A
+    // cheap way of getting softer, colored shadows without any extra rays.
+    pub fn with_shadow_tint(mut self, shadow_tint: Color) -> Phong {
+        self.shadow_tint = shadow_tint;
+        self
+    }
+
+    // Tints the highlights, which otherwise come out the color of the light, e.g. so that gold
+    // gets golden highlights. Plastics and the like should be left untinted.
+    pub fn with_specular_color(mut self, specular_color: Color) -> Phong {
+        self.specular_color = Some(specular_color);
+        self
+    }
+
+    // Only makes a difference to surfaces that are both reflective and transparent
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Phong {
+        if let BlendMode::Fixed(reflectance) = blend_mode {
+            assert!((0.0..=1.0).contains(&reflectance));
+        }
+
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    // Returns a copy of the material with its ambient, diffuse and specular responses scaled by the
+    // given factor, as a quick way of brightening or dimming it. Each coefficient is kept between
+    // 0.0 and 1.0 so that scaling up can't make the surface emit more light than it receives.
+    pub fn with_scaled_reflectance(&self, factor: f64) -> Phong {
+        let scale = |coefficient: f64| (coefficient * factor).clamp(0.0, 1.0);
+
+        Phong {
+            ambient: scale(self.ambient),
+            diffuse: scale(self.diffuse),
+            specular: scale(self.specular),
+            ..self.clone()
+        }
+    }
+
+    // The surface color at the hit, looked up by the texture coordinates there if both the object
+    // and the pattern use them, otherwise by position
+    fn color_at_hit(&self, comps: &Computations) -> Color {
+        if let Some(color) = comps.uv.and_then(|(u, v)| self.pattern.pattern_at_uv(u, v)) {
+            return color;
+        }
+
+        self.pattern.pattern_at_shape_filtered(
+            comps.object.clone(),
+            comps.over_point,
+            comps.dpdx,
+            comps.dpdy,
         )
     }
-}
 
-impl Material for Phong {
-    fn lighting(
+    // Shades a point on the surface given the color of the surface there
+    fn shade(
         &self,
-        object: Arc<dyn Shape>,
+        color: Color,
         light: PointLight,
         world_point: Tuple,
         eyev: Tuple,
         normalv: Tuple,
         in_shadow: bool,
     ) -> Color {
-        let color = self.pattern.pattern_at_shape(object, world_point);
-
         // Combine the surface color with the light's color/intensity
         let effective_color = color * light.intensity;
 
@@ -77,9 +142,10 @@ impl Material for Phong {
         let ambient = effective_color * self.ambient;
 
         // Diffuse and specular both have a dependency on the light source
-        // so if the point is in shadow only use the ambient component.
+        // so if the point is in shadow only use the ambient component (plus
+        // the tint standing in for any light bouncing into the shadow).
         if in_shadow {
-            return ambient;
+            return ambient + color * self.shadow_tint;
         }
 
         let diffuse: Color;
@@ -102,12 +168,17 @@ impl Material for Phong {
             let reflect_vector = Tuple::reflect(-light_vector, normalv);
             let reflect_dot_eye = Tuple::dot(reflect_vector, eyev);
 
-            if reflect_dot_eye <= 0.0 {
+            if reflect_dot_eye <= 0.0 || !light.specular_enabled {
                 specular = Color::black();
             } else {
                 // Compute the specular contribution
                 let factor = reflect_dot_eye.powf(self.shininess);
-                specular = light.intensity * self.specular * factor;
+                let highlight = match self.specular_color {
+                    Some(specular_color) => light.intensity * specular_color,
+                    None => light.intensity,
+                };
+
+                specular = highlight * self.specular * factor;
             }
         }
 
@@ -115,10 +186,208 @@ impl Material for Phong {
         ambient + diffuse + specular
     }
 
+    // Shades the point once for each light, given the color of the surface there
+    fn shade_multi(
+        &self,
+        color: Color,
+        lights: &[PointLight],
+        world_point: Tuple,
+        eyev: Tuple,
+        normalv: Tuple,
+        in_shadow: &[bool],
+    ) -> Color {
+        lights
+            .iter()
+            .zip(in_shadow)
+            .fold(Color::black(), |result, (light, shadowed)| {
+                result + self.shade(color, *light, world_point, eyev, normalv, *shadowed)
+            })
+    }
+
+    pub fn default() -> Phong {
+        PhongBuilder::new().build()
+    }
+}
+
+/*
+    Builds a Phong material from named settings, rather than Phong::new's eight positional ones
+    where it's easy to mix up e.g. the reflective, transparency and refractive index. Anything not
+    set keeps its value from Phong::default, and the setters can be called in any order.
+*/
+pub struct PhongBuilder {
+    pattern: Box<dyn Pattern>,
+    ambient: f64,
+    diffuse: f64,
+    specular: f64,
+    shininess: f64,
+    reflective: f64,
+    transparency: f64,
+    refractive_index: f64,
+}
+
+impl PhongBuilder {
+    pub fn new() -> PhongBuilder {
+        PhongBuilder {
+            pattern: Box::new(Solid::default()),
+            ambient: 0.1,
+            diffuse: 0.9,
+            specular: 0.9,
+            shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+        }
+    }
+
+    pub fn pattern(mut self, pattern: Box<dyn Pattern>) -> PhongBuilder {
+        self.pattern = pattern;
+        self
+    }
+
+    // Shorthand for a solid color pattern
+    pub fn color(self, color: Color) -> PhongBuilder {
+        self.pattern(Box::new(Solid::new(color)))
+    }
+
+    pub fn ambient(mut self, ambient: f64) -> PhongBuilder {
+        self.ambient = ambient;
+        self
+    }
+
+    pub fn diffuse(mut self, diffuse: f64) -> PhongBuilder {
+        self.diffuse = diffuse;
+        self
+    }
+
+    pub fn specular(mut self, specular: f64) -> PhongBuilder {
+        self.specular = specular;
+        self
+    }
+
+    pub fn shininess(mut self, shininess: f64) -> PhongBuilder {
+        self.shininess = shininess;
+        self
+    }
+
+    pub fn reflective(mut self, reflective: f64) -> PhongBuilder {
+        self.reflective = reflective;
+        self
+    }
+
+    pub fn transparency(mut self, transparency: f64) -> PhongBuilder {
+        self.transparency = transparency;
+        self
+    }
+
+    pub fn refractive_index(mut self, refractive_index: f64) -> PhongBuilder {
+        self.refractive_index = refractive_index;
+        self
+    }
+
+    // The rest of the settings (e.g. opacity) can be added on after with Phong's with_ methods
+    pub fn build(self) -> Phong {
+        Phong::new(
+            self.pattern,
+            self.ambient,
+            self.diffuse,
+            self.specular,
+            self.shininess,
+            self.reflective,
+            self.transparency,
+            self.refractive_index,
+        )
+    }
+}
+
+impl Default for PhongBuilder {
+    fn default() -> Self {
+        PhongBuilder::new()
+    }
+}
+
+impl Material for Phong {
+    fn lighting(
+        &self,
+        object: Arc<dyn Shape>,
+        light: PointLight,
+        world_point: Tuple,
+        eyev: Tuple,
+        normalv: Tuple,
+        in_shadow: bool,
+    ) -> Color {
+        let color = self.pattern.pattern_at_shape(object, world_point);
+
+        self.shade(color, light, world_point, eyev, normalv, in_shadow)
+    }
+
+    fn lighting_at_hit(&self, light: PointLight, comps: &Computations, in_shadow: bool) -> Color {
+        let color = self.color_at_hit(comps);
+
+        self.shade(
+            color,
+            light,
+            comps.over_point,
+            comps.eyev,
+            comps.normalv,
+            in_shadow,
+        )
+    }
+
+    fn lighting_multi(
+        &self,
+        object: Arc<dyn Shape>,
+        lights: &[PointLight],
+        world_point: Tuple,
+        eyev: Tuple,
+        normalv: Tuple,
+        in_shadow: &[bool],
+    ) -> Color {
+        assert_eq!(lights.len(), in_shadow.len());
+
+        let color = self.pattern.pattern_at_shape(object, world_point);
+
+        self.shade_multi(color, lights, world_point, eyev, normalv, in_shadow)
+    }
+
+    fn lighting_multi_at_hit(
+        &self,
+        lights: &[PointLight],
+        comps: &Computations,
+        in_shadow: &[bool],
+    ) -> Color {
+        assert_eq!(lights.len(), in_shadow.len());
+
+        let color = self.color_at_hit(comps);
+
+        self.shade_multi(
+            color,
+            lights,
+            comps.over_point,
+            comps.eyev,
+            comps.normalv,
+            in_shadow,
+        )
+    }
+
+    fn diffuse_albedo(&self, object: Arc<dyn Shape>, world_point: Tuple) -> Color {
+        self.pattern.pattern_at_shape(object, world_point) * self.diffuse
+    }
+
+    fn clone_with_color(&self, color: Color) -> Arc<dyn Material> {
+        Arc::new(Phong {
+            pattern: Arc::new(Solid::new(color)),
+            ..self.clone()
+        })
+    }
+
     fn reflective(&self) -> f64 {
         self.reflective
     }
 
+    fn reflection_roughness(&self) -> f64 {
+        self.reflection_roughness
+    }
+
     fn transparency(&self) -> f64 {
         self.transparency
     }
@@ -126,12 +395,30 @@ impl Material for Phong {
     fn refractive_index(&self) -> f64 {
         self.refractive_index
     }
+
+    fn opacity(&self) -> f64 {
+        self.opacity
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn is_cut_out_at_uv(&self, u: f64, v: f64) -> bool {
+        self.pattern.is_cut_out_at_uv(u, v)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::geometry::sphere::Sphere;
+    use crate::matrices::matrix::Matrix;
+    use crate::patterns::checker::Checker;
     use std::f64::consts;
 
     #[test]
@@ -152,6 +439,7 @@ mod tests {
         assert_eq!(specular, result.specular);
         assert_eq!(shininess, result.shininess);
         assert_eq!(reflective, result.reflective);
+        assert_eq!(1.0, result.opacity);
     }
 
     #[test]
@@ -237,6 +525,50 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn given_a_gold_specular_color_when_eye_in_path_of_reflection_vector_should_tint_the_highlight_yellow(
+    ) {
+        // Arrange
+        let shape: Arc<dyn Shape> = Arc::new(Sphere::unit());
+        let gold = Phong::default().with_specular_color(Color::new(1.0, 0.78, 0.34));
+        let position = Tuple::origin();
+
+        let eyev = Tuple::vector(0.0, -consts::SQRT_2 / 2.0, -consts::SQRT_2 / 2.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::white());
+
+        // Act
+        let result = gold.lighting(shape, light, position, eyev, normalv, false);
+
+        // Assert
+        // The ambient and diffuse terms come to 0.7364 as usual, but the highlight is gold
+        let expected = Color::new(1.6364, 1.4384, 1.0424);
+
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn given_a_fill_light_with_specular_disabled_when_eye_in_path_of_reflection_vector_should_only_drop_the_highlight(
+    ) {
+        // Arrange
+        let shape: Arc<dyn Shape> = Arc::new(Sphere::unit());
+        let default = Phong::default();
+        let position = Tuple::origin();
+
+        let eyev = Tuple::vector(0.0, -consts::SQRT_2 / 2.0, -consts::SQRT_2 / 2.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let key = PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::white());
+        let fill = key.with_specular_enabled(false);
+
+        // Act
+        let with_specular = default.lighting(shape.clone(), key, position, eyev, normalv, false);
+        let without_specular = default.lighting(shape, fill, position, eyev, normalv, false);
+
+        // Assert
+        assert_eq!(Color::new(1.6364, 1.6364, 1.6364), with_specular);
+        assert_eq!(Color::new(0.7364, 0.7364, 0.7364), without_specular);
+    }
+
     #[test]
     fn given_default_material_when_lighting_behind_the_surface_should_calculate_resulting_color_correctly(
     ) {
@@ -276,4 +608,206 @@ mod tests {
         // Assert
         assert_eq!(Color::new(0.1, 0.1, 0.1), result);
     }
+
+    #[test]
+    fn given_a_material_with_a_shadow_tint_when_lighting_surface_in_shadow_should_lighten_it_towards_the_tint(
+    ) {
+        // Arrange
+        let shape: Arc<dyn Shape> = Arc::new(Sphere::unit());
+        let tinted = Phong::default().with_shadow_tint(Color::new(0.0, 0.0, 0.3));
+        let position = Tuple::origin();
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+
+        // Act
+        let shadowed = tinted.lighting(shape.clone(), light, position, eyev, normalv, true);
+        let lit = tinted.lighting(shape, light, position, eyev, normalv, false);
+
+        // Assert
+        assert_eq!(Color::new(0.1, 0.1, 0.4), shadowed);
+        assert_eq!(Color::new(1.9, 1.9, 1.9), lit);
+    }
+
+    #[test]
+    fn given_two_lights_when_lighting_them_together_should_equal_the_sum_of_lighting_each() {
+        // Arrange
+        let shape: Arc<dyn Shape> = Arc::new(Sphere::unit());
+        let material = Phong::new(
+            Box::new(Checker::new(
+                Box::new(Solid::new(Color::new(0.9, 0.2, 0.2))),
+                Box::new(Solid::new(Color::new(0.2, 0.2, 0.9))),
+                Arc::new(Matrix::identity(4)),
+            )),
+            0.1,
+            0.9,
+            0.9,
+            200.0,
+            0.0,
+            0.0,
+            1.0,
+        );
+        let position = Tuple::point(0.25, 0.5, -1.5);
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let lights = [
+            PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::white()),
+            PointLight::new(Tuple::point(-5.0, 0.0, -10.0), Color::new(0.2, 0.4, 0.6)),
+        ];
+        let in_shadow = [false, true];
+
+        // Act
+        let together =
+            material.lighting_multi(shape.clone(), &lights, position, eyev, normalv, &in_shadow);
+
+        // Assert
+        let separately =
+            material.lighting(shape.clone(), lights[0], position, eyev, normalv, false)
+                + material.lighting(shape, lights[1], position, eyev, normalv, true);
+
+        assert_eq!(separately, together);
+    }
+
+    #[test]
+    fn given_a_material_scaled_by_half_when_lighting_should_halve_the_diffuse_contribution() {
+        // Arrange
+        let shape: Arc<dyn Shape> = Arc::new(Sphere::unit());
+        let original = Phong::default();
+        let position = Tuple::origin();
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::white());
+
+        let diffuse_of = |material: &Phong| {
+            let lit = material.lighting(shape.clone(), light, position, eyev, normalv, false);
+            let ambient = material.lighting(shape.clone(), light, position, eyev, normalv, true);
+
+            lit - ambient
+        };
+
+        // Act
+        let scaled = original.with_scaled_reflectance(0.5);
+
+        // Assert
+        assert_eq!(0.05, scaled.ambient);
+        assert_eq!(0.45, scaled.diffuse);
+        assert_eq!(0.45, scaled.specular);
+        assert_eq!(diffuse_of(&original) * 0.5, diffuse_of(&scaled));
+    }
+
+    #[test]
+    fn given_a_material_scaled_past_one_when_scaling_should_clamp_the_coefficients() {
+        // Arrange
+        let original = Phong::default();
+
+        // Act
+        let brighter = original.with_scaled_reflectance(2.0);
+        let negative = original.with_scaled_reflectance(-1.0);
+
+        // Assert
+        assert_eq!(0.2, brighter.ambient);
+        assert_eq!(1.0, brighter.diffuse);
+        assert_eq!(1.0, brighter.specular);
+        assert_eq!(0.0, negative.ambient);
+        assert_eq!(0.0, negative.diffuse);
+    }
+
+    #[test]
+    fn given_a_shared_material_when_cloning_it_with_a_new_color_should_leave_the_original_unchanged(
+    ) {
+        // Arrange
+        let original: Arc<dyn Material> = Arc::new(Phong::default().with_opacity(0.5));
+        let object: Arc<dyn Shape> = Arc::new(Sphere::unit());
+
+        let eyev = Tuple::vector(0.0, 0.0, -1.0);
+        let normalv = Tuple::vector(0.0, 0.0, -1.0);
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        let color_of = |material: &Arc<dyn Material>| {
+            material.lighting(object.clone(), light, Tuple::origin(), eyev, normalv, true)
+        };
+
+        // Act
+        let recolored = original.clone_with_color(Color::red());
+
+        // Assert
+        assert_eq!(Color::new(0.1, 0.1, 0.1), color_of(&original));
+        assert_eq!(Color::new(0.1, 0.0, 0.0), color_of(&recolored));
+        assert_eq!(original.opacity(), recolored.opacity());
+    }
+
+    #[test]
+    fn given_a_phong_material_behind_the_trait_when_downcasting_should_recover_the_phong() {
+        // Arrange
+        let material: Arc<dyn Material> = Arc::new(Phong::default().with_opacity(0.25));
+
+        // Act
+        let result = material.as_any().downcast_ref::<Phong>();
+
+        // Assert
+        assert!(result.is_some());
+        assert_eq!(0.25, result.unwrap().opacity);
+    }
+
+    // Compares every setting the builder can change
+    fn assert_same_settings(expected: &Phong, actual: &Phong) {
+        assert!(expected.pattern.eq_dyn(actual.pattern.as_ref()));
+        assert_eq!(expected.ambient, actual.ambient);
+        assert_eq!(expected.diffuse, actual.diffuse);
+        assert_eq!(expected.specular, actual.specular);
+        assert_eq!(expected.shininess, actual.shininess);
+        assert_eq!(expected.reflective, actual.reflective);
+        assert_eq!(expected.transparency, actual.transparency);
+        assert_eq!(expected.refractive_index, actual.refractive_index);
+    }
+
+    #[test]
+    fn given_a_builder_with_only_diffuse_set_when_building_should_match_new_with_the_other_defaults(
+    ) {
+        // Arrange
+        let expected = Phong::new(
+            Box::new(Solid::default()),
+            0.1,
+            0.4,
+            0.9,
+            200.0,
+            0.0,
+            0.0,
+            1.0,
+        );
+
+        // Act
+        let result = PhongBuilder::new().diffuse(0.4).build();
+
+        // Assert
+        assert_same_settings(&expected, &result);
+    }
+
+    #[test]
+    fn given_the_same_settings_in_a_different_order_when_building_should_give_the_same_material() {
+        // Arrange
+        let color = Color::new(0.2, 0.4, 0.6);
+
+        // Act
+        let forwards = PhongBuilder::new()
+            .color(color)
+            .reflective(0.3)
+            .transparency(0.8)
+            .refractive_index(1.5)
+            .build();
+        let backwards = PhongBuilder::new()
+            .refractive_index(1.5)
+            .transparency(0.8)
+            .reflective(0.3)
+            .color(color)
+            .build();
+
+        // Assert
+        assert_same_settings(&forwards, &backwards);
+        assert_eq!(0.3, forwards.reflective());
+        assert_eq!(0.8, forwards.transparency());
+        assert_eq!(1.5, forwards.refractive_index());
+    }
 }
@@ -0,0 +1,147 @@
+use super::material::{BlendMode, Material};
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::geometry::shape::Shape;
+use crate::tuples::{color::Color, point_light::PointLight, tuple::Tuple};
+
+/*
+    A matte surface for compositing rendered objects onto a photo. It's invisible, showing whatever
+    is behind it (usually just the background), except that it's darkened wherever shadows fall on
+    it. The strength sets how dark a full shadow gets, from 0.0 (not at all) to 1.0 (black).
+
+    Rendering with the background set to white leaves just the shadows, so one minus the result can
+    be used as an alpha matte for laying them over the photo.
+*/
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShadowCatcher {
+    strength: f64,
+}
+
+impl ShadowCatcher {
+    pub fn new(strength: f64) -> ShadowCatcher {
+        assert!((0.0..=1.0).contains(&strength));
+
+        ShadowCatcher { strength }
+    }
+}
+
+impl Default for ShadowCatcher {
+    fn default() -> Self {
+        ShadowCatcher::new(1.0)
+    }
+}
+
+impl Material for ShadowCatcher {
+    // Never called by the world, which darkens what's behind the surface instead (see
+    // Material::shadow_catcher_strength), so there's no light of its own
+    fn lighting(
+        &self,
+        _object: Arc<dyn Shape>,
+        _light: PointLight,
+        _world_point: Tuple,
+        _eyev: Tuple,
+        _normalv: Tuple,
+        _in_shadow: bool,
+    ) -> Color {
+        Color::black()
+    }
+
+    fn diffuse_albedo(&self, _object: Arc<dyn Shape>, _world_point: Tuple) -> Color {
+        Color::black()
+    }
+
+    // There's no color to replace
+    fn clone_with_color(&self, _color: Color) -> Arc<dyn Material> {
+        Arc::new(*self)
+    }
+
+    fn reflective(&self) -> f64 {
+        0.0
+    }
+
+    fn reflection_roughness(&self) -> f64 {
+        0.0
+    }
+
+    fn transparency(&self) -> f64 {
+        0.0
+    }
+
+    fn refractive_index(&self) -> f64 {
+        1.0
+    }
+
+    fn opacity(&self) -> f64 {
+        1.0
+    }
+
+    fn blend_mode(&self) -> BlendMode {
+        BlendMode::Fresnel
+    }
+
+    fn shadow_catcher_strength(&self) -> Option<f64> {
+        Some(self.strength)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::plane::Plane;
+    use crate::geometry::shape::Shape;
+    use crate::geometry::sphere::Sphere;
+    use crate::materials::phong::Phong;
+    use crate::materials::shadow_catcher::ShadowCatcher;
+    use crate::matrices::matrix::Matrix;
+    use crate::patterns::solid::Solid;
+    use crate::scene::world::World;
+    use crate::tuples::color::Color;
+    use crate::tuples::point_light::PointLight;
+    use crate::tuples::ray::Ray;
+    use crate::tuples::tuple::Tuple;
+    use crate::MAX_RAY_RECURSION_DEPTH;
+    use std::sync::Arc;
+
+    #[test]
+    fn given_a_shadow_catcher_under_a_sphere_when_coloring_should_show_the_background_darkened_only_in_the_shadow(
+    ) {
+        // Arrange
+        let floor: Arc<dyn Shape> = Arc::new(Plane::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(ShadowCatcher::new(0.8)),
+            false,
+        ));
+
+        let sphere: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(
+                (&Matrix::translation(0.0, 2.0, 0.0) * &Matrix::scaling(0.5, 0.5, 0.5)).unwrap(),
+            ),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        let light = PointLight::new(Tuple::point(0.0, 10.0, 0.0), Color::white());
+        let mut world = World::new(vec![floor, sphere], vec![Arc::new(light)]);
+        world.set_background(Some(Box::new(Solid::new(Color::blue()))));
+
+        // Both rays pass under the sphere and hit the floor, at the origin and off to the side
+        let ray_to = |x: f64| {
+            Ray::new(
+                Tuple::point(x, 1.0, -5.0),
+                Tuple::vector(0.0, -1.0, 5.0).normalize(),
+            )
+        };
+
+        // Act
+        let in_shadow = world.color_at(&ray_to(0.0), MAX_RAY_RECURSION_DEPTH);
+        let lit = world.color_at(&ray_to(3.0), MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        assert_eq!(Color::blue(), lit);
+        assert_eq!(Color::blue() * 0.2, in_shadow);
+    }
+}
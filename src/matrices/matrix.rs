@@ -1,7 +1,9 @@
 use std::ops::Mul;
+use std::sync::OnceLock;
 
-use {array2d::Array2D, array2d::Error};
+use array2d::Array2D;
 
+use crate::error::RaytracerError;
 use crate::tuples::tuple::Tuple;
 
 use crate::EPSILON;
@@ -9,6 +11,9 @@ use crate::EPSILON;
 #[derive(Debug, Clone)]
 pub struct Matrix {
     grid: Array2D<f64>,
+    // Matrices are rarely changed after they're built, so the determinant is worked out the first
+    // time it's asked for and then reused, e.g. between is_invertible and inverse
+    determinant: OnceLock<f64>,
 }
 
 impl Matrix {
@@ -19,6 +24,7 @@ impl Matrix {
 
         Matrix {
             grid: Array2D::filled_with(0.0, num_rows, num_columns),
+            determinant: OnceLock::new(),
         }
     }
 
@@ -38,19 +44,46 @@ impl Matrix {
             }
         }
 
-        Matrix { grid }
+        Matrix {
+            grid,
+            determinant: OnceLock::new(),
+        }
     }
 
-    pub fn from_columns(columns: &[Vec<f64>]) -> Result<Matrix, Error> {
+    // Builds a square matrix with the given values down the diagonal and zeros everywhere else,
+    // e.g. [x, y, z, 1.0] gives the same matrix as scaling(x, y, z)
+    pub fn from_diagonal(diagonal: &[f64]) -> Result<Matrix, RaytracerError> {
+        if diagonal.is_empty() {
+            return Err(RaytracerError::MatrixSize(
+                "Can only build a matrix from a non-empty diagonal",
+            ));
+        }
+
+        let mut result = Matrix::new(diagonal.len(), diagonal.len());
+
+        for (i, value) in diagonal.iter().enumerate() {
+            result.set(i, i, *value)?;
+        }
+
+        Ok(result)
+    }
+
+    pub fn from_columns(columns: &[Vec<f64>]) -> Result<Matrix, RaytracerError> {
         let grid = Array2D::from_columns(columns)?;
 
-        Ok(Matrix { grid })
+        Ok(Matrix {
+            grid,
+            determinant: OnceLock::new(),
+        })
     }
 
-    pub fn from_rows(rows: &[Vec<f64>]) -> Result<Matrix, Error> {
+    pub fn from_rows(rows: &[Vec<f64>]) -> Result<Matrix, RaytracerError> {
         let grid = Array2D::from_rows(rows)?;
 
-        Ok(Matrix { grid })
+        Ok(Matrix {
+            grid,
+            determinant: OnceLock::new(),
+        })
     }
 
     pub fn translation(x: f64, y: f64, z: f64) -> Matrix {
@@ -172,6 +205,107 @@ impl Matrix {
         result.unwrap()
     }
 
+    // The opposite of view_transform, rather than moving the world in front of a camera this
+    // moves a shape out to the from point and turns its +y axis to face the to point, e.g. to aim
+    // a cylinder or cone at a target. Up only has to be roughly perpendicular to the direction, it
+    // just decides which way the shape's x and z axes end up.
+    pub fn look_at_transform(from: Tuple, to: Tuple, up: Tuple) -> Matrix {
+        assert!(from.is_point());
+        assert!(to.is_point());
+        assert!(up.is_vector());
+
+        let forward = (to - from).normalize();
+        let right = Tuple::cross(forward, up.normalize()).normalize();
+        let true_up = Tuple::cross(right, forward);
+
+        // The columns are where each of the shape's axes end up, i.e. x, y, z = right, forward, up
+        let orientation = Matrix::from_columns(&[
+            vec![right.x, right.y, right.z, 0.0],
+            vec![forward.x, forward.y, forward.z, 0.0],
+            vec![true_up.x, true_up.y, true_up.z, 0.0],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ])
+        .unwrap();
+
+        (&Matrix::translation(from.x, from.y, from.z) * &orientation).unwrap()
+    }
+
+    // Stretches a shape running along +y from 0 to 1 (like a cylinder or cone with those bounds)
+    // out between the two points, with the given radius around the line between them
+    pub fn span_transform(from: Tuple, to: Tuple, radius: f64) -> Matrix {
+        let direction = to - from;
+
+        assert!(direction.magnitude() > 0.0);
+        assert!(radius > 0.0);
+
+        // Any up that isn't parallel to the line will do, since the shape is round about its axis
+        let up = if direction.normalize().y.abs() > 0.9 {
+            Tuple::vector(1.0, 0.0, 0.0)
+        } else {
+            Tuple::vector(0.0, 1.0, 0.0)
+        };
+
+        let orientation = Matrix::look_at_transform(from, to, up);
+        let scaling = Matrix::scaling(radius, direction.magnitude(), radius);
+
+        (&orientation * &scaling).unwrap()
+    }
+
+    // Scales about the given point rather than the origin, so the point stays where it is, e.g. to
+    // resize a shape in place about its own center
+    pub fn scale_about(point: Tuple, x: f64, y: f64, z: f64) -> Matrix {
+        Matrix::about(point, &Matrix::scaling(x, y, z))
+    }
+
+    // Rotates by the given angle around an axis running through the point, turning clockwise when
+    // looking along the axis towards the point (the same way as rotation_x, _y and _z)
+    pub fn rotate_about(point: Tuple, axis: Tuple, radians: f64) -> Matrix {
+        assert!(axis.is_vector());
+
+        let axis = axis.normalize();
+        let (x, y, z) = (axis.x, axis.y, axis.z);
+
+        let cos = radians.cos();
+        let sin = radians.sin();
+        let t = 1.0 - cos;
+
+        // Rodrigues' rotation formula written out as a matrix
+        let rotation = Matrix::from_rows(&[
+            vec![
+                t * x * x + cos,
+                t * x * y - sin * z,
+                t * x * z + sin * y,
+                0.0,
+            ],
+            vec![
+                t * x * y + sin * z,
+                t * y * y + cos,
+                t * y * z - sin * x,
+                0.0,
+            ],
+            vec![
+                t * x * z - sin * y,
+                t * y * z + sin * x,
+                t * z * z + cos,
+                0.0,
+            ],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ])
+        .unwrap();
+
+        Matrix::about(point, &rotation)
+    }
+
+    // Moves the point to the origin, applies the transform, then moves it back again
+    fn about(point: Tuple, transform: &Matrix) -> Matrix {
+        assert!(point.is_point());
+
+        let to_origin = Matrix::translation(-point.x, -point.y, -point.z);
+        let back = Matrix::translation(point.x, point.y, point.z);
+
+        (&(&back * transform).unwrap() * &to_origin).unwrap()
+    }
+
     pub fn transpose(&self) -> Matrix {
         let mut result = Matrix::new(self.num_columns(), self.num_rows());
 
@@ -198,11 +332,18 @@ impl Matrix {
      *   2. Transpose the cofactor matrix
      *   3. Divide each of the resulting elements by the determinant of the original matrix
      */
-    pub fn inverse(&self) -> Result<Matrix, &'static str> {
+    pub fn is_invertible(&self) -> bool {
+        match self.determinant() {
+            Ok(determinant) => determinant.abs() >= EPSILON,
+            Err(_) => false,
+        }
+    }
+
+    pub fn inverse(&self) -> Result<Matrix, RaytracerError> {
         let determinant = self.determinant()?;
 
         if (determinant.abs() - 0.0) < EPSILON {
-            return Err("Error: Matrix is not invertible due to a zero determinant");
+            return Err(RaytracerError::SingularMatrix);
         }
 
         let mut result = Matrix::new(self.num_rows(), self.num_columns());
@@ -230,7 +371,17 @@ impl Matrix {
      * determine whether the system has a solution. If the determinant is zero, then the
      * corresponding system of equations has no solution.
      */
-    pub fn determinant(&self) -> Result<f64, &'static str> {
+    pub fn determinant(&self) -> Result<f64, RaytracerError> {
+        if let Some(determinant) = self.determinant.get() {
+            return Ok(*determinant);
+        }
+
+        let determinant = self.calculate_determinant()?;
+
+        Ok(*self.determinant.get_or_init(|| determinant))
+    }
+
+    fn calculate_determinant(&self) -> Result<f64, RaytracerError> {
         return if self.num_rows() == 2 && self.num_columns() == 2 {
             let a = *self.get(0, 0).unwrap();
             let b = *self.get(0, 1).unwrap();
@@ -250,11 +401,68 @@ impl Matrix {
         };
     }
 
-    pub fn submatrix(&self, row: usize, column: usize) -> Result<Matrix, &'static str> {
+    /*
+        Splits a transform back into the translation, scale and rotation that it was built from,
+        i.e. the inverse of translation * rotation * scaling. The translation is the last column,
+        the scale along each axis is the length of each of the first three columns, and dividing
+        those columns by their scale leaves the rotation. A reflection shows up as a negative x scale.
+
+        Note: this is only valid for matrices built that way, any shearing (or scaling applied
+        after rotating) can't be separated out like this.
+    */
+    pub fn decompose(&self) -> (Tuple, Tuple, Matrix) {
+        assert!(self.num_rows() == 4 && self.num_columns() == 4);
+
+        let element = |row: usize, column: usize| *self.get(row, column).unwrap();
+        let column =
+            |index: usize| Tuple::vector(element(0, index), element(1, index), element(2, index));
+
+        let translation = Tuple::vector(element(0, 3), element(1, 3), element(2, 3));
+
+        let mut scale = [
+            column(0).magnitude(),
+            column(1).magnitude(),
+            column(2).magnitude(),
+        ];
+
+        // A negative determinant means the transform flips space over
+        if Tuple::dot(Tuple::cross(column(0), column(1)), column(2)) < 0.0 {
+            scale[0] = -scale[0];
+        }
+
+        let mut rotation = Matrix::identity(4);
+
+        for (index, axis_scale) in scale.iter().enumerate() {
+            let axis = column(index) / *axis_scale;
+
+            rotation.set(0, index, axis.x).unwrap();
+            rotation.set(1, index, axis.y).unwrap();
+            rotation.set(2, index, axis.z).unwrap();
+        }
+
+        (
+            translation,
+            Tuple::vector(scale[0], scale[1], scale[2]),
+            rotation,
+        )
+    }
+
+    // The sum of the elements down the diagonal, which is only defined for square matrices
+    pub fn trace(&self) -> Result<f64, RaytracerError> {
+        if self.num_rows() != self.num_columns() {
+            return Err(RaytracerError::MatrixSize(
+                "Can only take the trace of a square matrix",
+            ));
+        }
+
+        Ok((0..self.num_rows()).map(|i| self.get(i, i).unwrap()).sum())
+    }
+
+    pub fn submatrix(&self, row: usize, column: usize) -> Result<Matrix, RaytracerError> {
         if self.num_rows() == 1 || self.num_columns() == 1 {
-            return Err(
-                "Error: Unable to take submatrix since both dimensions are not greater than one",
-            );
+            return Err(RaytracerError::MatrixSize(
+                "Unable to take submatrix since both dimensions are not greater than one",
+            ));
         }
 
         let mut result = Matrix::new(self.num_rows() - 1, self.num_columns() - 1);
@@ -289,7 +497,7 @@ impl Matrix {
     }
 
     // A minor of an element at row i and column j is the determinant of the submatrix at (i, j)
-    fn minor(&self, row: usize, column: usize) -> Result<f64, &'static str> {
+    fn minor(&self, row: usize, column: usize) -> Result<f64, RaytracerError> {
         return if self.num_rows() == 3 && self.num_columns() == 3 {
             let submatrix = self.submatrix(row, column)?;
 
@@ -297,12 +505,14 @@ impl Matrix {
 
             Ok(determinant)
         } else {
-            Err("Error: Unable to take minor since both dimensions are not equal to three")
+            Err(RaytracerError::MatrixSize(
+                "Unable to take minor since both dimensions are not equal to three",
+            ))
         };
     }
 
     // A cofactor is a minor that possibly has their sign changed
-    fn cofactor(&self, row: usize, column: usize) -> Result<f64, &'static str> {
+    fn cofactor(&self, row: usize, column: usize) -> Result<f64, RaytracerError> {
         return if self.num_rows() == 4 && self.num_columns() == 4 {
             let base: f64 = -1.0;
             let exp: f64 = (row + column) as f64;
@@ -320,7 +530,9 @@ impl Matrix {
                 Ok(result)
             }
         } else {
-            Err("Error: This function can only take cofactor of a 4x4 or a 3x3 matrix")
+            Err(RaytracerError::MatrixSize(
+                "This function can only take cofactor of a 4x4 or a 3x3 matrix",
+            ))
         };
     }
 
@@ -328,8 +540,11 @@ impl Matrix {
         self.grid.get(row, column)
     }
 
-    pub fn set(&mut self, row: usize, column: usize, element: f64) -> Result<(), Error> {
-        self.grid.set(row, column, element)
+    pub fn set(&mut self, row: usize, column: usize, element: f64) -> Result<(), RaytracerError> {
+        // Any cached determinant is now out of date
+        self.determinant = OnceLock::new();
+
+        Ok(self.grid.set(row, column, element)?)
     }
 
     pub fn num_rows(&self) -> usize {
@@ -339,14 +554,24 @@ impl Matrix {
     pub fn num_columns(&self) -> usize {
         self.grid.num_columns()
     }
+
+    // Checks that none of the elements are NaN or infinite, which would poison every calculation
+    // the matrix is used in
+    pub fn is_finite(&self) -> bool {
+        self.grid
+            .elements_row_major_iter()
+            .all(|element| element.is_finite())
+    }
 }
 
 impl Mul<&Tuple> for &Matrix {
-    type Output = Result<Tuple, &'static str>;
+    type Output = Result<Tuple, RaytracerError>;
 
-    fn mul(self, rhs: &Tuple) -> Result<Tuple, &'static str> {
+    fn mul(self, rhs: &Tuple) -> Result<Tuple, RaytracerError> {
         if self.num_columns() != 4 || self.num_rows() != 4 {
-            return Err("Error: Incompatible matrix-tuple sizes for multiplication");
+            return Err(RaytracerError::MatrixSize(
+                "Incompatible matrix-tuple sizes for multiplication",
+            ));
         }
 
         let mut result = [0.0, 0.0, 0.0, 0.0];
@@ -365,11 +590,13 @@ impl Mul<&Tuple> for &Matrix {
 // Matrix multiplication is associative, but not commutative. A x B is not the same as B x A.
 // You must concatenate the transformations in the reverse order to have them applied in the order you want!
 impl Mul<&Matrix> for &Matrix {
-    type Output = Result<Matrix, &'static str>;
+    type Output = Result<Matrix, RaytracerError>;
 
-    fn mul(self, rhs: &Matrix) -> Result<Matrix, &'static str> {
+    fn mul(self, rhs: &Matrix) -> Result<Matrix, RaytracerError> {
         if self.num_columns() != rhs.num_rows() {
-            return Err("Error: Incompatible matrix sizes for multiplication");
+            return Err(RaytracerError::MatrixSize(
+                "Incompatible matrix sizes for multiplication",
+            ));
         }
 
         let first_dimension = self.num_rows();
@@ -430,7 +657,13 @@ impl PartialEq for Matrix {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::geometry::cylinder::Cylinder;
+    use crate::geometry::shape::Shape;
+    use crate::geometry::sphere::Sphere;
+    use crate::materials::phong::Phong;
+    use crate::tuples::ray::Ray;
     use std::f64::consts;
+    use std::sync::Arc;
 
     #[test]
     fn given_normal_values_for_a_matrix_when_creating_a_4_by_4_should_instantiate_correctly() {
@@ -852,6 +1085,71 @@ mod tests {
         assert_eq!(true, result.is_err());
     }
 
+    #[test]
+    fn given_singular_and_invertible_matrices_when_checking_if_invertible_should_identify_each() {
+        // Arrange
+        let singular = Matrix::from_rows(&[
+            vec![-4.0, 2.0, -2.0, -3.0],
+            vec![9.0, 6.0, 2.0, 6.0],
+            vec![0.0, -5.0, 1.0, -5.0],
+            vec![0.0, 0.0, 0.0, 0.0],
+        ])
+        .unwrap();
+
+        let invertible = Matrix::from_rows(&[
+            vec![6.0, 4.0, 4.0, 4.0],
+            vec![5.0, 5.0, 7.0, 6.0],
+            vec![4.0, -9.0, 3.0, -7.0],
+            vec![9.0, 1.0, 7.0, -6.0],
+        ])
+        .unwrap();
+
+        let other_invertible = Matrix::from_rows(&[
+            vec![-5.0, 2.0, 6.0, -8.0],
+            vec![1.0, -5.0, 1.0, 8.0],
+            vec![7.0, 7.0, -6.0, -7.0],
+            vec![1.0, -3.0, 7.0, 4.0],
+        ])
+        .unwrap();
+
+        // Act & Assert
+        assert!(!singular.is_invertible());
+        assert!(invertible.is_invertible());
+        assert!(other_invertible.is_invertible());
+        assert!(Matrix::identity(4).is_invertible());
+    }
+
+    #[test]
+    fn given_a_matrix_containing_nan_when_checking_if_finite_should_return_false() {
+        // Arrange
+        let mut matrix = Matrix::translation(1.0, 2.0, 3.0);
+        let finite = matrix.is_finite();
+
+        // Act
+        matrix.set(0, 3, f64::NAN).unwrap();
+
+        // Assert
+        assert!(finite);
+        assert!(!matrix.is_finite());
+        assert!(!Matrix::scaling(f64::INFINITY, 1.0, 1.0).is_finite());
+    }
+
+    #[test]
+    fn given_a_matrix_with_a_cached_determinant_when_setting_an_element_should_recalculate_it() {
+        // Arrange
+        let mut matrix = Matrix::identity(4);
+        let before = matrix.determinant().unwrap();
+
+        // Act
+        matrix.set(3, 3, 0.0).unwrap();
+        let after = matrix.determinant().unwrap();
+
+        // Assert
+        assert_eq!(1.0, before);
+        assert_eq!(0.0, after);
+        assert!(!matrix.is_invertible());
+    }
+
     #[test]
     fn given_an_invertible_4_by_4_matrix_when_taking_the_inversion_should_output_correct_result() {
         // Arrange
@@ -1392,4 +1690,148 @@ mod tests {
 
         assert_eq!(expected, result);
     }
+
+    #[test]
+    fn given_a_default_cylinder_when_applying_a_look_at_transform_should_point_its_axis_at_the_target(
+    ) {
+        // Arrange
+        let from = Tuple::point(1.0, 2.0, 3.0);
+        let to = Tuple::point(4.0, 6.0, 3.0);
+        let up = Tuple::vector(0.0, 0.0, 1.0);
+
+        // Act
+        let transform = Matrix::look_at_transform(from, to, up);
+
+        let cylinder = Cylinder::new(
+            Arc::new(transform.clone()),
+            Arc::new(Phong::default()),
+            true,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            false,
+        );
+
+        // Assert
+        let axis = (&transform * &Tuple::vector(0.0, 1.0, 0.0)).unwrap();
+
+        assert_eq!(from, (&transform * &Tuple::origin()).unwrap());
+        assert_eq!(Tuple::vector(0.6, 0.8, 0.0), axis);
+        assert_eq!(1.0, transform.determinant().unwrap());
+
+        // Points along the line from A to B are inside it, but ones off to the side aren't
+        assert!(cylinder.contains_point(to));
+        assert!(cylinder.contains_point(from + axis * 20.0));
+        assert!(!cylinder.contains_point(to + Tuple::vector(0.0, 0.0, 1.5)));
+    }
+
+    #[test]
+    fn given_a_sphere_when_scaling_it_about_its_center_should_keep_the_center_fixed_and_double_the_radius(
+    ) {
+        // Arrange
+        let center = Tuple::point(5.0, 0.0, 0.0);
+        let placed = Matrix::translation(5.0, 0.0, 0.0);
+
+        let ray = Ray::new(Tuple::point(5.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let transform = (&Matrix::scale_about(center, 2.0, 2.0, 2.0) * &placed).unwrap();
+
+        let sphere = Arc::new(Sphere::new(
+            Arc::new(transform.clone()),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        let intersections = sphere.intersect(&ray);
+
+        // Assert
+        assert_eq!(center, (&transform * &Tuple::origin()).unwrap());
+        assert_eq!(2, intersections.len());
+        assert_eq!(3.0, intersections[0].time());
+        assert_eq!(7.0, intersections[1].time());
+    }
+
+    #[test]
+    fn given_a_point_and_an_axis_when_rotating_about_them_should_match_rotating_about_the_origin_in_place(
+    ) {
+        // Arrange
+        let pivot = Tuple::point(1.0, 2.0, 3.0);
+        let point = Tuple::point(1.0, 2.0, 4.0);
+
+        // Act
+        let about_y = Matrix::rotate_about(pivot, Tuple::vector(0.0, 1.0, 0.0), consts::PI / 2.0);
+        let about_x = Matrix::rotate_about(pivot, Tuple::vector(1.0, 0.0, 0.0), consts::PI / 2.0);
+
+        // Assert
+        assert_eq!(pivot, (&about_y * &pivot).unwrap());
+        assert_eq!(Tuple::point(2.0, 2.0, 3.0), (&about_y * &point).unwrap());
+        assert_eq!(Tuple::point(1.0, 1.0, 3.0), (&about_x * &point).unwrap());
+        assert_eq!(
+            Matrix::rotation_z(1.0),
+            Matrix::rotate_about(Tuple::origin(), Tuple::vector(0.0, 0.0, 1.0), 1.0)
+        );
+    }
+
+    #[test]
+    fn given_a_diagonal_when_creating_a_matrix_from_it_should_match_the_equivalent_scaling_matrix()
+    {
+        // Arrange
+        let diagonal = [2.0, 3.0, 4.0, 1.0];
+
+        // Act
+        let result = Matrix::from_diagonal(&diagonal).unwrap();
+
+        // Assert
+        assert_eq!(Matrix::scaling(2.0, 3.0, 4.0), result);
+        assert_eq!(10.0, result.trace().unwrap());
+    }
+
+    #[test]
+    fn given_an_empty_diagonal_when_creating_a_matrix_from_it_should_return_an_error() {
+        // Arrange
+        let diagonal: [f64; 0] = [];
+
+        // Act
+        let result = Matrix::from_diagonal(&diagonal);
+
+        // Assert
+        assert!(matches!(result, Err(RaytracerError::MatrixSize(_))));
+    }
+
+    #[test]
+    fn given_identity_matrices_when_taking_the_trace_should_equal_their_size() {
+        // Arrange
+        let sizes = [1, 3, 4];
+
+        // Act
+        let traces: Vec<f64> = sizes
+            .iter()
+            .map(|size| Matrix::identity(*size).trace().unwrap())
+            .collect();
+
+        // Assert
+        assert_eq!(vec![1.0, 3.0, 4.0], traces);
+        assert!(Matrix::new(2, 3).trace().is_err());
+    }
+
+    #[test]
+    fn given_a_composed_transform_when_decomposing_should_recover_the_translation_scale_and_rotation(
+    ) {
+        // Arrange
+        let rotation = (&Matrix::rotation_y(consts::PI / 3.0)
+            * &Matrix::rotation_x(consts::PI / 5.0))
+            .unwrap();
+
+        let transform = (&(&Matrix::translation(1.0, -2.0, 3.5) * &rotation).unwrap()
+            * &Matrix::scaling(2.0, 0.5, 3.0))
+            .unwrap();
+
+        // Act
+        let (translation, scale, result) = transform.decompose();
+
+        // Assert
+        assert_eq!(Tuple::vector(1.0, -2.0, 3.5), translation);
+        assert_eq!(Tuple::vector(2.0, 0.5, 3.0), scale);
+        assert_eq!(rotation, result);
+    }
 }
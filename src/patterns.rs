@@ -1,6 +1,8 @@
 pub mod blended;
 pub mod checker;
 pub mod gradient;
+pub mod image_map;
+pub mod layered_pattern;
 pub mod pattern;
 pub mod perturbed;
 pub mod ring;
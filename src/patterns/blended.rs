@@ -3,8 +3,10 @@ use crate::patterns::solid::Solid;
 use crate::Color;
 use crate::Matrix;
 use crate::Tuple;
+use std::any::Any;
 use std::sync::Arc;
 
+#[derive(Debug)]
 pub struct Blended {
     former: Box<dyn Pattern>,
     latter: Box<dyn Pattern>,
@@ -46,4 +48,19 @@ impl Pattern for Blended {
     fn get_transform(&self) -> Arc<Matrix> {
         self.transform.clone()
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<Blended>()
+            .is_some_and(|other| {
+                self.former.eq_dyn(other.former.as_ref())
+                    && self.latter.eq_dyn(other.latter.as_ref())
+                    && self.transform == other.transform
+            })
+    }
 }
@@ -3,8 +3,10 @@ use crate::patterns::solid::Solid;
 use crate::tuples::tuple::Tuple;
 use crate::Color;
 use crate::Matrix;
+use std::any::Any;
 use std::sync::Arc;
 
+#[derive(Debug)]
 pub struct Checker {
     former: Box<dyn Pattern>,
     latter: Box<dyn Pattern>,
@@ -48,17 +50,58 @@ impl Pattern for Checker {
         }
     }
 
+    /*
+        Once the footprint covers more than a couple of cells, any single sample is as likely to
+        land on one color as the other, which is what causes moire patterns in the distance. So
+        fade from the crisp color toward the average of the two as the footprint grows from half a
+        cell to two cells, using a smoothstep to avoid a visible seam where the blending starts.
+    */
+    fn pattern_at_filtered(&self, pattern_point: Tuple, footprint: f64) -> Color {
+        let crisp = self.pattern_at(pattern_point);
+
+        let t = ((footprint - 0.5) / 1.5).clamp(0.0, 1.0);
+        let blend = t * t * (3.0 - 2.0 * t);
+
+        if blend <= 0.0 {
+            return crisp;
+        }
+
+        let average = (self.former.as_ref().local_pattern_at(pattern_point)
+            + self.latter.as_ref().local_pattern_at(pattern_point))
+            * 0.5;
+
+        crisp + (average - crisp) * blend
+    }
+
     fn get_transform(&self) -> Arc<Matrix> {
         self.transform.clone()
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<Checker>()
+            .is_some_and(|other| {
+                self.former.eq_dyn(other.former.as_ref())
+                    && self.latter.eq_dyn(other.latter.as_ref())
+                    && self.transform == other.transform
+            })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::geometry::shape::Shape;
     use crate::geometry::sphere::Sphere;
+    use crate::matrices::matrix::Matrix;
     use crate::patterns::checker::Checker;
     use crate::patterns::pattern::Pattern;
+    use crate::patterns::solid::Solid;
+    use crate::patterns::striped::Striped;
     use crate::tuples::color::Color;
     use crate::tuples::tuple::Tuple;
     use std::sync::Arc;
@@ -122,4 +165,59 @@ mod tests {
         assert_eq!(Color::white(), results[1]);
         assert_eq!(Color::black(), results[2]);
     }
+
+    #[test]
+    fn given_a_checker_pattern_when_sampling_with_a_large_footprint_should_return_the_average_color(
+    ) {
+        // Arrange
+        let pattern = Checker::default();
+        let point = Tuple::point(0.5, 0.0, 0.5);
+
+        // Act
+        let blurred = pattern.pattern_at_filtered(point, 10.0);
+        let crisp = pattern.pattern_at_filtered(point, 0.01);
+
+        // Assert
+        assert_eq!(Color::new(0.5, 0.5, 0.5), blurred);
+        assert_eq!(Color::white(), crisp);
+        assert_eq!(
+            Color::black(),
+            pattern.pattern_at_filtered(Tuple::point(1.5, 0.0, 0.5), 0.01)
+        );
+    }
+
+    #[test]
+    fn given_a_checker_of_stripes_and_a_solid_when_sampling_each_cell_should_show_the_scaled_stripes_in_theirs(
+    ) {
+        // Arrange
+        let red = Color::new(1.0, 0.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let green = Color::new(0.0, 1.0, 0.0);
+
+        // Stripes a quarter of a cell wide, from their own transform
+        let stripes = Striped::new(
+            Box::new(Solid::new(red)),
+            Box::new(Solid::new(blue)),
+            Arc::new(Matrix::scaling(0.25, 1.0, 1.0)),
+        );
+
+        let pattern = Checker::new(
+            Box::new(stripes),
+            Box::new(Solid::new(green)),
+            Arc::new(Matrix::identity(4)),
+        );
+
+        // Act
+        let results = vec![
+            pattern.pattern_at(Tuple::point(0.1, 0.5, 0.5)),
+            pattern.pattern_at(Tuple::point(0.3, 0.5, 0.5)),
+            pattern.pattern_at(Tuple::point(1.1, 0.5, 0.5)),
+            pattern.pattern_at(Tuple::point(1.1, 1.5, 0.5)),
+            pattern.pattern_at(Tuple::point(1.3, 1.5, 0.5)),
+            pattern.pattern_at(Tuple::point(-0.9, 0.5, 0.5)),
+        ];
+
+        // Assert
+        assert_eq!(vec![red, blue, green, red, blue, green], results);
+    }
 }
@@ -3,8 +3,10 @@ use crate::patterns::solid::Solid;
 use crate::tuples::tuple::Tuple;
 use crate::Color;
 use crate::Matrix;
+use std::any::Any;
 use std::sync::Arc;
 
+#[derive(Debug)]
 pub struct Gradient {
     former: Box<dyn Pattern>,
     latter: Box<dyn Pattern>,
@@ -49,6 +51,21 @@ impl Pattern for Gradient {
     fn get_transform(&self) -> Arc<Matrix> {
         self.transform.clone()
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<Gradient>()
+            .is_some_and(|other| {
+                self.former.eq_dyn(other.former.as_ref())
+                    && self.latter.eq_dyn(other.latter.as_ref())
+                    && self.transform == other.transform
+            })
+    }
 }
 
 #[cfg(test)]
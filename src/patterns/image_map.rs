@@ -0,0 +1,287 @@
+use crate::matrices::matrix::Matrix;
+use crate::patterns::pattern::Pattern;
+use crate::tuples::color::ColorSpace;
+use crate::window::canvas::Canvas;
+use crate::Color;
+use crate::Tuple;
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/*
+    Wraps an image over a surface. On shapes with texture coordinates (see Shape::texture_uv_at)
+    the image is looked up by those, with (0, 0) at the bottom left of the image and (1, 1) at the
+    top right. Anywhere else it's projected flat along z, so the same coordinates are taken from
+    the x and y of the point in pattern space, and the image repeats every unit.
+
+    It can also be given an alpha mask, the same size as the image, for cutting holes out of the
+    surface. The mask is read as grayscale, and wherever its texel is below the cutoff the surface
+    is treated as missing, so rays carry on through to whatever is behind it.
+*/
+pub struct ImageMap {
+    image: Arc<Canvas>,
+    transform: Arc<Matrix>,
+    alpha_mask: Option<(Arc<Canvas>, f64)>,
+}
+
+impl ImageMap {
+    pub fn new(image: Arc<Canvas>, transform: Arc<Matrix>) -> ImageMap {
+        assert!(image.width() > 0 && image.height() > 0);
+
+        ImageMap {
+            image,
+            transform,
+            alpha_mask: None,
+        }
+    }
+
+    // Texels of the mask darker than the cutoff (between 0 and 1) are cut out of the surface
+    pub fn with_alpha_mask(mut self, mask: Arc<Canvas>, cutoff: f64) -> ImageMap {
+        assert_eq!(self.image.width(), mask.width());
+        assert_eq!(self.image.height(), mask.height());
+        assert!((0.0..=1.0).contains(&cutoff));
+
+        self.alpha_mask = Some((mask, cutoff));
+        self
+    }
+
+    // Picks the nearest pixel to the coordinates, which are clamped to the edges of the image
+    fn sample(&self, u: f64, v: f64) -> Color {
+        ImageMap::sample_canvas(&self.image, u, v)
+    }
+
+    fn sample_canvas(canvas: &Canvas, u: f64, v: f64) -> Color {
+        let u = u.clamp(0.0, 1.0);
+        let v = v.clamp(0.0, 1.0);
+
+        // The image's rows run top to bottom, whereas v runs bottom to top
+        let x = (u * (canvas.width() - 1) as f64).round() as usize;
+        let y = ((1.0 - v) * (canvas.height() - 1) as f64).round() as usize;
+
+        let color = *canvas.pixel_at(x, y).unwrap();
+
+        // The lighting is all worked out in linear space, so undo the gamma of images from disk
+        match canvas.color_space() {
+            ColorSpace::Linear => color,
+            ColorSpace::Srgb => color.srgb_to_linear(),
+        }
+    }
+}
+
+impl Debug for ImageMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImageMap")
+            .field("width", &self.image.width())
+            .field("height", &self.image.height())
+            .field("transform", &self.transform)
+            .field(
+                "alpha_cutoff",
+                &self.alpha_mask.as_ref().map(|(_, cutoff)| *cutoff),
+            )
+            .finish()
+    }
+}
+
+impl Pattern for ImageMap {
+    fn pattern_at(&self, pattern_point: Tuple) -> Color {
+        self.sample(
+            pattern_point.x.rem_euclid(1.0),
+            pattern_point.y.rem_euclid(1.0),
+        )
+    }
+
+    fn pattern_at_uv(&self, u: f64, v: f64) -> Option<Color> {
+        Some(self.sample(u, v))
+    }
+
+    fn is_cut_out_at_uv(&self, u: f64, v: f64) -> bool {
+        match &self.alpha_mask {
+            Some((mask, cutoff)) => ImageMap::sample_canvas(mask, u, v).luminance() < *cutoff,
+            None => false,
+        }
+    }
+
+    fn get_transform(&self) -> Arc<Matrix> {
+        self.transform.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<ImageMap>()
+            .is_some_and(|other| {
+                Arc::ptr_eq(&self.image, &other.image)
+                    && self.transform == other.transform
+                    && match (&self.alpha_mask, &other.alpha_mask) {
+                        (Some((mask, cutoff)), Some((other_mask, other_cutoff))) => {
+                            Arc::ptr_eq(mask, other_mask) && cutoff == other_cutoff
+                        }
+                        (None, None) => true,
+                        _ => false,
+                    }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::group::Group;
+    use crate::geometry::plane::Plane;
+    use crate::geometry::shape::Shape;
+    use crate::geometry::triangle::Triangle;
+    use crate::materials::material::Material;
+    use crate::materials::phong::Phong;
+    use crate::matrices::matrix::Matrix;
+    use crate::patterns::image_map::ImageMap;
+    use crate::patterns::pattern::Pattern;
+    use crate::patterns::solid::Solid;
+    use crate::scene::world::World;
+    use crate::tuples::color::{Color, ColorSpace};
+    use crate::tuples::intersection::Intersection;
+    use crate::tuples::point_light::PointLight;
+    use crate::tuples::ray::Ray;
+    use crate::tuples::tuple::Tuple;
+    use crate::window::canvas::Canvas;
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    // A 2x2 square facing -z, centred on the origin, with the texture stretched across it
+    fn textured_quad(material: Arc<dyn Material>) -> Arc<Group> {
+        let (bottom_left, bottom_right) =
+            (Tuple::point(-1.0, -1.0, 0.0), Tuple::point(1.0, -1.0, 0.0));
+        let (top_left, top_right) = (Tuple::point(-1.0, 1.0, 0.0), Tuple::point(1.0, 1.0, 0.0));
+
+        let quad = Arc::new(Group::default());
+        quad.add_children(vec![
+            Arc::new(
+                Triangle::new(
+                    bottom_left,
+                    bottom_right,
+                    top_right,
+                    Arc::new(Matrix::identity(4)),
+                    material.clone(),
+                    true,
+                )
+                .with_texture_coords((0.0, 0.0), (1.0, 0.0), (1.0, 1.0)),
+            ),
+            Arc::new(
+                Triangle::new(
+                    bottom_left,
+                    top_right,
+                    top_left,
+                    Arc::new(Matrix::identity(4)),
+                    material.clone(),
+                    true,
+                )
+                .with_texture_coords((0.0, 0.0), (1.0, 1.0), (0.0, 1.0)),
+            ),
+        ]);
+
+        quad
+    }
+
+    #[test]
+    fn given_a_textured_quad_when_shading_near_its_corners_should_sample_the_image_corners() {
+        // Arrange
+        let red = Color::new(1.0, 0.0, 0.0);
+        let green = Color::new(0.0, 1.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let white = Color::white();
+
+        // Top left red, top right green, bottom left blue and bottom right white
+        let mut image = Canvas::from_rows(&[
+            vec![red, Color::black(), green],
+            vec![Color::black(), Color::black(), Color::black()],
+            vec![blue, Color::black(), white],
+        ])
+        .unwrap();
+        image.set_color_space(ColorSpace::Linear);
+
+        // Fully ambient, so the shaded color is just the texture color
+        let material: Arc<dyn Material> = Arc::new(Phong::new(
+            Box::new(ImageMap::new(
+                Arc::new(image),
+                Arc::new(Matrix::identity(4)),
+            )),
+            1.0,
+            0.0,
+            0.0,
+            200.0,
+            0.0,
+            0.0,
+            1.0,
+        ));
+
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        let objects: Vec<Arc<dyn Shape>> = vec![textured_quad(material)];
+        let world = World::new(objects, vec![Arc::new(light)]);
+
+        let color_at = |x: f64, y: f64| {
+            let ray = Ray::new(Tuple::point(x, y, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+            let intersections = world.intersect_world(&ray);
+
+            assert!(Intersection::hit(&intersections).is_some());
+
+            world.color_at(&ray, 0)
+        };
+
+        // Act
+        let corners = [
+            color_at(-0.95, 0.95),
+            color_at(0.95, 0.95),
+            color_at(-0.95, -0.95),
+            color_at(0.95, -0.95),
+        ];
+
+        // Assert
+        assert_eq!([red, green, blue, white], corners);
+    }
+
+    #[test]
+    fn given_an_alpha_mask_when_shading_should_see_through_the_cut_out_texels_only() {
+        // Arrange
+        let green = Color::new(0.0, 1.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+
+        let mut image = Canvas::from_rows(&[vec![green, green]]).unwrap();
+        image.set_color_space(ColorSpace::Linear);
+
+        // The left half is see through, the right half solid
+        let mask = Canvas::from_rows(&[vec![Color::black(), Color::white()]]).unwrap();
+
+        let fully_ambient = |pattern: Box<dyn Pattern>| -> Arc<dyn Material> {
+            Arc::new(Phong::new(pattern, 1.0, 0.0, 0.0, 200.0, 0.0, 0.0, 1.0))
+        };
+
+        let quad = textured_quad(fully_ambient(Box::new(
+            ImageMap::new(Arc::new(image), Arc::new(Matrix::identity(4)))
+                .with_alpha_mask(Arc::new(mask), 0.5),
+        )));
+
+        let backdrop = Arc::new(Plane::new(
+            Arc::new(
+                (&Matrix::translation(0.0, 0.0, 5.0) * &Matrix::rotation_x(PI / 2.0)).unwrap(),
+            ),
+            fully_ambient(Box::new(Solid::new(blue))),
+            true,
+        ));
+
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        let objects: Vec<Arc<dyn Shape>> = vec![quad, backdrop];
+        let world = World::new(objects, vec![Arc::new(light)]);
+
+        let ray_at = |x: f64| Ray::new(Tuple::point(x, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let through_hole = world.color_at(&ray_at(-0.75), 0);
+        let on_solid = world.color_at(&ray_at(0.75), 0);
+
+        // Assert
+        assert_eq!(blue, through_hole);
+        assert_eq!(green, on_solid);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::patterns::pattern::Pattern;
+use crate::Color;
+use crate::Matrix;
+use crate::Tuple;
+use std::any::Any;
+use std::sync::Arc;
+
+/*
+    Stamps an overlay pattern (e.g. a logo or decal) over a base pattern. How much of the overlay
+    shows through at each point is given by a mask pattern, where the brightness of the mask is
+    used as the alpha, i.e. white shows the overlay, black shows the base and anything in between
+    mixes the two.
+*/
+#[derive(Debug)]
+pub struct LayeredPattern {
+    base: Box<dyn Pattern>,
+    overlay: Box<dyn Pattern>,
+    mask: Box<dyn Pattern>,
+    transform: Arc<Matrix>,
+}
+
+impl LayeredPattern {
+    pub fn new(
+        base: Box<dyn Pattern>,
+        overlay: Box<dyn Pattern>,
+        mask: Box<dyn Pattern>,
+        transform: Arc<Matrix>,
+    ) -> LayeredPattern {
+        LayeredPattern {
+            base,
+            overlay,
+            mask,
+            transform,
+        }
+    }
+}
+
+impl Pattern for LayeredPattern {
+    fn pattern_at(&self, pattern_point: Tuple) -> Color {
+        assert!(pattern_point.is_point());
+
+        let base_color = self.base.as_ref().local_pattern_at(pattern_point);
+        let overlay_color = self.overlay.as_ref().local_pattern_at(pattern_point);
+
+        let alpha = self
+            .mask
+            .as_ref()
+            .local_pattern_at(pattern_point)
+            .luminance()
+            .clamp(0.0, 1.0);
+
+        base_color * (1.0 - alpha) + overlay_color * alpha
+    }
+
+    fn get_transform(&self) -> Arc<Matrix> {
+        self.transform.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<LayeredPattern>()
+            .is_some_and(|other| {
+                self.base.eq_dyn(other.base.as_ref())
+                    && self.overlay.eq_dyn(other.overlay.as_ref())
+                    && self.mask.eq_dyn(other.mask.as_ref())
+                    && self.transform == other.transform
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrices::matrix::Matrix;
+    use crate::patterns::layered_pattern::LayeredPattern;
+    use crate::patterns::pattern::Pattern;
+    use crate::patterns::solid::Solid;
+    use crate::patterns::striped::Striped;
+    use crate::tuples::color::Color;
+    use crate::tuples::tuple::Tuple;
+    use std::sync::Arc;
+
+    #[test]
+    fn given_a_black_overlay_on_white_with_a_half_alpha_mask_when_sampling_should_be_gray_only_where_masked(
+    ) {
+        // Arrange
+        let mask = Striped::new(
+            Box::new(Solid::new(Color::new(0.5, 0.5, 0.5))),
+            Box::new(Solid::new(Color::black())),
+            Arc::new(Matrix::identity(4)),
+        );
+
+        let pattern = LayeredPattern::new(
+            Box::new(Solid::new(Color::white())),
+            Box::new(Solid::new(Color::black())),
+            Box::new(mask),
+            Arc::new(Matrix::identity(4)),
+        );
+
+        // Act
+        let masked = pattern.pattern_at(Tuple::point(0.5, 0.0, 0.0));
+        let unmasked = pattern.pattern_at(Tuple::point(1.5, 0.0, 0.0));
+
+        // Assert
+        assert_eq!(Color::new(0.5, 0.5, 0.5), masked);
+        assert_eq!(Color::white(), unmasked);
+    }
+}
@@ -2,9 +2,11 @@ use crate::geometry::shape::Shape;
 use crate::matrices::matrix::Matrix;
 use crate::Color;
 use crate::Tuple;
+use std::any::Any;
+use std::fmt::Debug;
 use std::sync::Arc;
 
-pub trait Pattern: Sync + Send {
+pub trait Pattern: Sync + Send + Debug {
     fn pattern_at_shape(&self, object: Arc<dyn Shape>, world_point: Tuple) -> Color {
         assert!(world_point.is_point());
 
@@ -13,6 +15,39 @@ pub trait Pattern: Sync + Send {
         self.local_pattern_at(object_point)
     }
 
+    /*
+        Same as pattern_at_shape, but also given how far the sample point moves in world space
+        between neighbouring pixels (see Computations::set_differentials). Those offsets are taken
+        through into pattern space, so that the footprint handed to pattern_at_filtered accounts
+        for any scaling by the object or pattern transforms.
+    */
+    fn pattern_at_shape_filtered(
+        &self,
+        object: Arc<dyn Shape>,
+        world_point: Tuple,
+        dpdx: Tuple,
+        dpdy: Tuple,
+    ) -> Color {
+        assert!(world_point.is_point());
+
+        let no_offset = Tuple::vector(0.0, 0.0, 0.0);
+
+        if dpdx == no_offset && dpdy == no_offset {
+            return self.pattern_at_shape(object, world_point);
+        }
+
+        let pattern_inverse_transform = self.get_transform().inverse().unwrap();
+        let to_pattern_space =
+            |point: Tuple| (&pattern_inverse_transform * &object.world_to_object(point)).unwrap();
+
+        let pattern_point = to_pattern_space(world_point);
+        let footprint = (to_pattern_space(world_point + dpdx) - pattern_point)
+            .magnitude()
+            .max((to_pattern_space(world_point + dpdy) - pattern_point).magnitude());
+
+        self.pattern_at_filtered(pattern_point, footprint)
+    }
+
     fn local_pattern_at(&self, object_point: Tuple) -> Color {
         let pattern_inverse_transform = self.get_transform().inverse().unwrap();
 
@@ -23,5 +58,32 @@ pub trait Pattern: Sync + Send {
 
     fn pattern_at(&self, pattern_point: Tuple) -> Color;
 
+    // Same as pattern_at, but given an estimate of how wide an area (in pattern space) the sample
+    // is standing in for, e.g. the footprint of a pixel on a distant floor. Patterns with fine
+    // detail can use this to blur that detail away rather than aliasing. By default the footprint
+    // is ignored.
+    fn pattern_at_filtered(&self, pattern_point: Tuple, _footprint: f64) -> Color {
+        self.pattern_at(pattern_point)
+    }
+
+    // The color at the given texture coordinates, for patterns that are mapped onto a surface by
+    // them (e.g. an image) rather than by position. Returns None for every other pattern.
+    fn pattern_at_uv(&self, _u: f64, _v: f64) -> Option<Color> {
+        None
+    }
+
+    // Whether the surface is cut away at the given texture coordinates, e.g. the gaps between the
+    // leaves on a textured quad, so that rays pass straight through it there
+    fn is_cut_out_at_uv(&self, _u: f64, _v: f64) -> bool {
+        false
+    }
+
     fn get_transform(&self) -> Arc<Matrix>;
+
+    // Allows callers to downcast to the concrete pattern type, see eq_dyn
+    fn as_any(&self) -> &dyn Any;
+
+    // Checks whether the other pattern is the same type as this one, with the same settings (and
+    // equivalent sub patterns), e.g. for asserting what a pattern was built as in tests
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool;
 }
@@ -3,6 +3,7 @@ use crate::patterns::solid::Solid;
 use crate::tuples::tuple::Tuple;
 use crate::Color;
 use crate::Matrix;
+use std::any::Any;
 use std::sync::Arc;
 
 const PERMUTATIONS: [i64; 512] = [
@@ -33,6 +34,7 @@ const PERMUTATIONS: [i64; 512] = [
     222, 114, 67, 29, 24, 72, 243, 141, 128, 195, 78, 66, 215, 61, 156, 180,
 ];
 
+#[derive(Debug)]
 pub struct Perturbed {
     delegate: Box<dyn Pattern>,
     scale: f64,
@@ -160,4 +162,19 @@ impl Pattern for Perturbed {
     fn get_transform(&self) -> Arc<Matrix> {
         self.transform.clone()
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<Perturbed>()
+            .is_some_and(|other| {
+                self.delegate.eq_dyn(other.delegate.as_ref())
+                    && self.scale == other.scale
+                    && self.transform == other.transform
+            })
+    }
 }
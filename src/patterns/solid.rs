@@ -2,8 +2,10 @@ use crate::matrices::matrix::Matrix;
 use crate::patterns::pattern::Pattern;
 use crate::Color;
 use crate::Tuple;
+use std::any::Any;
 use std::sync::Arc;
 
+#[derive(Debug)]
 pub struct Solid {
     color: Color,
 }
@@ -26,4 +28,15 @@ impl Pattern for Solid {
     fn get_transform(&self) -> Arc<Matrix> {
         Arc::new(Matrix::identity(4))
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<Solid>()
+            .is_some_and(|other| self.color == other.color)
+    }
 }
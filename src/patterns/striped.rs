@@ -3,8 +3,10 @@ use crate::patterns::solid::Solid;
 use crate::Color;
 use crate::Matrix;
 use crate::Tuple;
+use std::any::Any;
 use std::sync::Arc;
 
+#[derive(Debug)]
 pub struct Striped {
     former: Box<dyn Pattern>,
     latter: Box<dyn Pattern>,
@@ -47,6 +49,21 @@ impl Pattern for Striped {
     fn get_transform(&self) -> Arc<Matrix> {
         self.transform.clone()
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<Striped>()
+            .is_some_and(|other| {
+                self.former.eq_dyn(other.former.as_ref())
+                    && self.latter.eq_dyn(other.latter.as_ref())
+                    && self.transform == other.transform
+            })
+    }
 }
 
 #[cfg(test)]
@@ -223,4 +240,27 @@ mod tests {
         // Assert
         assert_eq!(Color::white(), result);
     }
+
+    #[test]
+    fn given_two_default_stripe_patterns_when_comparing_should_be_equal_but_differ_from_a_solid() {
+        // Arrange
+        let first = Striped::default();
+        let second = Striped::default();
+        let shifted = Striped::new(
+            Box::new(Solid::new(Color::white())),
+            Box::new(Solid::new(Color::black())),
+            Arc::new(Matrix::translation(0.5, 0.0, 0.0)),
+        );
+        let solid = Solid::new(Color::white());
+
+        // Act
+        let same = first.eq_dyn(&second);
+        let different_type = first.eq_dyn(&solid);
+
+        // Assert
+        assert!(same);
+        assert!(!different_type);
+        assert!(!first.eq_dyn(&shifted));
+        assert!(format!("{:?}", first).starts_with("Striped"));
+    }
 }
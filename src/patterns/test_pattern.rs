@@ -2,8 +2,10 @@ use crate::matrices::matrix::Matrix;
 use crate::patterns::pattern::Pattern;
 use crate::tuples::color::Color;
 use crate::tuples::tuple::Tuple;
+use std::any::Any;
 use std::sync::Arc;
 
+#[derive(Debug)]
 pub struct TestPattern {
     transform: Arc<Matrix>,
 }
@@ -28,4 +30,15 @@ impl Pattern for TestPattern {
     fn get_transform(&self) -> Arc<Matrix> {
         self.transform.clone()
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn Pattern) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<TestPattern>()
+            .is_some_and(|other| self.transform == other.transform)
+    }
 }
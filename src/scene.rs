@@ -1,4 +1,7 @@
 pub mod camera;
 pub mod computations;
 pub mod obj_file_parser;
+pub mod pick_result;
+pub mod render_accumulator;
+pub mod render_stats;
 pub mod world;
@@ -1,8 +1,11 @@
 use crate::matrices::matrix::Matrix;
 use crate::tuples::ray::Ray;
+use crate::tuples::sampling::{halton, SamplePattern};
 use crate::tuples::tuple::Tuple;
+use std::f64::consts::PI;
 
-// Note the camera's canvas will always be 1 unit in front of the camera
+// Note the camera's canvas will always be 1 unit in front of the camera, and the field of view
+// covers whichever of its width or height is longer
 pub struct Camera {
     hsize: usize, // Horizontal size (in pixels) of the canvas that the picture will be rendered to
     vsize: usize, // Canvas's vertical size (in pixels)
@@ -11,10 +14,133 @@ pub struct Camera {
     half_height: f64, // Half the height of the canvas
     half_width: f64, // Half the width of the canvas
     pixel_size: f64, // Size of a single pixel on the canvas
+    aperture: f64, // Diameter of the lens, or zero for a pinhole camera where everything is sharp
+    focal_distance: f64, // Distance in front of the camera that's in focus when there's a lens
 }
 
 impl Camera {
     pub fn new(hsize: usize, vsize: usize, field_of_view: f64, transform: Matrix) -> Camera {
+        let (half_width, half_height, pixel_size) =
+            Camera::pixel_geometry(hsize, vsize, field_of_view);
+
+        Camera {
+            hsize,
+            vsize,
+            field_of_view,
+            transform,
+            half_height,
+            half_width,
+            pixel_size,
+            aperture: 0.0,
+            focal_distance: 1.0,
+        }
+    }
+
+    /*
+        Gives the camera a lens of the given diameter, focused at the given distance in front of
+        it. Things at that distance stay sharp, while anything nearer or further away is blurred,
+        the more so the wider the aperture. See ray_through_lens for how to render with it.
+    */
+    pub fn with_depth_of_field(mut self, aperture: f64, focal_distance: f64) -> Camera {
+        assert!(aperture >= 0.0);
+        assert!(focal_distance > 0.0);
+
+        self.aperture = aperture;
+        self.focal_distance = focal_distance;
+        self
+    }
+
+    pub fn aperture(&self) -> f64 {
+        self.aperture
+    }
+
+    pub fn focal_distance(&self) -> f64 {
+        self.focal_distance
+    }
+
+    // Changes how much the camera can see, e.g. to zoom in or out during an animation, without
+    // having to rebuild the camera
+    pub fn set_field_of_view(&mut self, field_of_view: f64) {
+        let (half_width, half_height, pixel_size) =
+            Camera::pixel_geometry(self.hsize, self.vsize, field_of_view);
+
+        self.field_of_view = field_of_view;
+        self.half_width = half_width;
+        self.half_height = half_height;
+        self.pixel_size = pixel_size;
+    }
+
+    pub fn field_of_view(&self) -> f64 {
+        self.field_of_view
+    }
+
+    pub fn transform(&self) -> &Matrix {
+        &self.transform
+    }
+
+    /*
+        Creates a left and right eye camera for rendering a stereo pair, e.g. for VR or anaglyph
+        images. The eyes are shifted apart sideways in camera space by eye_separation, and both
+        toed in so that they converge on the point focal_distance in front of this camera (i.e.
+        things at that distance appear at the depth of the screen).
+
+        Note: in camera space the camera looks toward -z, so +x is to its *left*.
+    */
+    pub fn stereo_pair(&self, eye_separation: f64, focal_distance: f64) -> (Camera, Camera) {
+        let eye = |offset: f64| {
+            let eye_transform = Matrix::view_transform(
+                Tuple::point(offset, 0.0, 0.0),
+                Tuple::point(0.0, 0.0, -focal_distance),
+                Tuple::vector(0.0, 1.0, 0.0),
+            );
+
+            Camera::new(
+                self.hsize,
+                self.vsize,
+                self.field_of_view,
+                (&eye_transform * &self.transform).unwrap(),
+            )
+            .with_depth_of_field(self.aperture, self.focal_distance)
+        };
+
+        (eye(eye_separation / 2.0), eye(-eye_separation / 2.0))
+    }
+
+    /*
+        Creates a camera of the same size and field of view, circling the target at the given
+        distance and looking at it. The azimuth (in radians) goes around the y axis, starting from
+        directly in front of the target along -z, and the elevation (also in radians) raises the
+        camera up above the target's level.
+    */
+    pub fn orbit(&self, target: Tuple, radius: f64, azimuth: f64, elevation: f64) -> Camera {
+        assert!(target.is_point());
+        assert!(radius > 0.0);
+        // Looking straight up or down leaves no way to tell which way is up
+        assert!(elevation.abs() < std::f64::consts::FRAC_PI_2);
+
+        let from = target
+            + Tuple::vector(
+                radius * elevation.cos() * azimuth.sin(),
+                radius * elevation.sin(),
+                -radius * elevation.cos() * azimuth.cos(),
+            );
+
+        Camera::new(
+            self.hsize,
+            self.vsize,
+            self.field_of_view,
+            Matrix::view_transform(from, target, Tuple::vector(0.0, 1.0, 0.0)),
+        )
+        .with_depth_of_field(self.aperture, self.focal_distance)
+    }
+
+    /*
+        Works out the half width, half height and pixel size of the canvas one unit in front of the
+        camera, given the size of the image and the field of view. The field of view spans the
+        longer side of the image, and the shorter side is cut down to match the aspect ratio, so
+        that pixels stay square and nothing gets stretched.
+    */
+    fn pixel_geometry(hsize: usize, vsize: usize, field_of_view: f64) -> (f64, f64, f64) {
         let half_view = (field_of_view / 2.0).tan();
         let aspect = (hsize as f64) / (vsize as f64);
 
@@ -31,28 +157,103 @@ impl Camera {
 
         let pixel_size = (half_width * 2.0) / (hsize as f64);
 
-        Camera {
-            hsize,
-            vsize,
-            field_of_view,
-            transform,
-            half_height,
-            half_width,
-            pixel_size,
-        }
+        (half_width, half_height, pixel_size)
     }
 
     // Generates a ray that starts at the camera and hits the X, Y pixel on the
     // canvas in front of the camera
     pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
-        // The offset from the edge of the canvas to the pixel's center
-        let x_offset = (px as f64 + 0.5) * self.pixel_size;
-        let y_offset = (py as f64 + 0.5) * self.pixel_size;
+        // Aim for the pixel's center
+        self.ray_through(px as f64 + 0.5, py as f64 + 0.5)
+    }
+
+    /*
+        Same as ray_for_pixel, but also returns the rays through the centers of the next pixel
+        along in x and the next pixel down in y. These differential rays let shading estimate how
+        much of a surface the pixel covers where the main ray hits it, e.g. so that patterns can be
+        filtered rather than aliasing when they get squashed into a few pixels in the distance.
+    */
+    pub fn ray_differentials_for_pixel(&self, px: usize, py: usize) -> (Ray, Ray, Ray) {
+        let x = px as f64 + 0.5;
+        let y = py as f64 + 0.5;
+
+        (
+            self.ray_through(x, y),
+            self.ray_through(x + 1.0, y),
+            self.ray_through(x, y + 1.0),
+        )
+    }
+
+    // Generates a ray through each of the given number of sample points within the pixel, spread
+    // out according to the sample pattern, e.g. to average together for antialiasing
+    pub fn rays_for_pixel(
+        &self,
+        px: usize,
+        py: usize,
+        pattern: SamplePattern,
+        count: usize,
+    ) -> Vec<Ray> {
+        self.rays_through_pixel(px, py, &pattern.offsets(count))
+    }
+
+    // Generates a ray through each of the given points within the pixel, as offsets from its top
+    // left corner in the range [0, 1)
+    pub fn rays_through_pixel(&self, px: usize, py: usize, offsets: &[(f64, f64)]) -> Vec<Ray> {
+        offsets
+            .iter()
+            .map(|(x, y)| self.ray_through(px as f64 + x, py as f64 + y))
+            .collect()
+    }
+
+    /*
+        Generates a ray through the center of the pixel that starts from a point on the camera's
+        lens rather than its center (see with_depth_of_field), aimed at where the pixel's ray
+        through the center would cross the focal plane. The points are spread evenly over the lens
+        using the sample index, so a single ray is noisy, and render_depth_of_field averages the
+        colors for sample indices 0 to N - 1 for each pixel instead.
+
+        Without an aperture every sample is the same as ray_for_pixel.
+    */
+    pub fn ray_through_lens(&self, px: usize, py: usize, sample: usize) -> Ray {
+        let (canvas_x, canvas_y) = self.canvas_point(px as f64 + 0.5, py as f64 + 0.5);
+
+        // Uniformly over the disk, which needs the square root to not bunch up in the middle
+        // (starting from 1, as every Halton sequence starts at 0)
+        let radius = (self.aperture / 2.0) * halton(sample + 1, 2).sqrt();
+        let angle = 2.0 * PI * halton(sample + 1, 3);
+
+        // The canvas is one unit in front of the camera, so scaling the point on it puts it on
+        // the focal plane
+        let focal_point = Tuple::point(
+            canvas_x * self.focal_distance,
+            canvas_y * self.focal_distance,
+            -self.focal_distance,
+        );
+        let lens_point = Tuple::point(radius * angle.cos(), radius * angle.sin(), 0.0);
+
+        let inverse = self.transform.inverse().unwrap();
+        let focal_point = (&inverse * &focal_point).unwrap();
+        let origin = (&inverse * &lens_point).unwrap();
+        let direction = (focal_point - origin).normalize();
+
+        Ray::new(origin, direction)
+    }
+
+    // The untransformed coordinates on the canvas of the given position, measured in pixels from
+    // the top left corner
+    fn canvas_point(&self, x: f64, y: f64) -> (f64, f64) {
+        // The offset from the edge of the canvas to the position
+        let x_offset = x * self.pixel_size;
+        let y_offset = y * self.pixel_size;
 
-        // The untransformed coordinates of the pixel in world space
         // (remember that the camera looks toward -z, so +x is to the *left*.)
-        let world_x = self.half_width - x_offset;
-        let world_y = self.half_height - y_offset;
+        (self.half_width - x_offset, self.half_height - y_offset)
+    }
+
+    // Generates a ray that starts at the camera and passes through the given position on the
+    // canvas, measured in pixels from the top left corner
+    fn ray_through(&self, x: f64, y: f64) -> Ray {
+        let (world_x, world_y) = self.canvas_point(x, y);
 
         // Using the camera matrix, transform the canvas point and the origin
         // and then compute the ray's direction vector.
@@ -66,11 +267,11 @@ impl Camera {
     }
 
     pub fn height(&self) -> usize {
-        self.hsize
+        self.vsize
     }
 
     pub fn width(&self) -> usize {
-        self.vsize
+        self.hsize
     }
 }
 
@@ -78,6 +279,8 @@ impl Camera {
 mod tests {
     use crate::matrices::matrix::Matrix;
     use crate::scene::camera::Camera;
+    use crate::tuples::ray::Ray;
+    use crate::tuples::sampling::SamplePattern;
     use crate::tuples::tuple::Tuple;
     use crate::EPSILON;
     use std::f64::consts::{PI, SQRT_2};
@@ -150,4 +353,242 @@ mod tests {
             ray.direction()
         )
     }
+
+    #[test]
+    fn given_a_camera_when_widening_the_field_of_view_should_increase_pixel_size_and_spread_the_rays(
+    ) {
+        // Arrange
+        let mut camera = Camera::new(201, 101, 0.785, Matrix::identity(4));
+
+        let original_pixel_size = camera.pixel_size;
+        let original_corner = camera.ray_for_pixel(0, 0);
+
+        // Act
+        camera.set_field_of_view(PI / 2.0);
+
+        let corner = camera.ray_for_pixel(0, 0);
+        let center = camera.ray_for_pixel(100, 50);
+
+        // Assert
+        assert_eq!(PI / 2.0, camera.field_of_view());
+        assert!(camera.pixel_size > original_pixel_size);
+        assert_eq!(
+            Tuple::vector(0.66519, 0.33259, -0.66851),
+            corner.direction()
+        );
+        assert!(corner.direction().z > original_corner.direction().z);
+        assert_eq!(Tuple::vector(0.0, 0.0, -1.0), center.direction());
+    }
+
+    #[test]
+    fn given_a_camera_when_creating_a_stereo_pair_should_offset_the_eyes_and_converge_on_the_focal_target(
+    ) {
+        // Arrange
+        let camera = Camera::new(
+            101,
+            101,
+            PI / 2.0,
+            Matrix::view_transform(
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            ),
+        );
+
+        // Act
+        let (left, right) = camera.stereo_pair(0.2, 5.0);
+
+        let left_ray = left.ray_for_pixel(50, 50);
+        let right_ray = right.ray_for_pixel(50, 50);
+
+        // Assert
+        assert_eq!(Tuple::point(-0.1, 0.0, -5.0), left_ray.origin());
+        assert_eq!(Tuple::point(0.1, 0.0, -5.0), right_ray.origin());
+        assert_ne!(left_ray.direction(), right_ray.direction());
+
+        let target = Tuple::point(0.0, 0.0, 0.0);
+        let focal_time = (target - left_ray.origin()).magnitude();
+
+        assert_eq!(target, left_ray.position(focal_time));
+        assert_eq!(target, right_ray.position(focal_time));
+    }
+
+    #[test]
+    fn given_a_camera_when_calling_ray_differentials_for_pixel_should_diverge_by_one_pixel_on_the_canvas(
+    ) {
+        // Arrange
+        let camera = Camera::new(201, 201, PI / 2.0, Matrix::identity(4));
+
+        // Where each ray crosses the canvas, which sits at z = -1 in camera space
+        let on_canvas = |ray: Ray| ray.position(-1.0 / ray.direction().z);
+
+        // Act
+        let (main, next_x, next_y) = camera.ray_differentials_for_pixel(40, 70);
+
+        // Assert
+        assert_eq!(camera.ray_for_pixel(40, 70).direction(), main.direction());
+
+        // Moving right across the image goes toward -x in camera space, and down goes toward -y
+        assert_eq!(
+            Tuple::vector(-camera.pixel_size, 0.0, 0.0),
+            on_canvas(next_x) - on_canvas(main)
+        );
+        assert_eq!(
+            Tuple::vector(0.0, -camera.pixel_size, 0.0),
+            on_canvas(next_y) - on_canvas(main)
+        );
+    }
+
+    #[test]
+    fn given_a_camera_when_generating_rays_for_a_pixel_should_keep_every_sample_inside_the_pixel() {
+        // Arrange
+        let camera = Camera::new(201, 201, PI / 2.0, Matrix::identity(4));
+        let on_canvas = |ray: &Ray| ray.position(-1.0 / ray.direction().z);
+
+        // The pixel's corners on the canvas, remembering +x is to the left
+        let left = camera.half_width - 40.0 * camera.pixel_size;
+        let top = camera.half_height - 70.0 * camera.pixel_size;
+
+        // Act
+        let rays = camera.rays_for_pixel(40, 70, SamplePattern::Halton, 8);
+
+        // Assert
+        assert_eq!(8, rays.len());
+
+        for ray in rays.iter() {
+            let point = on_canvas(ray);
+
+            assert!(point.x <= left && point.x >= left - camera.pixel_size);
+            assert!(point.y <= top && point.y >= top - camera.pixel_size);
+        }
+    }
+
+    #[test]
+    fn given_a_camera_when_orbiting_a_target_should_circle_it_while_looking_at_it() {
+        // Arrange
+        let camera = Camera::new(11, 11, PI / 2.0, Matrix::identity(4));
+        let target = Tuple::point(1.0, 2.0, 3.0);
+
+        // Act
+        let front = camera.orbit(target, 5.0, 0.0, 0.0);
+        let side = camera.orbit(target, 5.0, PI / 2.0, 0.0);
+        let above = camera.orbit(target, 5.0, 0.0, PI / 4.0);
+
+        // Assert
+        for (orbiting, expected_origin) in [
+            (front, Tuple::point(1.0, 2.0, -2.0)),
+            (side, Tuple::point(6.0, 2.0, 3.0)),
+            (
+                above,
+                Tuple::point(1.0, 2.0 + 5.0 / SQRT_2, 3.0 - 5.0 / SQRT_2),
+            ),
+        ] {
+            let ray = orbiting.ray_for_pixel(5, 5);
+
+            assert_eq!(11, orbiting.width());
+            assert_eq!(PI / 2.0, orbiting.field_of_view());
+            assert_eq!(expected_origin, ray.origin());
+            assert_eq!((target - expected_origin).normalize(), ray.direction());
+        }
+    }
+
+    #[test]
+    fn given_a_wide_and_a_tall_canvas_when_viewing_a_centred_sphere_should_keep_it_circular_in_both(
+    ) {
+        // Arrange
+        let wide = Camera::new(40, 20, PI / 2.0, Matrix::identity(4));
+        let tall = Camera::new(20, 40, PI / 2.0, Matrix::identity(4));
+
+        // A unit sphere 3 units in front of the camera, covering a fair part of both images
+        let centre = Tuple::point(0.0, 0.0, -3.0);
+        let hits_sphere = |ray: Ray| {
+            let to_centre = centre - ray.origin();
+            let along = Tuple::dot(to_centre, ray.direction());
+
+            to_centre.magnitude().powi(2) - along.powi(2) < 1.0
+        };
+
+        // How many pixels of the sphere there are across the middle row, and down the middle column
+        let extents = |camera: &Camera| {
+            let across = (0..camera.width())
+                .filter(|x| hits_sphere(camera.ray_for_pixel(*x, camera.height() / 2)))
+                .count();
+            let down = (0..camera.height())
+                .filter(|y| hits_sphere(camera.ray_for_pixel(camera.width() / 2, *y)))
+                .count();
+
+            (across, down)
+        };
+
+        // Act
+        let (wide_across, wide_down) = extents(&wide);
+        let (tall_across, tall_down) = extents(&tall);
+
+        // Assert
+        assert_eq!((40, 20), (wide.width(), wide.height()));
+        assert_eq!((20, 40), (tall.width(), tall.height()));
+
+        assert_eq!(wide_across, wide_down);
+        assert_eq!(tall_across, tall_down);
+
+        // The field of view is across the longer side of each, so the sphere is the same size in both
+        assert_eq!(wide_across, tall_down);
+    }
+
+    #[test]
+    fn given_a_camera_with_a_lens_when_sampling_it_should_keep_points_at_the_focal_distance_on_the_same_pixel(
+    ) {
+        // Arrange
+        let camera = Camera::new(
+            11,
+            11,
+            PI / 2.0,
+            (&Matrix::rotation_y(PI / 6.0) * &Matrix::translation(1.0, 2.0, 5.0)).unwrap(),
+        )
+        .with_depth_of_field(0.5, 4.0);
+
+        // Where the pinhole ray through the pixel crosses the focal plane, which is 4 units along
+        // the camera's view direction rather than along the ray itself
+        let pinhole = camera.ray_for_pixel(2, 7);
+        let forward = camera.ray_for_pixel(5, 5).direction();
+        let focused = pinhole.position(4.0 / Tuple::dot(pinhole.direction(), forward));
+
+        // Act
+        let rays: Vec<Ray> = (0..16)
+            .map(|sample| camera.ray_through_lens(2, 7, sample))
+            .collect();
+
+        // Assert
+        for ray in &rays {
+            let to_focused = focused - ray.origin();
+            let along = Tuple::dot(to_focused, ray.direction());
+
+            assert!((to_focused.magnitude().powi(2) - along.powi(2)).abs() < EPSILON);
+        }
+
+        // The rays do actually start from different points spread over the lens
+        assert_ne!(rays[0].origin(), rays[1].origin());
+        assert!(rays
+            .iter()
+            .all(|ray| (ray.origin() - pinhole.origin()).magnitude() <= 0.25 + EPSILON));
+    }
+
+    #[test]
+    fn given_a_camera_without_a_lens_when_sampling_it_should_match_ray_for_pixel() {
+        // Arrange
+        let camera = Camera::new(
+            9,
+            5,
+            PI / 3.0,
+            (&Matrix::rotation_y(PI / 4.0) * &Matrix::translation(0.0, -2.0, 5.0)).unwrap(),
+        );
+
+        // Act
+        let through_lens = camera.ray_through_lens(3, 1, 7);
+        let through_pinhole = camera.ray_for_pixel(3, 1);
+
+        // Assert
+        assert_eq!(through_pinhole.origin(), through_lens.origin());
+        assert_eq!(through_pinhole.direction(), through_lens.direction());
+    }
 }
@@ -1,7 +1,9 @@
 use std::sync::Arc;
 
 use crate::geometry::shape::Shape;
+use crate::tuples::ray::Ray;
 use crate::tuples::tuple::Tuple;
+use crate::EPSILON;
 
 pub struct Computations {
     pub time: f64,
@@ -15,6 +17,12 @@ pub struct Computations {
     pub normalv: Tuple,
     pub reflectv: Tuple,
     pub inside: bool,
+    // How far the hit point moves across the surface between this pixel and the next one along in
+    // x and y. Left as zero vectors unless set from ray differentials.
+    pub dpdx: Tuple,
+    pub dpdy: Tuple,
+    // The texture coordinates at the hit, if the object has any (see Shape::texture_uv_at)
+    pub uv: Option<(f64, f64)>,
 }
 
 impl Computations {
@@ -49,6 +57,31 @@ impl Computations {
             normalv,
             reflectv,
             inside,
+            dpdx: Tuple::vector(0.0, 0.0, 0.0),
+            dpdy: Tuple::vector(0.0, 0.0, 0.0),
+            uv: None,
         };
     }
+
+    /*
+        Estimates the footprint of the pixel on the surface, by finding where the rays through the
+        neighbouring pixels cross the plane tangent to the surface at the hit point. Rays that run
+        parallel to the tangent plane never cross it, so those leave the differential at zero.
+    */
+    pub fn set_differentials(&mut self, rx: &Ray, ry: &Ray) {
+        self.dpdx = self.offset_on_tangent_plane(rx);
+        self.dpdy = self.offset_on_tangent_plane(ry);
+    }
+
+    fn offset_on_tangent_plane(&self, ray: &Ray) -> Tuple {
+        let denominator = Tuple::dot(ray.direction(), self.normalv);
+
+        if denominator.abs() < EPSILON {
+            return Tuple::vector(0.0, 0.0, 0.0);
+        }
+
+        let time = Tuple::dot(self.point - ray.origin(), self.normalv) / denominator;
+
+        ray.position(time) - self.point
+    }
 }
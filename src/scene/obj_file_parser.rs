@@ -1,11 +1,12 @@
+use crate::error::RaytracerError;
 use crate::geometry::group::Group;
 use crate::geometry::shape::Shape;
 use crate::geometry::smooth_triangle::SmoothTriangle;
 use crate::geometry::triangle::Triangle;
+use crate::geometry::triangle_mesh::TriangleMesh;
 use crate::materials::material::Material;
 use crate::matrices::matrix::Matrix;
 use crate::tuples::tuple::Tuple;
-use anyhow::anyhow;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io;
@@ -13,16 +14,20 @@ use std::io::BufRead;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::thread;
 use uuid::Uuid;
 
 const VERTEX_COMMAND: &str = "v";
 const FACE_COMMAND: &str = "f";
 const GROUP_COMMAND: &str = "g";
+const OBJECT_COMMAND: &str = "o";
 const VERTEX_NORMAL_COMMAND: &str = "vn";
+const TEXTURE_COORDINATE_COMMAND: &str = "vt";
 
 pub struct ObjFileParser {
     vertices: Vec<Tuple>,
     vertex_normals: Vec<Tuple>,
+    texture_coords: Vec<(f64, f64)>,
     groups: HashMap<String, Arc<Group>>,
     default_group: Uuid,
     current_group: String,
@@ -33,21 +38,62 @@ pub struct ObjFileParser {
 
 struct Indices {
     pub index: usize,
+    pub texture_index: Option<usize>,
     pub normal_index: Option<usize>,
 }
 
+// The faces (or the triangles they're fanned out into) along with the name of the group each is in
+type GroupedFaces = Vec<(String, Vec<Indices>)>;
+type GroupedTriangles = Vec<(String, Vec<Arc<dyn Shape>>)>;
+
 impl ObjFileParser {
     pub fn parse_obj_file(
         file_path: String,
         transform: Arc<Matrix>,
         material: Arc<dyn Material>,
         casts_shadow: bool,
-    ) -> Result<ObjFileParser, anyhow::Error> {
+    ) -> Result<ObjFileParser, RaytracerError> {
+        let default_group = Uuid::new_v4();
+
+        let mut result = ObjFileParser {
+            vertices: Vec::new(),
+            vertex_normals: Vec::new(),
+            texture_coords: Vec::new(),
+            groups: HashMap::new(),
+            default_group,
+            current_group: default_group.to_string(),
+            transform,
+            material,
+            casts_shadow,
+        };
+
+        result.parse_lines(&file_path, &mut ObjFileParser::handle_face_command)?;
+
+        Ok(result)
+    }
+
+    /*
+        Same as parse_obj_file, but triangulates the faces across multiple threads. The vertices,
+        normals and group names still have to be read in order, since faces refer back to them, so
+        that's done first while noting down each face's indices along with the group it's in. Each
+        face can then be triangulated independently, and the triangles are added to their groups
+        in the same order they'd have been in had the file been parsed sequentially.
+    */
+    pub fn parse_obj_file_parallel(
+        file_path: String,
+        transform: Arc<Matrix>,
+        material: Arc<dyn Material>,
+        casts_shadow: bool,
+        num_of_threads: usize,
+    ) -> Result<ObjFileParser, RaytracerError> {
+        assert!(num_of_threads > 0);
+
         let default_group = Uuid::new_v4();
 
         let mut result = ObjFileParser {
             vertices: Vec::new(),
             vertex_normals: Vec::new(),
+            texture_coords: Vec::new(),
             groups: HashMap::new(),
             default_group,
             current_group: default_group.to_string(),
@@ -56,36 +102,241 @@ impl ObjFileParser {
             casts_shadow,
         };
 
+        let mut faces: GroupedFaces = Vec::new();
+
+        result.parse_lines(&file_path, &mut |parser, vertex_indices| {
+            faces.push((parser.current_group.clone(), vertex_indices));
+        })?;
+
+        // Give each thread one contiguous run of the faces, so the results can simply be joined
+        // back up in order afterwards
+        let chunk_size = faces.len().div_ceil(num_of_threads).max(1);
+        let mut chunks: Vec<GroupedFaces> = Vec::new();
+
+        while !faces.is_empty() {
+            let rest = faces.split_off(chunk_size.min(faces.len()));
+            chunks.push(faces);
+            faces = rest;
+        }
+
+        let parser = &result;
+
+        let triangulated: Vec<GroupedTriangles> = thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .into_iter()
+                            .map(|(group_name, indices)| {
+                                (group_name, parser.fan_triangulation(indices))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        for (group_name, triangles) in triangulated.into_iter().flatten() {
+            result.add_to_group(group_name, triangles);
+        }
+
+        Ok(result)
+    }
+
+    // Reads each line of the file in turn and hands it to the handler for its command, apart from
+    // faces which go to the given handler since they're either triangulated there and then or
+    // saved up to be triangulated later on. Faces are checked before being handed over, so the
+    // handler can rely on their indices referring to vertices and normals that exist.
+    fn parse_lines<F>(&mut self, file_path: &str, handle_face: &mut F) -> Result<(), RaytracerError>
+    where
+        F: FnMut(&mut ObjFileParser, Vec<Indices>),
+    {
         // Open file
-        if let Ok(lines) = Self::read_lines(file_path.clone()) {
-            for line in lines.flatten() {
-                if !line.trim().is_empty() {
-                    let mut parameters: Vec<&str> = line.split(' ').collect();
-
-                    // Remove any empty parameters caused by multiple spaces
-                    parameters.retain(|&x| x != "");
-
-                    let command = parameters[0].to_lowercase();
-
-                    if command == VERTEX_COMMAND {
-                        result.handle_vertex_command(parameters)?;
-                    } else if command == FACE_COMMAND {
-                        result.handle_face_command(parameters)?;
-                    } else if command == GROUP_COMMAND {
-                        result.handle_group_command(parameters);
-                    } else if command == VERTEX_NORMAL_COMMAND {
-                        result.handle_vertex_normal_command(parameters)?;
+        let lines = Self::read_lines(file_path)?;
+
+        for line in lines.flatten() {
+            if !line.trim().is_empty() {
+                let mut parameters: Vec<&str> = line.split(' ').collect();
+
+                // Remove any empty parameters caused by multiple spaces
+                parameters.retain(|&x| x != "");
+
+                let command = parameters[0].to_lowercase();
+
+                if command == VERTEX_COMMAND {
+                    self.handle_vertex_command(parameters)?;
+                } else if command == FACE_COMMAND {
+                    let vertex_indices = self.parse_face_indices(&parameters)?;
+
+                    handle_face(self, vertex_indices);
+                } else if command == GROUP_COMMAND || command == OBJECT_COMMAND {
+                    // Objects are treated the same as groups, so faces get added to whichever
+                    // of the two was named most recently
+                    self.handle_group_command(parameters);
+                } else if command == VERTEX_NORMAL_COMMAND {
+                    self.handle_vertex_normal_command(parameters)?;
+                } else if command == TEXTURE_COORDINATE_COMMAND {
+                    self.handle_texture_coordinate_command(parameters)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /*
+        Parses an OBJ file straight into the flat buffers of a TriangleMesh, instead of creating a
+        separate triangle shape for every face. Makes two passes over the file, the first just
+        counts the vertices, normals and triangles so that the buffers can be allocated once at
+        the right size. Groups and objects are ignored, everything ends up in the one mesh.
+    */
+    pub fn parse_obj_file_to_mesh(file_path: String) -> Result<TriangleMesh, RaytracerError> {
+        let (vertices, normals, triangles) = Self::count_mesh_elements(&file_path)?;
+
+        let mut mesh = TriangleMesh::with_capacity(vertices, normals, triangles);
+
+        for line in Self::read_lines(&file_path)?.map_while(Result::ok) {
+            let mut parameters = line.split_whitespace();
+
+            let command = match parameters.next() {
+                Some(command) => command.to_lowercase(),
+                None => continue,
+            };
+
+            if command == VERTEX_COMMAND {
+                let (x, y, z) = Self::parse_coordinates(&mut parameters)?;
+                mesh.add_vertex(Tuple::point(x, y, z));
+            } else if command == VERTEX_NORMAL_COMMAND {
+                let (x, y, z) = Self::parse_coordinates(&mut parameters)?;
+                mesh.add_normal(Tuple::vector(x, y, z));
+            } else if command == FACE_COMMAND {
+                // Fan triangulate as the face is read in, so that no list of indices is needed
+                let (vertex_count, normal_count) = (mesh.vertex_count(), mesh.normal_count());
+                let parse_indices = |parameter: Option<&str>| {
+                    Self::parse_vertex_indices(parameter, vertex_count, normal_count)
+                };
+
+                let first = parse_indices(parameters.next())?;
+                let mut previous = parse_indices(parameters.next())?;
+
+                for parameter in parameters {
+                    let current = parse_indices(Some(parameter))?;
+
+                    let indices = [first.index, previous.index, current.index];
+
+                    match (
+                        first.normal_index,
+                        previous.normal_index,
+                        current.normal_index,
+                    ) {
+                        (Some(n1), Some(n2), Some(n3)) => {
+                            mesh.add_smooth_triangle(indices, [n1, n2, n3])
+                        }
+                        (None, None, None) => mesh.add_triangle(indices),
+                        _ => Err(RaytracerError::Parse(format!(
+                            "Faces must either all have vertex normals or none in: {}",
+                            file_path
+                        )))?,
                     }
+
+                    previous = current;
                 }
             }
+        }
 
-            Ok(result)
-        } else {
-            Err(anyhow!("Error: Could not read lines from: {}", file_path))?
+        Ok(mesh)
+    }
+
+    fn count_mesh_elements(file_path: &str) -> Result<(usize, usize, usize), RaytracerError> {
+        let mut vertices = 0;
+        let mut normals = 0;
+        let mut triangles = 0;
+
+        for line in Self::read_lines(file_path)?.map_while(Result::ok) {
+            let mut parameters = line.split_whitespace();
+
+            match parameters.next().map(|command| command.to_lowercase()) {
+                Some(command) if command == VERTEX_COMMAND => vertices += 1,
+                Some(command) if command == VERTEX_NORMAL_COMMAND => normals += 1,
+                // A face with n vertices gets fanned out into n - 2 triangles
+                Some(command) if command == FACE_COMMAND => {
+                    triangles += parameters.count().saturating_sub(2)
+                }
+                _ => {}
+            }
         }
+
+        Ok((vertices, normals, triangles))
+    }
+
+    fn parse_coordinates<'a>(
+        parameters: &mut impl Iterator<Item = &'a str>,
+    ) -> Result<(f64, f64, f64), RaytracerError> {
+        let mut next = || -> Result<f64, RaytracerError> {
+            let parameter = parameters.next().ok_or(RaytracerError::Parse(
+                "Expected three coordinates".to_string(),
+            ))?;
+
+            Ok(f64::from_str(parameter)?)
+        };
+
+        Ok((next()?, next()?, next()?))
+    }
+
+    // Parses a face parameter of the form v, v/vt, v//vn or v/vt/vn into zero based indices,
+    // checking that they refer to one of the vertices and normals read in so far
+    fn parse_vertex_indices(
+        parameter: Option<&str>,
+        vertex_count: usize,
+        normal_count: usize,
+    ) -> Result<Indices, RaytracerError> {
+        let parameter = parameter.ok_or(RaytracerError::Parse(
+            "Expected at least three face vertices".to_string(),
+        ))?;
+
+        let mut split = parameter.split('/');
+
+        let index = Self::zero_based_index(split.next().unwrap(), vertex_count, "vertex")?;
+        // Files in the wild sometimes have texture indices that don't refer to anything, so rather
+        // than failing those are just treated as missing when looked up
+        let texture_index = match split.next() {
+            Some(texture) if !texture.is_empty() => usize::from_str(texture)?.checked_sub(1),
+            _ => None,
+        };
+        let normal_index = match split.next() {
+            Some(normal) if !normal.is_empty() => {
+                Some(Self::zero_based_index(normal, normal_count, "normal")?)
+            }
+            _ => None,
+        };
+
+        Ok(Indices {
+            index,
+            texture_index,
+            normal_index,
+        })
+    }
+
+    // OBJ indices start from one, so zero (or anything past the last element) doesn't refer to any
+    fn zero_based_index(index: &str, count: usize, element: &str) -> Result<usize, RaytracerError> {
+        usize::from_str(index)?
+            .checked_sub(1)
+            .filter(|index| *index < count)
+            .ok_or_else(|| {
+                RaytracerError::Parse(format!(
+                    "Face refers to {} {}, but there are only {}",
+                    element, index, count
+                ))
+            })
     }
 
-    fn handle_vertex_command(&mut self, parameters: Vec<&str>) -> Result<(), anyhow::Error> {
+    fn handle_vertex_command(&mut self, parameters: Vec<&str>) -> Result<(), RaytracerError> {
         let x = f64::from_str(parameters[1])?;
         let y = f64::from_str(parameters[2])?;
         let z = f64::from_str(parameters[3])?;
@@ -95,44 +346,56 @@ impl ObjFileParser {
         Ok(())
     }
 
-    fn handle_face_command(&mut self, parameters: Vec<&str>) -> Result<(), anyhow::Error> {
-        let mut vertex_indices: Vec<Indices> = Vec::new();
-
-        for i in 1..parameters.len() {
-            let option = parameters[i];
-
-            let split: Vec<&str> = option.split('/').collect();
+    fn handle_face_command(&mut self, vertex_indices: Vec<Indices>) {
+        let triangles = self.fan_triangulation(vertex_indices);
 
-            if split.len() == 1 {
-                let index = usize::from_str(option)?;
+        self.add_to_group(self.current_group.clone(), triangles);
+    }
 
-                vertex_indices.push(Indices {
-                    index,
-                    normal_index: None,
-                })
-            } else {
-                let index = usize::from_str(split[0])?;
-                let normal_index = usize::from_str(split[2])?;
+    // Parses each of the face's parameters, making sure there are enough of them to make up at
+    // least one triangle and that either every corner has a normal or none of them do
+    fn parse_face_indices(&self, parameters: &[&str]) -> Result<Vec<Indices>, RaytracerError> {
+        if parameters.len() < 4 {
+            return Err(RaytracerError::Parse(
+                "Expected at least three face vertices".to_string(),
+            ));
+        }
 
-                vertex_indices.push(Indices {
-                    index,
-                    normal_index: Some(normal_index),
-                })
-            }
+        let vertex_indices = parameters
+            .iter()
+            .skip(1)
+            .map(|parameter| {
+                Self::parse_vertex_indices(
+                    Some(parameter),
+                    self.vertices.len(),
+                    self.vertex_normals.len(),
+                )
+            })
+            .collect::<Result<Vec<Indices>, RaytracerError>>()?;
+
+        let with_normals = vertex_indices
+            .iter()
+            .filter(|indices| indices.normal_index.is_some())
+            .count();
+
+        if with_normals != 0 && with_normals != vertex_indices.len() {
+            return Err(RaytracerError::Parse(
+                "Faces must either all have vertex normals or none".to_string(),
+            ));
         }
 
-        let triangles = self.fan_triangulation(vertex_indices);
+        Ok(vertex_indices)
+    }
 
+    fn add_to_group(&mut self, group_name: String, triangles: Vec<Arc<dyn Shape>>) {
         // If the group is already present, just add the new triangles as children
-        if let Some(group) = self.groups.get(&self.current_group) {
+        if let Some(group) = self.groups.get(&group_name) {
             group.add_children(triangles)
         } else {
             let group = Arc::new(Group::default());
             group.add_children(triangles);
-            self.groups.insert(self.current_group.clone(), group);
+            self.groups.insert(group_name, group);
         }
-
-        Ok(())
     }
 
     fn handle_group_command(&mut self, parameters: Vec<&str>) {
@@ -141,7 +404,10 @@ impl ObjFileParser {
         self.current_group = group_name.to_string();
     }
 
-    fn handle_vertex_normal_command(&mut self, parameters: Vec<&str>) -> Result<(), anyhow::Error> {
+    fn handle_vertex_normal_command(
+        &mut self,
+        parameters: Vec<&str>,
+    ) -> Result<(), RaytracerError> {
         let x = f64::from_str(parameters[1])?;
         let y = f64::from_str(parameters[2])?;
         let z = f64::from_str(parameters[3])?;
@@ -151,9 +417,25 @@ impl ObjFileParser {
         Ok(())
     }
 
+    fn handle_texture_coordinate_command(
+        &mut self,
+        parameters: Vec<&str>,
+    ) -> Result<(), RaytracerError> {
+        let u = f64::from_str(parameters[1])?;
+        // The v coordinate is optional, and defaults to zero
+        let v = match parameters.get(2) {
+            Some(v) => f64::from_str(v)?,
+            None => 0.0,
+        };
+
+        self.texture_coords.push((u, v));
+
+        Ok(())
+    }
+
     // Assumes we're dealing with convex polygons - i.e. those whose interior angles are all less
     // than or equal to 180 degrees
-    fn fan_triangulation(&mut self, vertex_indices: Vec<Indices>) -> Vec<Arc<dyn Shape>> {
+    fn fan_triangulation(&self, vertex_indices: Vec<Indices>) -> Vec<Arc<dyn Shape>> {
         let mut triangles: Vec<Arc<dyn Shape>> = Vec::new();
 
         for index in 1..(vertex_indices.len() - 1) {
@@ -161,16 +443,31 @@ impl ObjFileParser {
             let p2 = self.get_vertex(vertex_indices[index].index);
             let p3 = self.get_vertex(vertex_indices[index + 1].index);
 
+            // Only use the texture coordinates if every corner has one
+            let texture_coords = match (
+                self.get_texture_coord(vertex_indices[0].texture_index),
+                self.get_texture_coord(vertex_indices[index].texture_index),
+                self.get_texture_coord(vertex_indices[index + 1].texture_index),
+            ) {
+                (Some(uv1), Some(uv2), Some(uv3)) => Some((uv1, uv2, uv3)),
+                _ => None,
+            };
+
             // If there's no normal index we're dealing with a non-smooth triangle
             if vertex_indices[0].normal_index.is_none() {
-                triangles.push(Arc::new(Triangle::new(
+                let triangle = Triangle::new(
                     p1,
                     p2,
                     p3,
                     self.transform.clone(),
                     self.material.clone(),
                     self.casts_shadow,
-                )));
+                );
+
+                triangles.push(Arc::new(match texture_coords {
+                    Some((uv1, uv2, uv3)) => triangle.with_texture_coords(uv1, uv2, uv3),
+                    None => triangle,
+                }));
             } else {
                 let n1 = self.get_vertex_normal(
                     vertex_indices[0]
@@ -188,7 +485,7 @@ impl ObjFileParser {
                         .expect("Error: Expected vertex normal for face to be present."),
                 );
 
-                triangles.push(Arc::new(SmoothTriangle::new(
+                let triangle = SmoothTriangle::new(
                     p1,
                     p2,
                     p3,
@@ -198,7 +495,12 @@ impl ObjFileParser {
                     self.transform.clone(),
                     self.material.clone(),
                     self.casts_shadow,
-                )));
+                );
+
+                triangles.push(Arc::new(match texture_coords {
+                    Some((uv1, uv2, uv3)) => triangle.with_texture_coords(uv1, uv2, uv3),
+                    None => triangle,
+                }));
             }
         }
 
@@ -206,20 +508,35 @@ impl ObjFileParser {
     }
 
     fn get_vertex(&self, index: usize) -> Tuple {
-        self.vertices[index - 1]
+        self.vertices[index]
     }
 
     fn get_vertex_normal(&self, index: usize) -> Tuple {
-        self.vertex_normals[index - 1]
+        self.vertex_normals[index]
+    }
+
+    fn get_texture_coord(&self, index: Option<usize>) -> Option<(f64, f64)> {
+        self.texture_coords.get(index?).copied()
     }
 
     // The output is wrapped in a Result to allow matching on errors.
     // Returns an Iterator to the Reader of the lines of the file.
-    fn read_lines<P>(file_path: P) -> io::Result<io::Lines<io::BufReader<File>>>
+    fn read_lines<P>(file_path: P) -> Result<io::Lines<io::BufReader<File>>, RaytracerError>
     where
         P: AsRef<Path>,
     {
-        let file = File::open(file_path)?;
+        // Name the file in the error, since the OS's message on its own doesn't
+        let file = File::open(&file_path).map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!(
+                    "Could not read lines from {}: {}",
+                    file_path.as_ref().display(),
+                    error
+                ),
+            )
+        })?;
+
         Ok(io::BufReader::new(file).lines())
     }
 
@@ -236,10 +553,12 @@ impl ObjFileParser {
 
 #[cfg(test)]
 mod tests {
+    use crate::error::RaytracerError;
     use crate::geometry::shape::Shape;
     use crate::materials::phong::Phong;
     use crate::matrices::matrix::Matrix;
     use crate::scene::obj_file_parser::ObjFileParser;
+    use crate::tuples::intersection::Intersection;
     use crate::tuples::tuple::Tuple;
     use std::sync::Arc;
 
@@ -456,4 +775,231 @@ mod tests {
         assert_eq!(t1_normals.1, t2_normals.1);
         assert_eq!(t1_normals.2, t2_normals.2);
     }
+
+    #[test]
+    fn given_an_obj_file_with_named_objects_when_parsing_should_put_each_object_in_its_own_group() {
+        // Arrange
+        let file_path = "tests/obj_files/named_objects.obj";
+
+        // Act
+        let result = ObjFileParser::parse_obj_file(
+            file_path.to_string(),
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            true,
+        )
+        .unwrap();
+
+        // Assert
+        assert_eq!(2, result.groups.len());
+
+        let cube = result.groups.get("Cube").unwrap();
+        let sphere = result.groups.get("Sphere").unwrap();
+
+        assert_eq!(3, cube.num_of_children());
+        assert_eq!(1, sphere.num_of_children());
+
+        let sphere_points = sphere.get_child(0).unwrap().points();
+
+        assert_eq!(result.vertices[0], sphere_points.0);
+        assert_eq!(result.vertices[2], sphere_points.1);
+        assert_eq!(result.vertices[3], sphere_points.2);
+    }
+
+    #[test]
+    fn given_a_large_obj_file_when_parsing_to_a_mesh_should_match_the_triangle_count_without_reallocating(
+    ) {
+        // Arrange
+        let file_path = "tests/obj_files/teapot.obj";
+
+        let parsed = ObjFileParser::parse_obj_file(
+            file_path.to_string(),
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            true,
+        )
+        .unwrap();
+
+        let expected: usize = parsed.groups.values().map(|g| g.num_of_children()).sum();
+
+        // Act
+        let mesh = ObjFileParser::parse_obj_file_to_mesh(file_path.to_string()).unwrap();
+
+        // Assert
+        assert_eq!(expected, mesh.triangle_count());
+        assert_eq!(parsed.vertices.len(), mesh.vertex_count());
+
+        assert_eq!(expected * 3, mesh.indices().len());
+
+        let (p1, p2, p3) = mesh.triangle(0);
+        let first = parsed.groups.values().next().unwrap().get_child(0).unwrap();
+
+        assert_eq!(first.points(), (p1, p2, p3));
+    }
+
+    #[test]
+    fn given_an_obj_file_with_faces_with_normals_when_parsing_to_a_mesh_should_be_smooth() {
+        // Arrange
+        let file_path = "tests/obj_files/faces_with_normals.obj";
+
+        // Act
+        let mesh = ObjFileParser::parse_obj_file_to_mesh(file_path.to_string()).unwrap();
+
+        // Assert
+        assert_eq!(2, mesh.triangle_count());
+        assert!(mesh.is_smooth());
+        assert_eq!(
+            Some((
+                Tuple::vector(0.0, 1.0, 0.0),
+                Tuple::vector(-1.0, 0.0, 0.0),
+                Tuple::vector(1.0, 0.0, 0.0)
+            )),
+            mesh.triangle_normals(0)
+        );
+    }
+
+    #[test]
+    fn given_an_obj_file_with_out_of_range_face_indices_when_parsing_to_a_mesh_should_return_an_error(
+    ) {
+        // Arrange
+        let zero_index = "tests/obj_files/zero_face_index.obj";
+        let missing_normal = "tests/obj_files/missing_face_normal.obj";
+
+        // Act
+        let zero_index_result = ObjFileParser::parse_obj_file_to_mesh(zero_index.to_string());
+        let missing_normal_result =
+            ObjFileParser::parse_obj_file_to_mesh(missing_normal.to_string());
+
+        // Assert
+        assert!(matches!(zero_index_result, Err(RaytracerError::Parse(_))));
+        assert!(matches!(
+            missing_normal_result,
+            Err(RaytracerError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn given_obj_files_with_invalid_faces_when_parsing_into_groups_should_return_an_error() {
+        // Arrange
+        let file_paths = [
+            "tests/obj_files/zero_face_index.obj",
+            "tests/obj_files/missing_face_normal.obj",
+            "tests/obj_files/degenerate_face.obj",
+        ];
+
+        for file_path in file_paths {
+            // Act
+            let sequential = ObjFileParser::parse_obj_file(
+                file_path.to_string(),
+                Arc::new(Matrix::identity(4)),
+                Arc::new(Phong::default()),
+                true,
+            );
+            let parallel = ObjFileParser::parse_obj_file_parallel(
+                file_path.to_string(),
+                Arc::new(Matrix::identity(4)),
+                Arc::new(Phong::default()),
+                true,
+                2,
+            );
+
+            // Assert
+            assert!(matches!(sequential, Err(RaytracerError::Parse(_))));
+            assert!(matches!(parallel, Err(RaytracerError::Parse(_))));
+        }
+    }
+
+    #[test]
+    fn given_a_medium_obj_file_when_parsing_in_parallel_should_match_the_sequential_parse() {
+        // Arrange
+        let file_path = "tests/obj_files/medium.obj";
+
+        let parse_with = |num_of_threads: Option<usize>| {
+            let transform = Arc::new(Matrix::identity(4));
+            let material = Arc::new(Phong::default());
+
+            match num_of_threads {
+                Some(num_of_threads) => ObjFileParser::parse_obj_file_parallel(
+                    file_path.to_string(),
+                    transform,
+                    material,
+                    true,
+                    num_of_threads,
+                ),
+                None => {
+                    ObjFileParser::parse_obj_file(file_path.to_string(), transform, material, true)
+                }
+            }
+            .unwrap()
+        };
+
+        // Act
+        let sequential = parse_with(None);
+        let parallel = parse_with(Some(4));
+
+        // Assert
+        let mut names: Vec<&String> = parallel.groups.keys().collect();
+        names.sort();
+
+        assert_eq!(vec!["back", "front", "middle"], names);
+        assert_eq!(sequential.groups.len(), parallel.groups.len());
+
+        for (name, expected_count) in [("front", 192), ("middle", 160), ("back", 160)] {
+            let expected = sequential.groups.get(name).unwrap();
+            let actual = parallel.groups.get(name).unwrap();
+
+            assert_eq!(expected_count, expected.num_of_children());
+            assert_eq!(expected_count, actual.num_of_children());
+
+            for i in 0..expected_count {
+                let expected_child = expected.get_child(i).unwrap();
+                let actual_child = actual.get_child(i).unwrap();
+
+                assert_eq!(expected_child.points(), actual_child.points());
+            }
+        }
+    }
+
+    #[test]
+    fn given_an_obj_file_with_texture_coordinates_when_parsing_should_give_them_to_each_triangle() {
+        // Arrange
+        let file_path = "tests/obj_files/textured_quad.obj";
+
+        // Act
+        let result = ObjFileParser::parse_obj_file(
+            file_path.to_string(),
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            true,
+        )
+        .unwrap();
+
+        // Assert
+        assert_eq!(
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            result.texture_coords
+        );
+
+        let quad = result
+            .groups
+            .get(&result.default_group.to_string())
+            .unwrap();
+        let smooth = result.groups.get("smooth").unwrap();
+
+        // The quad is fanned out into two triangles, sharing the first corner
+        let first = quad.get_child(0).unwrap();
+        let second = quad.get_child(1).unwrap();
+        let third = smooth.get_child(0).unwrap();
+
+        let corner = |shape: &Arc<dyn Shape>, u: f64, v: f64| {
+            shape.texture_uv_at(&Intersection::new_with_uv(1.0, shape.clone(), u, v))
+        };
+
+        assert_eq!(Some((0.0, 0.0)), corner(&first, 0.0, 0.0));
+        assert_eq!(Some((1.0, 0.0)), corner(&first, 1.0, 0.0));
+        assert_eq!(Some((1.0, 1.0)), corner(&first, 0.0, 1.0));
+        assert_eq!(Some((1.0, 1.0)), corner(&second, 1.0, 0.0));
+        assert_eq!(Some((0.0, 1.0)), corner(&second, 0.0, 1.0));
+        assert_eq!(Some((0.5, 0.5)), corner(&third, 0.0, 0.5));
+    }
 }
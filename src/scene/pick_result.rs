@@ -0,0 +1,13 @@
+use uuid::Uuid;
+
+use crate::tuples::tuple::Tuple;
+
+// What a ray hit when picking from outside the renderer (e.g. clicking on an object in an editor),
+// see World::pick. The normal faces back towards the ray, same as when shading.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PickResult {
+    pub object_id: Uuid,
+    pub point: Tuple,
+    pub normal: Tuple,
+    pub distance: f64,
+}
@@ -0,0 +1,133 @@
+use std::sync::{Arc, Mutex};
+
+use crate::scene::camera::Camera;
+use crate::scene::world::World;
+use crate::tuples::color::{Color, ColorSpace};
+use crate::tuples::sampling::SeededRng;
+use crate::tuples::tuple::Tuple;
+use crate::window::canvas::Canvas;
+use crate::{render_pixels, MAX_RAY_RECURSION_DEPTH};
+
+/*
+    Builds up a path traced render one sample per pixel at a time, rather than blocking until all
+    of them are done like render_gi does. After each pass the running average can be shown, so the
+    image visibly converges as more passes are added.
+
+    Each pixel keeps its own generator between passes, seeded the same way as in render_gi, so
+    after N passes the result matches an N sample render_gi of the same scene.
+*/
+pub struct RenderAccumulator {
+    average: Canvas,
+    samples: usize,
+    rngs: Arc<Vec<Mutex<SeededRng>>>,
+}
+
+impl RenderAccumulator {
+    pub fn new(width: usize, height: usize) -> RenderAccumulator {
+        let mut average = Canvas::new(width, height);
+        average.set_color_space(ColorSpace::Linear);
+
+        // Stored row by row, i.e. the generator for (x, y) is at y * width + x
+        let rngs = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| Mutex::new(SeededRng::from_tuple(Tuple::point(x as f64, y as f64, 0.0))))
+            .collect();
+
+        RenderAccumulator {
+            average,
+            samples: 0,
+            rngs: Arc::new(rngs),
+        }
+    }
+
+    // Traces one more path through every pixel and folds it into the running average
+    pub fn add_pass(&mut self, world: Arc<World>, camera: Arc<Camera>) {
+        assert_eq!(self.average.width(), camera.width());
+        assert_eq!(self.average.height(), camera.height());
+
+        let width = camera.width();
+        let rngs = self.rngs.clone();
+
+        let pass = render_pixels(world, camera, move |world, camera, x, y| {
+            let ray = camera.ray_for_pixel(x, y);
+
+            // Only the thread working on this pixel ever takes its lock, so it's never contended
+            let mut rng = rngs[y * width + x].lock().unwrap();
+
+            world.color_at_gi(&ray, MAX_RAY_RECURSION_DEPTH, &mut rng)
+        });
+
+        self.samples += 1;
+
+        for y in 0..self.average.height() {
+            for x in 0..self.average.width() {
+                let previous = *self.average.pixel_at(x, y).unwrap();
+                let sample = *pass.pixel_at(x, y).unwrap();
+
+                // Nudge the average towards the new sample, by less the more samples there are
+                let updated: Color = previous + (sample - previous) / self.samples as f64;
+
+                self.average.write_pixel(x, y, updated).unwrap();
+            }
+        }
+    }
+
+    pub fn average(&self) -> &Canvas {
+        &self.average
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrices::matrix::Matrix;
+    use crate::render_gi;
+    use crate::scene::camera::Camera;
+    use crate::scene::render_accumulator::RenderAccumulator;
+    use crate::scene::world::World;
+    use crate::tuples::tuple::Tuple;
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    #[test]
+    fn given_several_single_sample_passes_when_accumulating_should_match_a_render_with_that_many_samples(
+    ) {
+        // Arrange
+        let world = Arc::new(World::default());
+        let camera = Arc::new(Camera::new(
+            6,
+            6,
+            PI / 3.0,
+            Matrix::view_transform(
+                Tuple::point(0.0, 0.0, -5.0),
+                Tuple::point(0.0, 0.0, 0.0),
+                Tuple::vector(0.0, 1.0, 0.0),
+            ),
+        ));
+
+        let passes = 4;
+        let mut accumulator = RenderAccumulator::new(camera.width(), camera.height());
+
+        // Act
+        for _ in 0..passes {
+            accumulator.add_pass(world.clone(), camera.clone());
+        }
+
+        let expected = render_gi(world, camera.clone(), passes);
+
+        // Assert
+        assert_eq!(passes, accumulator.samples());
+
+        for y in 0..camera.height() {
+            for x in 0..camera.width() {
+                assert_eq!(
+                    expected.pixel_at(x, y).unwrap(),
+                    accumulator.average().pixel_at(x, y).unwrap()
+                );
+            }
+        }
+    }
+}
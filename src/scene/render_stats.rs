@@ -0,0 +1,188 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::RaytracerError;
+use crate::scene::camera::Camera;
+use crate::Config;
+
+/*
+    What went into a render and how long it took, so that it can be reproduced exactly later on.
+    run writes these out next to the image as a small JSON sidecar, e.g. render.meta.json beside
+    render.ppm.
+
+    There's no seed to record, since the generators used for path tracing are seeded from each
+    pixel's coordinates, so the same settings always give the same image.
+*/
+#[derive(Debug)]
+pub struct RenderStats {
+    pub field_of_view: f64,
+    pub camera_transform: Vec<Vec<f64>>, // Row by row
+    pub samples_per_pixel: f64, // Averaged over the pixels, since adaptive antialiasing varies it
+    pub max_recursion_depth: usize,
+    pub render_time: Duration,
+}
+
+impl RenderStats {
+    pub fn new(
+        camera: &Camera,
+        samples_per_pixel: f64,
+        max_recursion_depth: usize,
+        render_time: Duration,
+    ) -> RenderStats {
+        let transform = camera.transform();
+
+        let camera_transform = (0..transform.num_rows())
+            .map(|row| {
+                (0..transform.num_columns())
+                    .map(|column| *transform.get(row, column).unwrap())
+                    .collect()
+            })
+            .collect();
+
+        RenderStats {
+            field_of_view: camera.field_of_view(),
+            camera_transform,
+            samples_per_pixel,
+            max_recursion_depth,
+            render_time,
+        }
+    }
+
+    // Where the sidecar goes for an image at the given path, i.e. with its extension swapped
+    pub fn sidecar_path(image_path: &str) -> String {
+        Path::new(image_path)
+            .with_extension("meta.json")
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn to_json(&self, config: &Config) -> Result<String, RaytracerError> {
+        let mut json = String::new();
+
+        let rows: Vec<String> = self
+            .camera_transform
+            .iter()
+            .map(|row| {
+                let elements: Vec<String> =
+                    row.iter().map(|element| json_number(*element)).collect();
+                format!("[{}]", elements.join(", "))
+            })
+            .collect();
+
+        writeln!(json, "{{")?;
+        writeln!(json, "  \"file_path\": {},", json_string(&config.file_path))?;
+        writeln!(json, "  \"width\": {},", config.width)?;
+        writeln!(json, "  \"height\": {},", config.height)?;
+        writeln!(
+            json,
+            "  \"global_illumination\": {},",
+            config.global_illumination
+        )?;
+        writeln!(
+            json,
+            "  \"max_intersections\": {},",
+            config.max_intersections
+        )?;
+        writeln!(
+            json,
+            "  \"aa_threshold\": {},",
+            json_number(config.aa_threshold)
+        )?;
+        writeln!(json, "  \"aa_max_depth\": {},", config.aa_max_depth)?;
+        writeln!(
+            json,
+            "  \"field_of_view\": {},",
+            json_number(self.field_of_view)
+        )?;
+        writeln!(json, "  \"camera_transform\": [{}],", rows.join(", "))?;
+        writeln!(
+            json,
+            "  \"samples_per_pixel\": {},",
+            json_number(self.samples_per_pixel)
+        )?;
+        writeln!(
+            json,
+            "  \"max_recursion_depth\": {},",
+            self.max_recursion_depth
+        )?;
+        writeln!(
+            json,
+            "  \"render_time_seconds\": {}",
+            json_number(self.render_time.as_secs_f64())
+        )?;
+        writeln!(json, "}}")?;
+
+        Ok(json)
+    }
+
+    pub fn write_sidecar(&self, config: &Config) -> Result<(), RaytracerError> {
+        let json = self.to_json(config)?;
+
+        std::fs::write(RenderStats::sidecar_path(&config.file_path), json)?;
+
+        Ok(())
+    }
+}
+
+// Quotes the string for JSON, escaping the quotes, backslashes and control characters it can't
+// contain as they are
+fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+
+    result.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+
+    result
+}
+
+// JSON has no way to write NaN or infinity, so they're written as null instead
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        String::from("null")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::render_stats::{json_number, json_string};
+
+    #[test]
+    fn given_a_path_with_special_characters_when_writing_it_as_json_should_escape_them() {
+        // Arrange
+        let path = "C:\\renders\\\"final\"\n\u{1}é.ppm";
+
+        // Act
+        let result = json_string(path);
+
+        // Assert
+        assert_eq!("\"C:\\\\renders\\\\\\\"final\\\"\\n\\u0001é.ppm\"", result);
+    }
+
+    #[test]
+    fn given_non_finite_numbers_when_writing_them_as_json_should_write_null() {
+        // Arrange
+        let values = [0.25, f64::NAN, f64::INFINITY, f64::NEG_INFINITY];
+
+        // Act
+        let result: Vec<String> = values.iter().map(|value| json_number(*value)).collect();
+
+        // Assert
+        assert_eq!(vec!["0.25", "null", "null", "null"], result);
+    }
+}
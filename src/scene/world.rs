@@ -1,25 +1,83 @@
-use crate::geometry::shape::Shape;
+use crate::geometry::csg::CSG;
+use crate::geometry::group::Group;
+use crate::geometry::shape::{bounds_generation, Shape};
 use crate::geometry::sphere::Sphere;
+use crate::materials::material::{BlendMode, Material};
 use crate::materials::phong::Phong;
 use crate::matrices::matrix::Matrix;
+use crate::patterns::pattern::Pattern;
 use crate::patterns::solid::Solid;
 use crate::scene::computations::Computations;
+use crate::scene::pick_result::PickResult;
+use crate::tuples::area_light::AreaLight;
+use crate::tuples::bounding_box::BoundingBox;
 use crate::tuples::color::Color;
 use crate::tuples::intersection::Intersection;
 use crate::tuples::point_light::PointLight;
 use crate::tuples::ray::Ray;
+use crate::tuples::sampling::{sample_cone, sample_cosine_hemisphere, SeededRng};
+use crate::tuples::spot_light::SpotLight;
 use crate::tuples::tuple::Tuple;
-use crate::EPSILON;
-use std::sync::Arc;
+use crate::{EPSILON, GLOSSY_REFLECTION_SAMPLES, MAX_INTERSECTIONS_PER_RAY};
+use std::collections::BinaryHeap;
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+/*
+    What color_at draws. Shaded is the normal lit render, whereas Segmentation draws each object
+    as a flat color picked from its id with the background black, e.g. to make masks for labelling
+    a training set. A shape inside a group or CSG shape takes the color of the top level object it
+    belongs to, so a mesh comes out as one color rather than one per triangle.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderMode {
+    Shaded,
+    Segmentation,
+}
 
 pub struct World {
     objects: Vec<Arc<dyn Shape>>,
     lights: Vec<Arc<PointLight>>,
+    area_lights: Vec<Arc<AreaLight>>,
+    spot_lights: Vec<Arc<SpotLight>>,
+    shadows_enabled: bool,
+    background: Option<Box<dyn Pattern>>,
+    bounds: RwLock<Option<(usize, BoundingBox)>>, // Lazily worked out, see cached_bounds
+    finite_bounds: RwLock<Option<(usize, BoundingBox)>>, // Same, but leaving out unbounded objects
+    max_intersections: usize,
+    reported_too_many_intersections: AtomicBool,
+    light_importance_threshold: f64,
+    exact_fresnel: bool,
+    clip_planes: Vec<(Tuple, Tuple)>, // A point on each plane, and the normal facing the clipped side
+    ambient_refractive_index: f64,    // Of whatever fills the space between the objects
+    render_mode: RenderMode,
+    fog: Option<(Color, f64)>, // The fog color, and its density
+    deduplicate_intersections: bool,
 }
 
 impl World {
     pub fn new(objects: Vec<Arc<dyn Shape>>, lights: Vec<Arc<PointLight>>) -> World {
-        return World { objects, lights };
+        return World {
+            objects,
+            lights,
+            area_lights: Vec::new(),
+            spot_lights: Vec::new(),
+            shadows_enabled: true,
+            background: None,
+            bounds: RwLock::new(None),
+            finite_bounds: RwLock::new(None),
+            max_intersections: MAX_INTERSECTIONS_PER_RAY,
+            reported_too_many_intersections: AtomicBool::new(false),
+            light_importance_threshold: 0.0,
+            exact_fresnel: false,
+            clip_planes: Vec::new(),
+            ambient_refractive_index: 1.0,
+            render_mode: RenderMode::Shaded,
+            fog: None,
+            deduplicate_intersections: false,
+        };
     }
 
     pub fn default() -> World {
@@ -49,28 +107,633 @@ impl World {
         let objects: Vec<Arc<dyn Shape>> = vec![Arc::new(outer), Arc::new(inner)];
         let lights = vec![Arc::new(light)];
 
-        return World { objects, lights };
+        return World::new(objects, lights);
+    }
+
+    // Adds a light giving off light from the surface of its shape, which is also added to the
+    // world as an object so that rays can hit it. Only the global illumination mode (see
+    // color_at_gi) makes use of the emission.
+    pub fn add_area_light(&mut self, light: Arc<AreaLight>) {
+        self.objects.push(light.shape());
+        self.invalidate_bounds();
+        self.area_lights.push(light);
+    }
+
+    pub fn lights(&self) -> &[Arc<PointLight>] {
+        &self.lights
+    }
+
+    pub fn set_lights(&mut self, lights: Vec<Arc<PointLight>>) {
+        self.lights = lights;
+    }
+
+    pub fn add_spot_light(&mut self, light: Arc<SpotLight>) {
+        self.spot_lights.push(light);
+    }
+
+    pub fn objects(&self) -> &[Arc<dyn Shape>] {
+        &self.objects
+    }
+
+    pub fn spot_lights(&self) -> &[Arc<SpotLight>] {
+        &self.spot_lights
+    }
+
+    // Finds the emission of the object if it's the shape of one of the area lights
+    fn emission_of(&self, object: &Arc<dyn Shape>) -> Option<Color> {
+        self.area_lights
+            .iter()
+            .find(|light| Arc::ptr_eq(&light.shape(), object))
+            .map(|light| light.emission())
+    }
+
+    // Turning shadows off skips casting any shadow rays at all, which is useful to get a quick
+    // preview of the lighting in complex scenes
+    pub fn set_shadows_enabled(&mut self, shadows_enabled: bool) {
+        self.shadows_enabled = shadows_enabled;
+    }
+
+    pub fn shadows_enabled(&self) -> bool {
+        self.shadows_enabled
+    }
+
+    /*
+        Lights that could only add a small share of the total light at a point (less than the given
+        fraction of it) skip their shadow ray and are treated as if shadowed, leaving just their
+        ambient term. Saves casting shadow rays towards dim fill lights where they'd barely change
+        the result. Zero, the default, always casts them.
+    */
+    pub fn set_light_importance_threshold(&mut self, threshold: f64) {
+        assert!((0.0..=1.0).contains(&threshold));
+
+        self.light_importance_threshold = threshold;
+    }
+
+    pub fn light_importance_threshold(&self) -> f64 {
+        self.light_importance_threshold
+    }
+
+    // Surfaces that are both reflective and transparent split the light between the two using
+    // Schlick's approximation, unless told to use the exact Fresnel equations (see fresnel)
+    pub fn set_exact_fresnel(&mut self, exact_fresnel: bool) {
+        self.exact_fresnel = exact_fresnel;
+    }
+
+    pub fn exact_fresnel(&self) -> bool {
+        self.exact_fresnel
+    }
+
+    // The refractive index of the medium the whole scene sits in, 1.0 for a vacuum (or near enough
+    // for air), but e.g. 1.333 for an underwater scene. Light bends as it goes from this into an
+    // object that isn't inside any other, and back out again.
+    pub fn set_ambient_refractive_index(&mut self, ambient_refractive_index: f64) {
+        assert!(ambient_refractive_index >= 1.0);
+
+        self.ambient_refractive_index = ambient_refractive_index;
+    }
+
+    pub fn ambient_refractive_index(&self) -> f64 {
+        self.ambient_refractive_index
+    }
+
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+
+    // Fades whatever rays hit towards the fog color the further away it is, for depth cueing. The
+    // fog's share of the color is 1 - e^(-density * distance), and rays that miss everything
+    // disappear into the fog completely.
+    pub fn set_fog(&mut self, fog: Option<(Color, f64)>) {
+        assert!(fog.is_none_or(|(_, density)| density >= 0.0));
+
+        self.fog = fog;
+    }
+
+    pub fn fog(&self) -> Option<(Color, f64)> {
+        self.fog
+    }
+
+    fn apply_fog(&self, color: Color, distance: f64) -> Color {
+        match self.fog {
+            Some((fog_color, density)) => {
+                let fogginess = 1.0 - (-density * distance).exp();
+
+                (color * (1.0 - fogginess)) + (fog_color * fogginess)
+            }
+            None => color,
+        }
+    }
+
+    // The flat color the object is drawn in when segmenting, the same every time for the same id
+    pub fn segmentation_color(id: Uuid) -> Color {
+        let bits = id.as_u128();
+        let mut rng = SeededRng::new((bits ^ (bits >> 64)) as u64);
+
+        // Kept away from black, so that no object can be mistaken for the background
+        let mut channel = || 0.1 + 0.9 * rng.next_f64();
+
+        Color::new(channel(), channel(), channel())
+    }
+
+    // Cuts away everything on the side of the plane that the normal faces, e.g. to see inside a
+    // building. Rays carry on through anything clipped as if it weren't there, so the clipped
+    // parts don't cast shadows either.
+    pub fn add_clip_plane(&mut self, point: Tuple, normal: Tuple) {
+        assert!(point.is_point());
+        assert!(normal.is_vector());
+
+        self.clip_planes.push((point, normal.normalize()));
+    }
+
+    pub fn clip_planes(&self) -> &[(Tuple, Tuple)] {
+        &self.clip_planes
+    }
+
+    fn is_clipped(&self, point: Tuple) -> bool {
+        self.clip_planes
+            .iter()
+            .any(|(plane_point, normal)| Tuple::dot(point - *plane_point, *normal) > 0.0)
+    }
+
+    /*
+        A shape can be shared between several groups, e.g. to reuse the same geometry, in which
+        case wherever the groups overlap each of them reports the same hits on it. That throws off
+        anything counting the hits, like working out which objects a refracted ray is inside, so
+        this drops any hit on the same object at (near enough) the same time as one already found.
+        Off by default, since it costs a little on every ray.
+    */
+    pub fn set_deduplicate_intersections(&mut self, deduplicate_intersections: bool) {
+        self.deduplicate_intersections = deduplicate_intersections;
+    }
+
+    pub fn deduplicate_intersections(&self) -> bool {
+        self.deduplicate_intersections
+    }
+
+    // A safety valve against broken geometry (e.g. a bad CSG tree or mesh) producing enormous
+    // numbers of hits per ray. Any past the cap are dropped, keeping the nearest ones.
+    pub fn set_max_intersections(&mut self, max_intersections: usize) {
+        self.max_intersections = max_intersections;
+    }
+
+    pub fn max_intersections(&self) -> usize {
+        self.max_intersections
+    }
+
+    // Rays that don't hit anything pick up their color from the background, which is sampled
+    // using the direction of the ray as a point, i.e. as if it were painted on a unit sphere
+    // surrounding the scene. With no background set missed rays are black.
+    pub fn set_background(&mut self, background: Option<Box<dyn Pattern>>) {
+        self.background = background;
+    }
+
+    pub fn background(&self) -> Option<&dyn Pattern> {
+        self.background.as_deref()
+    }
+
+    fn background_color(&self, ray: &Ray) -> Color {
+        match &self.background {
+            None => Color::black(),
+            Some(pattern) => {
+                let direction = ray.direction().normalize();
+
+                pattern.local_pattern_at(Tuple::point(direction.x, direction.y, direction.z))
+            }
+        }
+    }
+
+    // Checks the scene for common mistakes that would otherwise fail silently when rendering, e.g.
+    // a missing light or a transform that can't be inverted, returning a description of each
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        if self.lights.is_empty() && self.spot_lights.is_empty() {
+            problems.push(String::from(
+                "world has no lights, so everything will render black",
+            ));
+        }
+
+        for object in self.objects.iter() {
+            World::validate_shape(object, &mut problems);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    fn validate_shape(shape: &Arc<dyn Shape>, problems: &mut Vec<String>) {
+        let transform = shape.get_transform();
+
+        if !transform.is_finite() {
+            problems.push(format!(
+                "shape {} has a transform containing NaN or infinite values",
+                shape.id()
+            ));
+        } else if !transform.is_invertible() {
+            problems.push(format!(
+                "shape {} has a singular transform that can't be inverted",
+                shape.id()
+            ));
+        }
+
+        let refractive_index = shape.get_material().refractive_index();
+
+        if refractive_index < 1.0 || refractive_index.is_nan() {
+            problems.push(format!(
+                "shape {} has a refractive index of {}, but it should be at least 1.0",
+                shape.id(),
+                refractive_index
+            ));
+        }
+
+        // Check inside any shapes made up of others
+        if let Some(group) = shape.as_any().downcast_ref::<Group>() {
+            for i in 0..group.num_of_children() {
+                World::validate_shape(&group.get_child(i).unwrap(), problems);
+            }
+        } else if let Some(csg) = shape.as_any().downcast_ref::<CSG>() {
+            World::validate_shape(&csg.left(), problems);
+            World::validate_shape(&csg.right(), problems);
+        }
+    }
+
+    /*
+        Builds a debug mesh showing how the scene is partitioned, with a wireframe box (see
+        Group::wire_box) around every group and CSG shape in the world. The boxes are in world
+        space, so the mesh can be added straight into the world to render it overlaid on the scene.
+        Nodes with unbounded contents (e.g. a group holding a plane) are skipped.
+    */
+    pub fn bounding_hierarchy_mesh(
+        &self,
+        thickness: f64,
+        material: Arc<dyn Material>,
+    ) -> Arc<Group> {
+        let result = Arc::new(Group::default());
+
+        for object in &self.objects {
+            World::add_bounding_boxes(object, &Matrix::identity(4), thickness, &material, &result);
+        }
+
+        result
+    }
+
+    fn add_bounding_boxes(
+        shape: &Arc<dyn Shape>,
+        parent_transform: &Matrix,
+        thickness: f64,
+        material: &Arc<dyn Material>,
+        mesh: &Arc<Group>,
+    ) {
+        let transform = (parent_transform * shape.get_transform().as_ref()).unwrap();
+
+        let children = if let Some(group) = shape.as_any().downcast_ref::<Group>() {
+            (0..group.num_of_children())
+                .map(|i| group.get_child(i).unwrap())
+                .collect()
+        } else if let Some(csg) = shape.as_any().downcast_ref::<CSG>() {
+            vec![csg.left(), csg.right()]
+        } else {
+            // Primitives are leaves, so they don't get a box of their own
+            return;
+        };
+
+        let bounds = shape.bounds().transform(&transform);
+
+        if bounds.min().is_finite() && bounds.max().is_finite() {
+            mesh.add_child(Group::wire_box(bounds, thickness, material.clone()));
+        }
+
+        for child in children {
+            World::add_bounding_boxes(&child, &transform, thickness, material, mesh);
+        }
+    }
+
+    // Appends the objects and lights of another world into this one, so that a scene can be
+    // assembled from separate parts. Lights that are already shared with this world are skipped
+    // so they don't end up contributing twice.
+    pub fn merge(&mut self, other: World) {
+        self.objects.extend(other.objects);
+        self.invalidate_bounds();
+
+        for light in other.lights {
+            if !self.lights.iter().any(|l| Arc::ptr_eq(l, &light)) {
+                self.lights.push(light);
+            }
+        }
+
+        for light in other.area_lights {
+            if !self.area_lights.iter().any(|l| Arc::ptr_eq(l, &light)) {
+                self.area_lights.push(light);
+            }
+        }
+
+        for light in other.spot_lights {
+            if !self.spot_lights.iter().any(|l| Arc::ptr_eq(l, &light)) {
+                self.spot_lights.push(light);
+            }
+        }
+    }
+
+    pub fn add_object(&mut self, object: Arc<dyn Shape>) {
+        self.objects.push(object);
+        self.invalidate_bounds();
+    }
+
+    // The box enclosing every object in the world. Any unbounded objects (e.g. planes) make it
+    // stretch off to infinity, so that rays towards them are never skipped, see finite_bounds
+    // for a box that can be used to frame the scene instead.
+    pub fn bounds(&self) -> BoundingBox {
+        World::cached_bounds(&self.bounds, || {
+            self.objects
+                .iter()
+                .fold(BoundingBox::empty(), |bounds, object| {
+                    bounds + object.parent_space_bounds_of()
+                })
+        })
+    }
+
+    // The box enclosing every object in the world that has finite bounds, leaving out things like
+    // ground planes that go on forever
+    pub fn finite_bounds(&self) -> BoundingBox {
+        World::cached_bounds(&self.finite_bounds, || {
+            self.objects
+                .iter()
+                .map(|object| object.parent_space_bounds_of())
+                .filter(|bounds| bounds.min().is_finite() && bounds.max().is_finite())
+                .fold(BoundingBox::empty(), |total, bounds| total + bounds)
+        })
+    }
+
+    /*
+        Gives back the cached bounds, unless they were worked out before the shapes last changed
+        (e.g. a group in the world being moved, see bounds_generation), in which case they're
+        worked out again. The generation is read before working them out, so a change made in the
+        meantime just means they get worked out again next time.
+    */
+    fn cached_bounds(
+        cache: &RwLock<Option<(usize, BoundingBox)>>,
+        compute: impl FnOnce() -> BoundingBox,
+    ) -> BoundingBox {
+        let generation = bounds_generation();
+
+        if let Some((cached_generation, bounds)) = *cache.read().unwrap() {
+            if cached_generation == generation {
+                return bounds;
+            }
+        }
+
+        let bounds = compute();
+        *cache.write().unwrap() = Some((generation, bounds));
+
+        bounds
+    }
+
+    // Has the bounds worked out again the next time they're asked for, whenever objects are added
+    // or removed
+    fn invalidate_bounds(&mut self) {
+        self.bounds = RwLock::new(None);
+        self.finite_bounds = RwLock::new(None);
+    }
+
+    pub fn object_ids(&self) -> Vec<Uuid> {
+        self.objects.iter().map(|object| object.id()).collect()
+    }
+
+    // Removes any objects that were added more than once, e.g. when a scene has been merged
+    // together from several sources, since each copy would report its own intersections
+    pub fn deduplicate_objects(&mut self) {
+        let mut seen: Vec<Uuid> = Vec::new();
+        self.invalidate_bounds();
+
+        self.objects.retain(|object| {
+            if seen.contains(&object.id()) {
+                false
+            } else {
+                seen.push(object.id());
+                true
+            }
+        });
+    }
+
+    // Same as intersect_world, but skips testing any of the objects when the ray heads off away
+    // from the whole scene. Only suitable for shading, since it also drops the hits behind the
+    // ray that intersect_world would otherwise report.
+    fn intersect_world_ahead(&self, ray: &Ray) -> Vec<Intersection> {
+        if self.bounds().intersects_ahead(ray) {
+            self.intersect_world(ray)
+        } else {
+            vec![]
+        }
     }
 
     pub fn intersect_world(&self, ray: &Ray) -> Vec<Intersection> {
-        let mut result: Vec<Intersection> = vec![];
+        // Only the nearest hits up to the limit are kept as they're found, so a shape with far too
+        // many of them can't use up all the memory. The farthest one kept is on top of the heap,
+        // ready to make way for anything nearer.
+        let mut nearest: BinaryHeap<Intersection> = BinaryHeap::new();
+        let mut found = 0;
+
+        self.for_each_intersection(ray, |intersection| {
+            if !self.clip_planes.is_empty() && self.is_clipped(ray.position(intersection.time())) {
+                return true;
+            }
+
+            found += 1;
+
+            if nearest.len() < self.max_intersections {
+                nearest.push(intersection.clone());
+            } else if nearest
+                .peek()
+                .is_some_and(|farthest| intersection < farthest)
+            {
+                nearest.pop();
+                nearest.push(intersection.clone());
+            }
 
-        for i in 0..self.objects.len() {
-            let obj = self.objects[i].clone();
+            true
+        });
 
-            let mut intersects = obj.intersect(ray);
-            result.append(&mut intersects);
+        // Use the same ordering as groups and CSG shapes so that hits come out consistently
+        let mut result = nearest.into_sorted_vec();
+
+        if self.deduplicate_intersections {
+            result = World::without_duplicate_hits(result);
         }
 
-        result.sort_by(|a, b| a.time().partial_cmp(&b.time()).unwrap());
+        // Only say so the first time, otherwise every ray through the offending shape would
+        if found > self.max_intersections
+            && !self
+                .reported_too_many_intersections
+                .swap(true, Ordering::Relaxed)
+        {
+            eprintln!(
+                "Warning: a ray produced {} intersections, only keeping the nearest {}",
+                found, self.max_intersections
+            );
+        }
 
-        return result;
+        result
+    }
+
+    // Assumes the intersections are sorted, so any duplicates are among the hits just before
+    fn without_duplicate_hits(intersections: Vec<Intersection>) -> Vec<Intersection> {
+        let mut result: Vec<Intersection> = Vec::with_capacity(intersections.len());
+
+        for intersection in intersections {
+            let duplicate = result
+                .iter()
+                .rev()
+                .take_while(|kept| intersection.time() - kept.time() < EPSILON)
+                .any(|kept| kept.object().id() == intersection.object().id());
+
+            if !duplicate {
+                result.push(intersection);
+            }
+        }
+
+        result
+    }
+
+    // Finds the first thing the ray hits and where, without shading it
+    pub fn pick(&self, ray: &Ray) -> Option<PickResult> {
+        let intersects = self.intersect_world(ray);
+        let i = World::visible_hit(&intersects)?;
+
+        let comps = self.computations_for(i, ray, &intersects);
+
+        Some(PickResult {
+            object_id: comps.object.id(),
+            point: comps.point,
+            normal: comps.normalv,
+            distance: (comps.point - ray.origin()).magnitude(),
+        })
+    }
+
+    // Picks with each of the rays in turn, e.g. for a marquee selection
+    pub fn pick_all(&self, rays: &[Ray]) -> Vec<Option<PickResult>> {
+        rays.iter().map(|ray| self.pick(ray)).collect()
+    }
+
+    /*
+        Visits every intersection of the ray with the world, in no particular order, without
+        gathering them up and sorting them first. The visitor returns whether to keep going, so
+        queries that only need to find one hit (e.g. for shadows) can stop as soon as they do.
+    */
+    pub fn for_each_intersection<F: FnMut(&Intersection) -> bool>(&self, ray: &Ray, mut visit: F) {
+        for object in &self.objects {
+            if !object.clone().for_each_intersection(ray, &mut visit) {
+                return;
+            }
+        }
+    }
+
+    /*
+        Checks whether the ray hits anything that casts a shadow between its origin and the given
+        time, skipping over anything clipped away or cut out the same as nearest_hit does. Objects
+        the ray can't reach before then going by their bounding spheres aren't tested at all.
+    */
+    pub fn any_hit_before(&self, ray: &Ray, max_time: f64) -> bool {
+        let mut visit = |intersection: &Intersection| {
+            let hit = intersection.time() > 0.0
+                && intersection.time() < max_time
+                && intersection.object().casts_shadow()
+                && !self.is_clipped(ray.position(intersection.time()))
+                && !World::is_cut_out(intersection);
+            !hit
+        };
+
+        !self
+            .objects
+            .iter()
+            .filter(|object| World::is_reachable_before(object.as_ref(), ray, max_time))
+            .all(|object| object.clone().for_each_intersection(ray, &mut visit))
+    }
+
+    // Whether the part of the ray up to the given time passes through the object's bounding
+    // sphere, which always holds for unbounded objects
+    fn is_reachable_before(object: &dyn Shape, ray: &Ray, max_time: f64) -> bool {
+        let (center, radius) = object.bounding_sphere();
+
+        if !radius.is_finite() {
+            return true;
+        }
+
+        // The nearest point to the center along the part of the ray up to the time
+        let direction = ray.direction();
+        let closest = (Tuple::dot(center - ray.origin(), direction)
+            / Tuple::dot(direction, direction))
+        .clamp(0.0, max_time);
+
+        (ray.position(closest) - center).magnitude() <= radius
+    }
+
+    fn is_nearer_hit(
+        &self,
+        ray: &Ray,
+        intersection: &Intersection,
+        nearest: &Option<Intersection>,
+    ) -> bool {
+        intersection.time() > 0.0
+            && nearest.as_ref().is_none_or(|best| intersection < best)
+            && !self.is_clipped(ray.position(intersection.time()))
+            && !World::is_cut_out(intersection)
+    }
+
+    // Finds the hit, i.e. the same intersection Intersection::hit would pick from intersect_world
+    pub fn nearest_hit(&self, ray: &Ray) -> Option<Intersection> {
+        let mut result: Option<Intersection> = None;
+
+        self.for_each_intersection(ray, |intersection| {
+            if self.is_nearer_hit(ray, intersection, &result) {
+                result = Some(intersection.clone());
+            }
+            true
+        });
+
+        result
+    }
+
+    // Whether the intersection lands on a hole cut out of the surface's texture, in which case the
+    // ray should carry on as though it missed
+    fn is_cut_out(intersection: &Intersection) -> bool {
+        let object = intersection.object();
+
+        object
+            .texture_uv_at(intersection)
+            .is_some_and(|(u, v)| object.get_material().is_cut_out_at_uv(u, v))
+    }
+
+    // Same as Intersection::hit, but skipping over any hits on cut out parts of a surface
+    fn visible_hit(intersects: &[Intersection]) -> Option<usize> {
+        intersects
+            .iter()
+            .position(|intersection| intersection.time() > 0.0 && !World::is_cut_out(intersection))
     }
 
+    // Assumes the objects sit in a vacuum, which is all the tests need
+    #[cfg(test)]
     fn prepare_computations(
         hit_index: usize,
         ray: &Ray,
         intersections: &Vec<Intersection>,
+    ) -> Computations {
+        World::prepare_computations_in_medium(hit_index, ray, intersections, 1.0)
+    }
+
+    // Same as prepare_computations, but with the space outside of every object filled with a
+    // medium of the given refractive index rather than a vacuum
+    fn prepare_computations_in_medium(
+        hit_index: usize,
+        ray: &Ray,
+        intersections: &Vec<Intersection>,
+        ambient_refractive_index: f64,
     ) -> Computations {
         let intersection = &intersections[hit_index];
 
@@ -91,7 +754,10 @@ impl World {
         let reflectv = Tuple::reflect(ray.direction(), normalv);
 
         // EPSILON is used to bump the intersection point slightly in the direction of the surface
-        // normal to help prevent self shadowing
+        // normal to help prevent self shadowing. Since the normal has already been flipped to face
+        // the eye when the ray starts inside the shape, over_point always lies on the same side as
+        // the incoming ray and under_point on the far side, so refracted rays leaving a shape (e.g.
+        // with the camera inside glass) start just outside of it rather than on the surface.
         let over_point = point + (normalv * EPSILON);
         let under_point = point - (normalv * EPSILON);
 
@@ -105,7 +771,7 @@ impl World {
             if i == hit_index {
                 if containers.is_empty() {
                     // Otherwise there's no object, so just set it to vacuum
-                    n1 = 1.0;
+                    n1 = ambient_refractive_index;
                 } else {
                     // Set n1 to the refractive index of the last object in the containers list
                     n1 = containers.last().unwrap().get_material().refractive_index();
@@ -122,7 +788,7 @@ impl World {
 
             if i == hit_index {
                 if containers.is_empty() {
-                    n2 = 1.0;
+                    n2 = ambient_refractive_index;
                 } else {
                     n2 = containers.last().unwrap().get_material().refractive_index();
                 }
@@ -131,9 +797,9 @@ impl World {
             }
         }
 
-        return Computations::new(
+        let mut result = Computations::new(
             time,
-            object,
+            object.clone(),
             point,
             over_point,
             under_point,
@@ -144,6 +810,9 @@ impl World {
             reflectv,
             inside,
         );
+        result.uv = object.texture_uv_at(intersection);
+
+        return result;
     }
 
     fn contains_object(
@@ -160,90 +829,422 @@ impl World {
     }
 
     pub fn shade_hit(&self, comps: &Computations, remaining: usize) -> Color {
-        let mut result = Color::new(0.0, 0.0, 0.0);
+        let potentials: Vec<f64> = self
+            .lights
+            .iter()
+            .map(|light| World::light_potential(light, comps))
+            .collect();
+        let cutoff = self.light_importance_threshold * potentials.iter().sum::<f64>();
+
+        let mut lights = Vec::with_capacity(self.lights.len() + self.spot_lights.len());
+        let mut in_shadow = Vec::with_capacity(self.lights.len() + self.spot_lights.len());
+
+        for (light, potential) in self.lights.iter().zip(potentials) {
+            let light = **light;
+
+            lights.push(light);
+            in_shadow.push(
+                if self.light_importance_threshold > 0.0 && potential < cutoff {
+                    true
+                } else {
+                    self.is_shadowed(comps.over_point, light)
+                },
+            );
+        }
 
-        for i in 0..self.lights.len() {
-            let light = *self.lights[i];
-            let in_shadow = self.is_shadowed(comps.over_point, light);
+        // Spotlights only light points within their cones
+        for spot_light in self.spot_lights.iter() {
+            if let Some(light) = spot_light.light_at(comps.over_point) {
+                lights.push(light);
+                in_shadow.push(self.is_shadowed(comps.over_point, light));
+            }
+        }
 
-            let shape = comps.object.clone();
-            let material = shape.get_material();
+        let material = comps.object.get_material();
 
-            let surface = shape.light_material(
-                comps.over_point,
-                light,
-                comps.eyev,
-                comps.normalv,
-                in_shadow,
-            );
+        let surface = material.lighting_multi_at_hit(&lights, comps, &in_shadow);
 
-            let reflected = self.reflected_color(comps, remaining);
-            let refracted = self.refracted_color(comps, remaining);
+        // The reflected and refracted light doesn't depend on the lights at the hit, so is only
+        // added the once
+        let reflected = self.reflected_color(comps, remaining);
+        let refracted = self.refracted_color(comps, remaining);
 
-            if material.reflective() > 0.0 && material.transparency() > 0.0 {
-                let reflectance = Self::schlick(comps);
+        match material.blend_mode() {
+            BlendMode::Fixed(reflectance) => {
+                surface + (reflected * reflectance) + (refracted * (1.0 - reflectance))
+            }
+            BlendMode::Fresnel if material.reflective() > 0.0 && material.transparency() > 0.0 => {
+                let reflectance = if self.exact_fresnel {
+                    Self::fresnel(comps)
+                } else {
+                    Self::schlick(comps)
+                };
 
-                result = result
-                    + surface
-                    + (reflected * reflectance)
-                    + (refracted * (1.0 - reflectance));
-            } else {
-                result = result + surface + reflected + refracted;
+                surface + (reflected * reflectance) + (refracted * (1.0 - reflectance))
             }
+            BlendMode::Fresnel => surface + reflected + refracted,
         }
+    }
 
-        return result;
+    fn computations_for(
+        &self,
+        hit_index: usize,
+        ray: &Ray,
+        intersections: &Vec<Intersection>,
+    ) -> Computations {
+        World::prepare_computations_in_medium(
+            hit_index,
+            ray,
+            intersections,
+            self.ambient_refractive_index,
+        )
+    }
+
+    // The most diffuse light the light could add at the hit if it weren't shadowed, i.e. its
+    // brightness scaled by how directly it faces the surface
+    fn light_potential(light: &PointLight, comps: &Computations) -> f64 {
+        let lightv = (light.position - comps.over_point).normalize();
+
+        light.intensity.luminance() * Tuple::dot(lightv, comps.normalv).max(0.0)
     }
 
     pub fn color_at(&self, ray: &Ray, remaining: usize) -> Color {
         // Call intersect to find the intersections of the given ray in this world
-        let intersects = self.intersect_world(ray);
+        let intersects = self.intersect_world_ahead(ray);
 
-        // Find the hit from the resulting intersects
-        let hit = Intersection::hit(&intersects);
+        self.color_of_intersections(ray, &intersects, remaining, None)
+    }
 
-        if let Some((i, _)) = hit {
-            let comps = World::prepare_computations(i, ray, &intersects);
+    // Same as color_at, but also given the rays through the neighbouring pixels (see
+    // Camera::ray_differentials_for_pixel) so that patterns at the primary hit can be filtered.
+    // Any reflected, refracted or shadow rays are traced without differentials.
+    pub fn color_at_with_differentials(
+        &self,
+        ray: &Ray,
+        rx: &Ray,
+        ry: &Ray,
+        remaining: usize,
+    ) -> Color {
+        let intersects = self.intersect_world_ahead(ray);
 
-            return self.shade_hit(&comps, remaining);
-        }
+        self.color_of_intersections(ray, &intersects, remaining, Some((rx, ry)))
+    }
 
-        Color::black()
+    // Colors the ray as if the object with the given id were the only thing it could hit, which
+    // helps isolate a single object's contribution when debugging a render. Only the primary hit is
+    // filtered, any reflected, refracted or shadow rays still see the whole scene so the object is
+    // shown in its proper lighting context.
+    pub fn color_at_filtered(&self, ray: &Ray, remaining: usize, only: Uuid) -> Color {
+        let intersects: Vec<Intersection> = self
+            .intersect_world(ray)
+            .into_iter()
+            .filter(|intersection| intersection.object().id() == only)
+            .collect();
+
+        self.color_of_intersections(ray, &intersects, remaining, None)
     }
 
-    pub fn reflected_color(&self, comps: &Computations, remaining: usize) -> Color {
-        // Base case for the recursion caused by parallel mirrors
-        if remaining == 0 {
-            return Color::black();
-        }
+    /*
+        Path traced version of color_at for global illumination. On top of the direct lighting from
+        shade_hit, a single bounce ray is fired off in a random direction from the hit and whatever
+        light it finds is tinted by the surface's diffuse color, so that light reflected between
+        surfaces (e.g. color bleeding from a red wall onto a white floor) shows up. One path is
+        noisy, so it's meant to be averaged over many samples per pixel.
+
+        Area lights are only found when a bounce happens to hit one, so small ones converge very
+        slowly, see color_at_gi_nee for a faster alternative.
+
+        Note: the bounce rays are cosine weighted, which exactly cancels out the cosine and the 1/pi
+        terms of the diffuse reflection, leaving just the albedo. Opacity is ignored.
+    */
+    pub fn color_at_gi(&self, ray: &Ray, remaining: usize, rng: &mut SeededRng) -> Color {
+        self.trace_path(ray, remaining, rng, false, true)
+    }
 
-        let reflective = comps.object.get_material().reflective();
+    // Same as color_at_gi, but with next event estimation, i.e. at every diffuse hit a point on
+    // each area light is picked and its light added in directly. Bounce rays that go on to hit a
+    // light then don't add its emission again, since it's already been counted.
+    pub fn color_at_gi_nee(&self, ray: &Ray, remaining: usize, rng: &mut SeededRng) -> Color {
+        self.trace_path(ray, remaining, rng, true, true)
+    }
 
-        if (reflective - 0.0).abs() < EPSILON {
-            return Color::black();
-        }
+    fn trace_path(
+        &self,
+        ray: &Ray,
+        remaining: usize,
+        rng: &mut SeededRng,
+        next_event: bool,
+        count_emission: bool,
+    ) -> Color {
+        let intersects = self.intersect_world_ahead(ray);
+
+        if let Some(i) = World::visible_hit(&intersects) {
+            let comps = self.computations_for(i, ray, &intersects);
+
+            if let Some(emission) = self.emission_of(&comps.object) {
+                return if count_emission {
+                    emission
+                } else {
+                    Color::black()
+                };
+            }
 
-        // Reflected ray starts at where the incident ray hit, and is pointed in the direction of reflectv
-        let reflect_ray = Ray::new(comps.over_point, comps.reflectv);
-        let color = self.color_at(&reflect_ray, remaining - 1);
+            let mut direct = self.shade_hit(&comps, remaining);
 
-        color * reflective
-    }
+            if next_event {
+                direct = direct + self.sample_area_lights(&comps, rng);
+            }
 
-    pub fn refracted_color(&self, comps: &Computations, remaining: usize) -> Color {
-        // Similar base case for recursion to reflection, stops infinities
-        if remaining == 0 {
-            return Color::black();
-        }
+            if remaining == 0 {
+                return direct;
+            }
 
-        let transparency = comps.object.get_material().transparency();
+            let albedo = comps
+                .object
+                .get_material()
+                .diffuse_albedo(comps.object.clone(), comps.over_point);
 
-        // Material is opaque so return black
-        if (transparency - 0.0).abs() < EPSILON {
-            return Color::black();
-        }
+            if albedo == Color::black() {
+                return direct;
+            }
 
-        // Find the ration of first index of refraction to the second.
+            let bounce = Ray::new(
+                comps.over_point,
+                sample_cosine_hemisphere(comps.normalv, rng),
+            );
+            let indirect = self.trace_path(&bounce, remaining - 1, rng, next_event, !next_event);
+
+            return direct + albedo * indirect;
+        }
+
+        self.background_color(ray)
+    }
+
+    /*
+        Estimates the diffuse light arriving at the hit from the area lights, using one point picked
+        on each. Since the points are picked uniformly by area, each sample is weighted by the
+        light's area, and the cosines at both ends over the distance squared convert that into the
+        solid angle the light covers as seen from the hit.
+    */
+    fn sample_area_lights(&self, comps: &Computations, rng: &mut SeededRng) -> Color {
+        let albedo = comps
+            .object
+            .get_material()
+            .diffuse_albedo(comps.object.clone(), comps.over_point);
+
+        let mut result = Color::black();
+
+        for light in &self.area_lights {
+            // A shape that can't be sampled still glows when hit, it just can't be aimed for
+            let Some((light_point, light_normal)) = light.sample(rng) else {
+                continue;
+            };
+
+            let to_light = light_point - comps.over_point;
+            let distance = to_light.magnitude();
+            let direction = to_light.normalize();
+
+            let cos_surface = Tuple::dot(comps.normalv, direction);
+            let cos_light = Tuple::dot(light_normal, -direction);
+
+            // Skip points facing away, either from the surface or on the far side of the light
+            if cos_surface <= 0.0 || cos_light <= 0.0 {
+                continue;
+            }
+
+            let visible = match self.nearest_hit(&Ray::new(comps.over_point, direction)) {
+                Some(hit) => {
+                    Arc::ptr_eq(&hit.object(), &light.shape()) || hit.time() > distance - EPSILON
+                }
+                None => true,
+            };
+
+            if visible {
+                let weight = cos_surface * cos_light * light.area() / (PI * distance * distance);
+
+                result = result + light.emission() * albedo * weight;
+            }
+        }
+
+        result
+    }
+
+    fn color_of_intersections(
+        &self,
+        ray: &Ray,
+        intersects: &Vec<Intersection>,
+        remaining: usize,
+        differentials: Option<(&Ray, &Ray)>,
+    ) -> Color {
+        // Find the hit from the resulting intersects
+        let hit = World::visible_hit(intersects);
+
+        if self.render_mode == RenderMode::Segmentation {
+            return match hit {
+                Some(i) => {
+                    let object = intersects[i].object();
+
+                    let id = self
+                        .objects
+                        .iter()
+                        .find(|top| Arc::clone(top).includes(&object))
+                        .map_or(object.id(), |top| top.id());
+
+                    World::segmentation_color(id)
+                }
+                None => Color::black(),
+            };
+        }
+
+        if let Some(i) = hit {
+            let mut comps = self.computations_for(i, ray, intersects);
+
+            if let Some(strength) = comps.object.get_material().shadow_catcher_strength() {
+                return self.caught_shadow(&comps, ray, remaining, strength);
+            }
+
+            if let Some((rx, ry)) = differentials {
+                comps.set_differentials(rx, ry);
+            }
+
+            let mut surface = self.shade_hit(&comps, remaining);
+            let opacity = comps.object.get_material().opacity();
+
+            // If the surface is partially see through then blend in whatever is behind it, by
+            // continuing the ray on from just past the surface without changing its direction
+            if opacity < 1.0 && remaining > 0 {
+                let continued_ray = Ray::new(comps.under_point, ray.direction());
+                let behind = self.color_at(&continued_ray, remaining - 1);
+
+                surface = (surface * opacity) + (behind * (1.0 - opacity));
+            }
+
+            let distance = intersects[i].time() * ray.direction().magnitude();
+
+            return self.apply_fog(surface, distance);
+        }
+
+        match self.fog {
+            Some((fog_color, _)) => fog_color,
+            None => self.background_color(ray),
+        }
+    }
+
+    /*
+        Colors a hit on a shadow catcher, by carrying on to whatever is behind it and darkening
+        that by how much of the light is blocked from the hit. Each light counts for as much light
+        as it could add to the point if it weren't blocked (see light_potential), so a dim light's
+        shadow is fainter than a bright one's.
+    */
+    fn caught_shadow(
+        &self,
+        comps: &Computations,
+        ray: &Ray,
+        remaining: usize,
+        strength: f64,
+    ) -> Color {
+        let behind = if remaining > 0 {
+            let continued_ray = Ray::new(comps.under_point, ray.direction());
+
+            self.color_at(&continued_ray, remaining - 1)
+        } else {
+            self.background_color(ray)
+        };
+
+        let mut total = 0.0;
+        let mut blocked = 0.0;
+
+        for light in self.lights.iter() {
+            let potential = World::light_potential(light, comps);
+
+            total += potential;
+
+            if self.is_shadowed(comps.over_point, **light) {
+                blocked += potential;
+            }
+        }
+
+        if total <= 0.0 {
+            return behind;
+        }
+
+        behind * (1.0 - strength * blocked / total)
+    }
+
+    pub fn reflected_color(&self, comps: &Computations, remaining: usize) -> Color {
+        // Base case for the recursion caused by parallel mirrors
+        if remaining == 0 {
+            return Color::black();
+        }
+
+        let reflective = comps.object.get_material().reflective();
+
+        if (reflective - 0.0).abs() < EPSILON {
+            return Color::black();
+        }
+
+        let roughness = comps.object.get_material().reflection_roughness();
+
+        // Reflected ray starts at where the incident ray hit, and is pointed in the direction of reflectv
+        if roughness <= 0.0 {
+            let reflect_ray = Ray::new(comps.over_point, comps.reflectv);
+            let color = self.color_at(&reflect_ray, remaining - 1);
+
+            return color * reflective;
+        }
+
+        // For rough surfaces, blur the reflection by averaging several rays jittered within a cone
+        // around reflectv. Seeding from the hit point keeps the result the same between renders.
+        let half_angle = roughness.min(1.0) * (PI / 2.0);
+        let mut rng = SeededRng::from_tuple(comps.over_point);
+        let mut color = Color::black();
+
+        for _ in 0..GLOSSY_REFLECTION_SAMPLES {
+            let mut direction = sample_cone(comps.reflectv, half_angle, &mut rng);
+
+            // Don't let the jitter send the ray back into the surface
+            if Tuple::dot(direction, comps.normalv) <= 0.0 {
+                direction = comps.reflectv;
+            }
+
+            let reflect_ray = Ray::new(comps.over_point, direction);
+            color = color + self.color_at(&reflect_ray, remaining - 1);
+        }
+
+        (color / GLOSSY_REFLECTION_SAMPLES as f64) * reflective
+    }
+
+    pub fn refracted_color(&self, comps: &Computations, remaining: usize) -> Color {
+        // Similar base case for recursion to reflection, stops infinities
+        if remaining == 0 {
+            return Color::black();
+        }
+
+        let transparency = comps.object.get_material().transparency();
+
+        // Material is opaque so return black
+        if (transparency - 0.0).abs() < EPSILON {
+            return Color::black();
+        }
+
+        // If there's total internal reflection then light isn't propagated across the interface
+        // between the two media, so we don't color it.
+        let Some(direction) = World::refracted_direction(comps) else {
+            return Color::black();
+        };
+
+        // Create the refracted ray
+        let refract_ray = Ray::new(comps.under_point, direction);
+
+        // Find the color of the refracted ray, making sure to multiply by the transparency value
+        // to account for any opacity
+        self.color_at(&refract_ray, remaining - 1) * transparency
+    }
+
+    // The direction light carries on in after crossing the surface, or None if there's total
+    // internal reflection
+    fn refracted_direction(comps: &Computations) -> Option<Tuple> {
+        // Find the ration of first index of refraction to the second.
         // i.e. the inversion of Snell's Law (describes relationship between angle of incoming ray
         // and the angle of the refracted ray).
         let n_ratio = comps.n1 / comps.n2;
@@ -254,10 +1255,9 @@ impl World {
         // Find sin(theta_t)^2 via trigonometric identity
         let sin2_t = (n_ratio * n_ratio) * (1.0 - (cos_i * cos_i));
 
-        // If sin2_t is greater than 1, then there's total internal reflection. This means that
-        // light isn't propagated across the interface between the two media, so we don't color it.
+        // If sin2_t is greater than 1, then there's total internal reflection
         if sin2_t > 1.0 {
-            return Color::black();
+            return None;
         }
 
         // Find cos(theta_t) via trigonometric identity
@@ -266,12 +1266,7 @@ impl World {
         // Compute the direction of the refracted ray
         let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
 
-        // Create the refracted ray
-        let refract_ray = Ray::new(comps.under_point, direction);
-
-        // Find the color of the refracted ray, making sure to multiply by the transparency value
-        // to account for any opacity
-        self.color_at(&refract_ray, remaining - 1) * transparency
+        Some(direction)
     }
 
     // Computes the approximation of the Fresnel Equations and returns the reflectance value between
@@ -290,7 +1285,7 @@ impl World {
             }
 
             // Compute cosine of theta_t using trig identity
-            let cos_t = (1.0 - sin2_t).cos();
+            let cos_t = (1.0 - sin2_t).sqrt();
 
             // When n1 > n2 use cos(theta_t) instead
             cos = cos_t;
@@ -301,53 +1296,117 @@ impl World {
         r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
 
+    /*
+        Computes the reflectance from the full Fresnel equations rather than Schlick's approximation
+        of them. Light is split into its s and p polarized parts, which reflect by different amounts
+        away from normal incidence, and since the light in the scene is unpolarized the two are
+        averaged. Returns 1.0 under total internal reflection, same as schlick.
+    */
+    pub fn fresnel(comps: &Computations) -> f64 {
+        let (n1, n2) = (comps.n1, comps.n2);
+
+        let cos_i = Tuple::dot(comps.eyev, comps.normalv);
+
+        let n = n1 / n2;
+        let sin2_t = (n * n) * (1.0 - (cos_i * cos_i));
+
+        if sin2_t > 1.0 {
+            return 1.0;
+        }
+
+        let cos_t = (1.0 - sin2_t).sqrt();
+
+        let s_polarized = ((n1 * cos_i - n2 * cos_t) / (n1 * cos_i + n2 * cos_t)).powi(2);
+        let p_polarized = ((n1 * cos_t - n2 * cos_i) / (n1 * cos_t + n2 * cos_i)).powi(2);
+
+        (s_polarized + p_polarized) / 2.0
+    }
+
     pub fn is_shadowed(&self, point: Tuple, light: PointLight) -> bool {
         assert!(point.is_point());
 
+        if !self.shadows_enabled {
+            return false;
+        }
+
         let vec = light.position - point;
 
         let distance = vec.magnitude(); // Measure the distance from the point to the light source
         let direction = vec.normalize(); // Create a ray pointing towards the light source
 
         let ray = Ray::new(point, direction);
-        let intersections = self.intersect_world(&ray); // Intersect the world with that ray
 
-        let hit = Intersection::hit(&intersections);
-        // Check to see if there was a hit, and if so did it occur before the ray reached the light source
-        if let Some((i, casts_shadow)) = hit {
-            if casts_shadow {
-                intersections[i].time() < distance
-            } else {
-                false
-            }
-        } else {
-            false
-        }
+        // Check to see if anything casting a shadow is hit before the ray reaches the light source
+        self.any_hit_before(&ray, distance)
+    }
+
+    // The indices (into lights) of the point lights with a clear line to the point, e.g. for
+    // working out why part of a render is darker than expected
+    pub fn illuminating_lights(&self, point: Tuple) -> Vec<usize> {
+        (0..self.lights.len())
+            .filter(|i| !self.is_shadowed(point, *self.lights[*i]))
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::geometry::cube::Cube;
+    use crate::geometry::group::Group;
     use crate::geometry::plane::Plane;
     use crate::geometry::shape::Shape;
     use crate::geometry::smooth_triangle::SmoothTriangle;
     use crate::geometry::sphere::Sphere;
-    use crate::materials::material::Material;
+    use crate::geometry::test_shape::TestShape;
+    use crate::materials::material::{BlendMode, Material};
     use crate::materials::phong::Phong;
     use crate::matrices::matrix::Matrix;
+    use crate::patterns::checker::Checker;
+    use crate::patterns::gradient::Gradient;
     use crate::patterns::solid::Solid;
+    use crate::patterns::striped::Striped;
     use crate::patterns::test_pattern::TestPattern;
     use crate::scene::computations::Computations;
-    use crate::scene::world::World;
+    use crate::scene::world::{RenderMode, World};
+    use crate::tuples::area_light::AreaLight;
     use crate::tuples::color::Color;
     use crate::tuples::intersection::Intersection;
     use crate::tuples::point_light::PointLight;
     use crate::tuples::ray::Ray;
+    use crate::tuples::sampling::SeededRng;
+    use crate::tuples::spot_light::SpotLight;
     use crate::tuples::tuple::Tuple;
     use crate::{EPSILON, MAX_RAY_RECURSION_DEPTH};
-    use std::f64::consts::SQRT_2;
+    use std::f64::consts::{PI, SQRT_2};
     use std::sync::Arc;
 
+    #[test]
+    fn given_a_world_with_no_lights_and_a_singular_transform_when_validating_should_report_both_problems(
+    ) {
+        // Arrange
+        let flattened: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(Matrix::scaling(0.0, 1.0, 1.0)),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        let group = Arc::new(Group::default());
+        group.add_child(flattened);
+
+        let world = World::new(vec![group], vec![]);
+
+        // Act
+        let result = world.validate();
+
+        // Assert
+        let problems = result.unwrap_err();
+
+        assert_eq!(2, problems.len());
+        assert!(problems[0].contains("no lights"));
+        assert!(problems[1].contains("singular transform"));
+        assert!(World::default().validate().is_ok());
+    }
+
     #[test]
     fn given_default_world_when_calculating_intersects_with_ray_should_return_correct_intersections_sorted_by_time(
     ) {
@@ -368,6 +1427,154 @@ mod tests {
         assert_eq!(6.0, result[3].time());
     }
 
+    #[test]
+    fn given_a_two_level_group_when_building_the_bounding_hierarchy_mesh_should_box_each_group() {
+        // Arrange
+        let inner = Arc::new(Group::new(Arc::new(Matrix::translation(5.0, 0.0, 0.0))));
+        inner.add_child(Arc::new(Sphere::unit()));
+
+        let outer = Arc::new(Group::default());
+        outer.add_children(vec![inner.clone(), Arc::new(Sphere::unit())]);
+
+        let world = World::new(vec![outer], vec![]);
+
+        // Act
+        let result = world.bounding_hierarchy_mesh(0.01, Arc::new(Phong::default()));
+
+        // Assert
+        assert_eq!(2, result.num_of_children());
+
+        let outer_box = result.get_child(0).unwrap();
+        let inner_box = result.get_child(1).unwrap();
+
+        assert_eq!(12, outer_box.num_of_children());
+        assert_eq!(12, inner_box.num_of_children());
+        assert!(!outer_box.casts_shadow());
+
+        // The inner group's box is moved along with the group
+        let bounds = inner_box.bounds();
+
+        assert_eq!(Tuple::point(3.995, -1.005, -1.005), bounds.min());
+        assert_eq!(Tuple::point(6.005, 1.005, 1.005), bounds.max());
+        assert_eq!(
+            Tuple::point(-1.005, -1.005, -1.005),
+            outer_box.bounds().min()
+        );
+        assert_eq!(Tuple::point(6.005, 1.005, 1.005), outer_box.bounds().max());
+    }
+
+    #[test]
+    fn given_default_world_when_visiting_each_intersection_should_see_the_same_hits_as_intersect_world(
+    ) {
+        // Arrange
+        let world = World::default();
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let mut visited: Vec<Intersection> = vec![];
+        world.for_each_intersection(&ray, |intersection| {
+            visited.push(intersection.clone());
+            true
+        });
+
+        // Assert
+        let mut collected = world.intersect_world(&ray);
+        visited.sort();
+
+        assert_eq!(collected.len(), visited.len());
+
+        for (expected, actual) in collected.iter().zip(visited.iter()) {
+            assert_eq!(expected.time(), actual.time());
+            assert!(Arc::ptr_eq(&expected.object(), &actual.object()));
+        }
+
+        let nearest = world.nearest_hit(&ray).unwrap();
+        let hit = collected.remove(0);
+
+        assert_eq!(hit.time(), nearest.time());
+        assert!(Arc::ptr_eq(&hit.object(), &nearest.object()));
+        assert!(world.any_hit_before(&ray, 4.5));
+        assert!(!world.any_hit_before(&ray, 3.5));
+    }
+
+    #[test]
+    fn given_an_object_that_casts_no_shadow_when_checking_for_any_hit_before_should_ignore_it() {
+        // Arrange
+        let sphere: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            false,
+        ));
+        let world = World::new(vec![sphere], vec![]);
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let result = world.any_hit_before(&ray, 10.0);
+
+        // Assert
+        assert!(!result);
+        assert!(world.nearest_hit(&ray).is_some());
+    }
+
+    #[test]
+    fn given_a_world_with_the_same_sphere_added_twice_when_deduplicating_should_keep_one_copy() {
+        // Arrange
+        let sphere: Arc<dyn Shape> = Arc::new(Sphere::unit());
+        let light = Arc::new(PointLight::new(
+            Tuple::point(-10.0, 10.0, -10.0),
+            Color::white(),
+        ));
+
+        let mut world = World::new(vec![sphere.clone(), sphere.clone()], vec![light.clone()]);
+        let single = World::new(vec![sphere.clone()], vec![light]);
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        world.deduplicate_objects();
+
+        // Assert
+        assert_eq!(vec![sphere.id()], world.object_ids());
+        assert_eq!(2, world.intersect_world(&ray).len());
+        assert_eq!(
+            single.color_at(&ray, MAX_RAY_RECURSION_DEPTH),
+            world.color_at(&ray, MAX_RAY_RECURSION_DEPTH)
+        );
+    }
+
+    #[test]
+    fn given_two_default_worlds_when_merging_them_should_sum_the_object_and_light_counts() {
+        // Arrange
+        let mut world = World::default();
+        let other = World::default();
+
+        // Act
+        world.merge(other);
+
+        // Assert
+        assert_eq!(4, world.objects.len());
+        assert_eq!(2, world.lights.len());
+    }
+
+    #[test]
+    fn given_two_worlds_sharing_a_light_when_merging_them_should_not_duplicate_the_light() {
+        // Arrange
+        let light = Arc::new(PointLight::new(
+            Tuple::point(-10.0, 10.0, -10.0),
+            Color::white(),
+        ));
+
+        let mut world = World::new(vec![Arc::new(Sphere::unit())], vec![light.clone()]);
+        let other = World::new(vec![Arc::new(Sphere::unit())], vec![light.clone()]);
+
+        // Act
+        world.merge(other);
+
+        // Assert
+        assert_eq!(2, world.objects.len());
+        assert_eq!(1, world.lights.len());
+    }
+
     #[test]
     fn given_standard_values_when_calling_prepare_computations_should_return_correct_values_for_lighting_function(
     ) {
@@ -475,36 +1682,321 @@ mod tests {
     }
 
     #[test]
-    fn given_a_ray_that_misses_when_calling_color_at_should_return_black() {
+    fn given_a_half_opaque_red_sphere_in_front_of_a_blue_background_when_calling_color_at_should_return_purple(
+    ) {
         // Arrange
-        let world = World::default();
+        let sphere = Sphere::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(
+                Phong::new(
+                    Box::new(Solid::new(Color::red())),
+                    1.0,
+                    0.0,
+                    0.0,
+                    200.0,
+                    0.0,
+                    0.0,
+                    1.0,
+                )
+                .with_opacity(0.5),
+            ),
+            true,
+        );
 
-        let ray = Ray::new(Tuple::point(0.0, 0.0, 5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let background = Plane::new(
+            Arc::new(
+                (&Matrix::translation(0.0, 0.0, 5.0) * &Matrix::rotation_x(PI / 2.0)).unwrap(),
+            ),
+            Arc::new(Phong::new(
+                Box::new(Solid::new(Color::blue())),
+                1.0,
+                0.0,
+                0.0,
+                200.0,
+                0.0,
+                0.0,
+                1.0,
+            )),
+            true,
+        );
+
+        let world = World::new(
+            vec![Arc::new(sphere), Arc::new(background)],
+            vec![Arc::new(PointLight::new(
+                Tuple::point(-10.0, 10.0, -10.0),
+                Color::white(),
+            ))],
+        );
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
 
         // Act
         let result = world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
 
         // Assert
-        assert_eq!(Color::black(), result);
+        // The ray passes through both the front and back of the sphere before reaching the
+        // background, so the blue is let through twice at half strength
+        assert_eq!(Color::new(0.75, 0.0, 0.25), result);
     }
 
     #[test]
-    fn given_a_ray_that_hits_when_calling_color_at_should_return_correct_color_value() {
+    fn given_a_ray_that_misses_when_calling_color_at_should_return_black() {
         // Arrange
         let world = World::default();
 
-        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let ray = Ray::new(Tuple::point(0.0, 0.0, 5.0), Tuple::vector(0.0, 0.0, 1.0));
 
         // Act
         let result = world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
 
         // Assert
-        assert_eq!(Color::new(0.38066, 0.47583, 0.2855), result);
+        assert_eq!(Color::black(), result);
     }
 
     #[test]
-    fn given_default_world_when_ray_is_between_outer_and_inner_but_pointed_at_inner_should_color_inner(
-    ) {
+    fn given_a_world_with_a_background_when_a_ray_misses_should_return_the_background_color() {
+        // Arrange
+        let mut world = World::default();
+        world.set_background(Some(Box::new(Gradient::new(
+            Box::new(Solid::new(Color::white())),
+            Box::new(Solid::new(Color::blue())),
+            Arc::new(Matrix::rotation_z(PI / 2.0)),
+        ))));
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.5, -1.0));
+
+        // Act
+        let result = world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        let y = ray.direction().normalize().y;
+        assert_eq!(Color::new(1.0 - y, 1.0 - y, 1.0), result);
+    }
+
+    #[test]
+    fn given_default_world_when_filtering_to_the_inner_sphere_should_only_color_rays_that_hit_it() {
+        // Arrange
+        let world = World::default();
+        let inner = world.objects[1].clone();
+
+        let through_both = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let outer_only = Ray::new(Tuple::point(0.0, 0.9, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let inner_color =
+            world.color_at_filtered(&through_both, MAX_RAY_RECURSION_DEPTH, inner.id());
+        let outer_color = world.color_at_filtered(&outer_only, MAX_RAY_RECURSION_DEPTH, inner.id());
+
+        // Assert
+        assert_ne!(Color::black(), inner_color);
+        assert_eq!(Color::black(), outer_color);
+        assert_ne!(
+            Color::black(),
+            world.color_at(&outer_only, MAX_RAY_RECURSION_DEPTH)
+        );
+    }
+
+    #[test]
+    fn given_a_checkered_floor_far_away_when_coloring_with_ray_differentials_should_blur_the_checks(
+    ) {
+        // Arrange
+        let floor: Arc<dyn Shape> = Arc::new(Plane::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::new(
+                Box::new(Checker::default()),
+                1.0,
+                0.0,
+                0.0,
+                200.0,
+                0.0,
+                0.0,
+                1.0,
+            )),
+            true,
+        ));
+
+        let world = World::new(
+            vec![floor],
+            vec![Arc::new(PointLight::new(
+                Tuple::point(0.0, 10.0, 0.0),
+                Color::white(),
+            ))],
+        );
+
+        // Glancing down at the floor, so that neighbouring pixels land several checks apart
+        let origin = Tuple::point(0.5, 1.0, 0.0);
+        let ray = Ray::new(origin, Tuple::vector(0.0, -0.01, 1.0).normalize());
+        let rx = Ray::new(origin, Tuple::vector(0.05, -0.01, 1.0).normalize());
+        let ry = Ray::new(origin, Tuple::vector(0.0, -0.0099, 1.0).normalize());
+
+        // Act
+        let crisp = world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
+        let filtered = world.color_at_with_differentials(&ray, &rx, &ry, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        assert_eq!(Color::white(), crisp);
+        assert_eq!(Color::new(0.5, 0.5, 0.5), filtered);
+    }
+
+    #[test]
+    fn given_a_white_floor_next_to_a_red_wall_when_path_tracing_should_bleed_red_onto_the_floor() {
+        // Arrange
+        let matte = |color: Color| {
+            Arc::new(Phong::new(
+                Box::new(Solid::new(color)),
+                0.1,
+                0.9,
+                0.0,
+                200.0,
+                0.0,
+                0.0,
+                1.0,
+            ))
+        };
+
+        let floor: Arc<dyn Shape> = Arc::new(Plane::new(
+            Arc::new(Matrix::identity(4)),
+            matte(Color::white()),
+            true,
+        ));
+        let wall: Arc<dyn Shape> = Arc::new(Plane::new(
+            Arc::new(
+                (&Matrix::translation(1.0, 0.0, 0.0) * &Matrix::rotation_z(PI / 2.0)).unwrap(),
+            ),
+            matte(Color::red()),
+            true,
+        ));
+
+        let world = World::new(
+            vec![floor, wall],
+            vec![Arc::new(PointLight::new(
+                Tuple::point(-2.0, 5.0, 0.0),
+                Color::white(),
+            ))],
+        );
+
+        // Looking straight down at the floor, just in front of the wall
+        let ray = Ray::new(Tuple::point(0.8, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        let mut rng = SeededRng::new(5);
+        let samples = 200;
+
+        // Act
+        let direct = world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
+
+        let mut total = Color::black();
+        for _ in 0..samples {
+            total = total + world.color_at_gi(&ray, MAX_RAY_RECURSION_DEPTH, &mut rng);
+        }
+        let global = total / samples as f64;
+
+        // Assert
+        assert!((direct.red - direct.green).abs() < EPSILON);
+        assert!(global.red > direct.red);
+        assert!(global.red - global.green > 0.05);
+    }
+
+    #[test]
+    fn given_an_area_light_whose_shape_cant_be_sampled_when_path_tracing_with_next_event_estimation_should_skip_sampling_it(
+    ) {
+        // Arrange
+        let floor: Arc<dyn Shape> = Arc::new(Plane::default());
+        let bulb: Arc<dyn Shape> = Arc::new(Cube::new(
+            Arc::new(Matrix::translation(0.0, 3.0, 0.0)),
+            Arc::new(Phong::default()),
+            true,
+        ));
+        let light = Arc::new(AreaLight::new(bulb, Color::white(), 24.0));
+
+        let mut world = World::new(vec![floor], vec![]);
+        world.add_area_light(light.clone());
+
+        let ray = Ray::new(Tuple::point(0.5, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        let mut rng = SeededRng::new(3);
+
+        // Act
+        let color = world.color_at_gi_nee(&ray, MAX_RAY_RECURSION_DEPTH, &mut rng);
+
+        // Assert
+        assert!(light.sample(&mut rng).is_none());
+        assert!(color.red.is_finite() && color.green.is_finite() && color.blue.is_finite());
+    }
+
+    #[test]
+    fn given_a_small_area_light_when_path_tracing_with_next_event_estimation_should_reduce_the_noise(
+    ) {
+        // Arrange
+        let floor: Arc<dyn Shape> = Arc::new(Plane::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::new(
+                Box::new(Solid::new(Color::white())),
+                0.0,
+                0.9,
+                0.0,
+                200.0,
+                0.0,
+                0.0,
+                1.0,
+            )),
+            true,
+        ));
+        let bulb: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(
+                (&Matrix::translation(0.0, 1.5, 0.0) * &Matrix::scaling(0.25, 0.25, 0.25)).unwrap(),
+            ),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        let mut world = World::new(vec![floor], vec![]);
+        world.add_area_light(Arc::new(AreaLight::new(
+            bulb,
+            Color::new(20.0, 20.0, 20.0),
+            4.0 * PI * 0.25 * 0.25,
+        )));
+
+        let ray = Ray::new(Tuple::point(0.5, 1.0, 0.0), Tuple::vector(0.0, -1.0, 0.0));
+        let samples = 400;
+
+        // Returns the mean and variance of the red channel over the samples
+        let statistics = |trace: &dyn Fn(&mut SeededRng) -> Color| {
+            let mut rng = SeededRng::new(17);
+            let values: Vec<f64> = (0..samples).map(|_| trace(&mut rng).red).collect();
+
+            let mean = values.iter().sum::<f64>() / samples as f64;
+            let variance =
+                values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / samples as f64;
+
+            (mean, variance)
+        };
+
+        // Act
+        let (naive_mean, naive_variance) =
+            statistics(&|rng| world.color_at_gi(&ray, MAX_RAY_RECURSION_DEPTH, rng));
+        let (nee_mean, nee_variance) =
+            statistics(&|rng| world.color_at_gi_nee(&ray, MAX_RAY_RECURSION_DEPTH, rng));
+
+        // Assert
+        assert!(nee_variance * 10.0 < naive_variance);
+        assert!((nee_mean - naive_mean).abs() < naive_mean * 0.5);
+    }
+
+    #[test]
+    fn given_a_ray_that_hits_when_calling_color_at_should_return_correct_color_value() {
+        // Arrange
+        let world = World::default();
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let result = world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        assert_eq!(Color::new(0.38066, 0.47583, 0.2855), result);
+    }
+
+    #[test]
+    fn given_default_world_when_ray_is_between_outer_and_inner_but_pointed_at_inner_should_color_inner(
+    ) {
         // Arrange
         let color = Color::white();
         let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
@@ -568,6 +2060,31 @@ mod tests {
         assert_eq!(true, result);
     }
 
+    #[test]
+    fn given_default_world_when_toggling_off_shadows_for_the_spheres_should_remove_their_shadow() {
+        // Arrange
+        let world = World::default();
+
+        let point = Tuple::point(10.0, -10.0, 10.0);
+        let before = world.is_shadowed(point, *world.lights[0]);
+
+        // Act
+        world.objects[0].set_casts_shadow(false);
+        world.objects[1].set_casts_shadow(false);
+
+        let after = world.is_shadowed(point, *world.lights[0]);
+
+        world.objects[0].set_casts_shadow(true);
+        world.objects[1].set_casts_shadow(true);
+
+        let restored = world.is_shadowed(point, *world.lights[0]);
+
+        // Assert
+        assert!(before);
+        assert!(!after);
+        assert!(restored);
+    }
+
     #[test]
     fn given_default_world_when_object_is_behind_the_light_should_be_no_shadow() {
         // Arrange
@@ -626,6 +2143,53 @@ mod tests {
         assert_eq!(Color::new(0.1, 0.1, 0.1), result);
     }
 
+    #[test]
+    fn given_default_world_with_shadows_disabled_when_object_is_between_point_and_light_should_be_no_shadow(
+    ) {
+        // Arrange
+        let mut world = World::default();
+        world.set_shadows_enabled(false);
+
+        let point = Tuple::point(10.0, -10.0, 10.0);
+
+        // Act
+        let result = world.is_shadowed(point, *world.lights[0]);
+
+        // Assert
+        assert!(!result);
+    }
+
+    #[test]
+    fn given_world_with_shadows_disabled_when_shading_hits_should_light_intersection_in_shadow() {
+        // Arrange
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        let material: Arc<dyn Material> = Arc::new(Phong::default());
+
+        let s1 = Arc::new(Sphere::unit());
+        let s2 = Arc::new(Sphere::new(
+            Arc::new(Matrix::translation(0.0, 0.0, 10.0)),
+            material.clone(),
+            true,
+        ));
+
+        let objects: Vec<Arc<dyn Shape>> = vec![s1.clone(), s2.clone()];
+        let lights = vec![Arc::new(light)];
+
+        let mut world = World::new(objects, lights);
+        world.set_shadows_enabled(false);
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, 5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let intersections = vec![Intersection::new(4.0, s2.clone())];
+
+        let comps = World::prepare_computations(0, &ray, &intersections);
+
+        // Act
+        let result = world.shade_hit(&comps, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        assert_eq!(Color::new(1.9, 1.9, 1.9), result);
+    }
+
     #[test]
     fn given_world_with_shadows_when_preparing_computations_should_slightly_offset_the_point() {
         // Arrange
@@ -774,42 +2338,21 @@ mod tests {
         assert_eq!(Color::new(0.19033, 0.23791, 0.14274), color);
     }
 
-    #[test]
-    fn given_a_ray_strikes_a_reflective_surface_when_shading_reflected_hit_should_return_correct_value(
-    ) {
-        // Arrange
+    fn reflective_plane_below_default_world(roughness: f64) -> (World, Arc<Plane>) {
         let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
-        let material: Arc<dyn Material> = Arc::new(Phong::new(
-            Box::new(Solid::default()),
-            0.1,
-            0.9,
-            0.9,
-            200.0,
-            0.5,
-            0.0,
-            1.0,
-        ));
-
-        let outer = Arc::new(Sphere::new(
-            Arc::new(Matrix::identity(4)),
-            Arc::new(Phong::new(
-                Box::new(Solid::new(Color::new(0.8, 1.0, 0.6))),
+        let material: Arc<dyn Material> = Arc::new(
+            Phong::new(
+                Box::new(Solid::default()),
                 0.1,
-                0.7,
-                0.2,
+                0.9,
+                0.9,
                 200.0,
-                0.0,
+                0.5,
                 0.0,
                 1.0,
-            )),
-            true,
-        ));
-
-        let inner = Arc::new(Sphere::new(
-            Arc::new(Matrix::scaling(0.5, 0.5, 0.5)),
-            Arc::new(Phong::default()),
-            true,
-        ));
+            )
+            .with_reflection_roughness(roughness),
+        );
 
         let plane = Arc::new(Plane::new(
             Arc::new(Matrix::translation(0.0, -1.0, 0.0)),
@@ -817,10 +2360,18 @@ mod tests {
             true,
         ));
 
-        let objects: Vec<Arc<dyn Shape>> = vec![outer.clone(), inner.clone(), plane.clone()];
-        let lights = vec![Arc::new(light)];
+        let mut world = World::default();
+        world.objects.push(plane.clone());
+        world.lights = vec![Arc::new(light)];
 
-        let world = World::new(objects, lights);
+        (world, plane)
+    }
+
+    #[test]
+    fn given_a_reflective_surface_with_no_roughness_when_calculating_reflected_color_should_match_a_sharp_reflection(
+    ) {
+        // Arrange
+        let (world, plane) = reflective_plane_below_default_world(0.0);
 
         let ray = Ray::new(
             Tuple::point(0.0, 0.0, -3.0),
@@ -831,30 +2382,181 @@ mod tests {
 
         // Act
         let comps = World::prepare_computations(0, &ray, &intersections);
-        let color = world.shade_hit(&comps, MAX_RAY_RECURSION_DEPTH);
+        let color = world.reflected_color(&comps, MAX_RAY_RECURSION_DEPTH);
 
         // Assert
-        assert_eq!(Color::new(0.87675, 0.92434, 0.82917), color);
+        assert_eq!(Color::new(0.19033, 0.23791, 0.14274), color);
     }
 
-    #[test]
-    pub fn given_a_ray_strikes_a_reflective_surface_when_reflection_is_not_allowed_to_recurse_further_should_return_black(
-    ) {
-        // Arrange
-        let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
-        let material: Arc<dyn Material> = Arc::new(Phong::new(
-            Box::new(Solid::default()),
-            0.1,
-            0.9,
-            0.9,
-            200.0,
-            0.5,
-            0.0,
-            1.0,
+    // A mirror floor reflecting a wall which is black for x < 0 and white for x >= 0, lit so that
+    // both halves show their flat pattern color
+    fn mirror_floor_facing_a_black_and_white_wall(roughness: f64) -> (World, Arc<Plane>) {
+        let floor = Arc::new(Plane::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(
+                Phong::new(
+                    Box::new(Solid::new(Color::black())),
+                    0.0,
+                    0.0,
+                    0.0,
+                    200.0,
+                    1.0,
+                    0.0,
+                    1.0,
+                )
+                .with_reflection_roughness(roughness),
+            ),
+            true,
         ));
 
-        let outer = Arc::new(Sphere::new(
-            Arc::new(Matrix::identity(4)),
+        // Stripes wide enough that the only edge anywhere near the reflection is the one at x = 0
+        let wall = Arc::new(Plane::new(
+            Arc::new(
+                (&Matrix::translation(0.0, 0.0, 10.0) * &Matrix::rotation_x(PI / 2.0)).unwrap(),
+            ),
+            Arc::new(Phong::new(
+                Box::new(Striped::new(
+                    Box::new(Solid::new(Color::white())),
+                    Box::new(Solid::new(Color::black())),
+                    Arc::new(Matrix::scaling(100.0, 1.0, 1.0)),
+                )),
+                1.0,
+                0.0,
+                0.0,
+                200.0,
+                0.0,
+                0.0,
+                1.0,
+            )),
+            true,
+        ));
+
+        let light = PointLight::new(Tuple::point(0.0, 5.0, 0.0), Color::white());
+        let world = World::new(vec![floor.clone(), wall], vec![Arc::new(light)]);
+
+        (world, floor)
+    }
+
+    #[test]
+    fn given_a_rough_reflective_surface_when_calculating_reflected_color_should_average_a_blurred_reflection(
+    ) {
+        // Arrange
+        let reflect_either_side_of_the_edge = |roughness: f64| {
+            let (world, floor) = mirror_floor_facing_a_black_and_white_wall(roughness);
+
+            [-0.05, 0.05].map(|x| {
+                // Hits the floor at z = 1, and reflects up towards the wall just beside the edge
+                let ray = Ray::new(
+                    Tuple::point(x, 1.0, 0.0),
+                    Tuple::vector(0.0, -SQRT_2 / 2.0, SQRT_2 / 2.0),
+                );
+                let intersections = vec![Intersection::new(SQRT_2, floor.clone())];
+                let comps = World::prepare_computations(0, &ray, &intersections);
+
+                world.reflected_color(&comps, MAX_RAY_RECURSION_DEPTH).red
+            })
+        };
+
+        // Act
+        let [sharp_black, sharp_white] = reflect_either_side_of_the_edge(0.0);
+        let [rough_black, rough_white] = reflect_either_side_of_the_edge(0.2);
+        let repeated = reflect_either_side_of_the_edge(0.2);
+
+        // Assert
+        assert!(sharp_black.abs() < EPSILON);
+        assert!((sharp_white - 1.0).abs() < EPSILON);
+
+        // Some of the blurred rays land on the other side of the edge, so neither side reflects
+        // its own color cleanly any more and the contrast across the edge drops
+        for value in [rough_black, rough_white] {
+            assert!(value > EPSILON && value < 1.0 - EPSILON);
+        }
+
+        assert!((rough_white - rough_black).abs() < sharp_white - sharp_black);
+        assert_eq!([rough_black, rough_white], repeated);
+    }
+
+    #[test]
+    fn given_a_ray_strikes_a_reflective_surface_when_shading_reflected_hit_should_return_correct_value(
+    ) {
+        // Arrange
+        let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
+        let material: Arc<dyn Material> = Arc::new(Phong::new(
+            Box::new(Solid::default()),
+            0.1,
+            0.9,
+            0.9,
+            200.0,
+            0.5,
+            0.0,
+            1.0,
+        ));
+
+        let outer = Arc::new(Sphere::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::new(
+                Box::new(Solid::new(Color::new(0.8, 1.0, 0.6))),
+                0.1,
+                0.7,
+                0.2,
+                200.0,
+                0.0,
+                0.0,
+                1.0,
+            )),
+            true,
+        ));
+
+        let inner = Arc::new(Sphere::new(
+            Arc::new(Matrix::scaling(0.5, 0.5, 0.5)),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        let plane = Arc::new(Plane::new(
+            Arc::new(Matrix::translation(0.0, -1.0, 0.0)),
+            material.clone(),
+            true,
+        ));
+
+        let objects: Vec<Arc<dyn Shape>> = vec![outer.clone(), inner.clone(), plane.clone()];
+        let lights = vec![Arc::new(light)];
+
+        let world = World::new(objects, lights);
+
+        let ray = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -SQRT_2 / 2.0, SQRT_2 / 2.0),
+        );
+
+        let intersections = vec![Intersection::new(SQRT_2, plane.clone())];
+
+        // Act
+        let comps = World::prepare_computations(0, &ray, &intersections);
+        let color = world.shade_hit(&comps, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        assert_eq!(Color::new(0.87675, 0.92434, 0.82917), color);
+    }
+
+    #[test]
+    pub fn given_a_ray_strikes_a_reflective_surface_when_reflection_is_not_allowed_to_recurse_further_should_return_black(
+    ) {
+        // Arrange
+        let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
+        let material: Arc<dyn Material> = Arc::new(Phong::new(
+            Box::new(Solid::default()),
+            0.1,
+            0.9,
+            0.9,
+            200.0,
+            0.5,
+            0.0,
+            1.0,
+        ));
+
+        let outer = Arc::new(Sphere::new(
+            Arc::new(Matrix::identity(4)),
             Arc::new(Phong::new(
                 Box::new(Solid::new(Color::new(0.8, 1.0, 0.6))),
                 0.1,
@@ -1016,6 +2718,49 @@ mod tests {
         assert_eq!(true, comps.point.z < comps.under_point.z);
     }
 
+    #[test]
+    fn given_a_ray_starting_inside_a_glass_sphere_when_preparing_computations_should_offset_under_point_outside_the_surface(
+    ) {
+        // Arrange
+        let shape: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::new(
+                Box::new(Solid::default()),
+                0.1,
+                0.9,
+                0.9,
+                200.0,
+                0.0,
+                1.0,
+                1.5,
+            )),
+            true,
+        ));
+
+        let world = World::new(vec![shape], vec![]);
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, 0.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let intersections = world.intersect_world(&ray);
+        let (hit, _) = Intersection::hit(&intersections).unwrap();
+
+        // Act
+        let comps = World::prepare_computations(hit, &ray, &intersections);
+
+        let refract_ray = Ray::new(comps.under_point, ray.direction());
+        let refract_hits = world.intersect_world(&refract_ray);
+
+        // Assert
+        assert!(comps.inside);
+        assert_eq!(1.5, comps.n1);
+        assert_eq!(1.0, comps.n2);
+
+        assert!(comps.over_point.z < 1.0);
+        assert!(comps.under_point.z > 1.0);
+
+        assert!(Intersection::hit(&refract_hits).is_none());
+    }
+
     #[test]
     fn given_an_opaque_surface_when_calculating_refracted_color_should_return_black() {
         // Arrange
@@ -1333,7 +3078,7 @@ mod tests {
         let result = World::schlick(&comps);
 
         // Assert
-        assert_eq!(true, (result - 0.0597) < EPSILON);
+        assert!((result - 0.04).abs() < EPSILON);
     }
 
     #[test]
@@ -1466,4 +3211,635 @@ mod tests {
         // Assert
         assert_eq!(Tuple::vector(-0.5547, 0.83205, 0.0), comps.normalv);
     }
+
+    #[test]
+    fn given_a_ray_aimed_away_from_all_geometry_when_coloring_should_return_the_background_without_testing_any_objects(
+    ) {
+        // Arrange
+        let shape = Arc::new(TestShape::new());
+        let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
+
+        let world = World::new(vec![shape.clone()], vec![Arc::new(light)]);
+
+        let away = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, -1.0));
+        let towards = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let color = world.color_at(&away, MAX_RAY_RECURSION_DEPTH);
+        let count_after_away = shape.intersect_count();
+
+        world.color_at(&towards, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        assert_eq!(Color::black(), color);
+        assert_eq!(0, count_after_away);
+        assert_eq!(1, shape.intersect_count());
+    }
+
+    #[test]
+    fn given_a_shape_with_thousands_of_intersections_when_intersecting_the_world_should_keep_only_the_nearest(
+    ) {
+        // Arrange
+        let row = Arc::new(Group::default());
+
+        // A long line of spheres one after another, standing in for a broken mesh
+        for i in 0..2000 {
+            row.add_child(Arc::new(Sphere::new(
+                Arc::new(Matrix::translation(0.0, 0.0, 3.0 * i as f64)),
+                Arc::new(Phong::default()),
+                true,
+            )));
+        }
+
+        let mut world = World::new(vec![row], vec![]);
+        world.set_max_intersections(100);
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let result = world.intersect_world(&ray);
+
+        // Assert
+        assert_eq!(100, result.len());
+        assert_eq!(4.0, result[0].time());
+        assert_eq!(6.0, result[1].time());
+        assert_eq!(153.0, result[99].time());
+    }
+
+    #[test]
+    fn given_a_spotlight_with_a_checker_gobo_when_lighting_a_plane_should_project_alternating_bands(
+    ) {
+        // Arrange
+        let plane = Arc::new(Plane::default());
+
+        let gobo = Checker::new(
+            Box::new(Solid::new(Color::white())),
+            Box::new(Solid::new(Color::black())),
+            Arc::new(Matrix::scaling(0.25, 0.25, 0.25)),
+        );
+
+        let spot_light = SpotLight::new(
+            Tuple::point(0.0, 10.0, 0.0),
+            Tuple::vector(0.0, -1.0, 0.0),
+            Color::white(),
+            PI / 4.0,
+        )
+        .with_gobo(Box::new(gobo));
+
+        let mut world = World::new(vec![plane], vec![]);
+        world.add_spot_light(Arc::new(spot_light));
+
+        // Act
+        let colors: Vec<Color> = [1.25, 3.75, 6.25, 8.75]
+            .iter()
+            .map(|x| {
+                let ray = Ray::new(Tuple::point(*x, 1.0, -1.25), Tuple::vector(0.0, -1.0, 0.0));
+
+                world.color_at(&ray, MAX_RAY_RECURSION_DEPTH)
+            })
+            .collect();
+
+        // Assert
+        assert!(colors[0].red > 0.1);
+        assert_eq!(Color::black(), colors[1]);
+        assert!(colors[2].red > 0.1);
+        assert_eq!(Color::black(), colors[3]);
+    }
+
+    #[test]
+    fn given_the_default_world_when_picking_with_the_centre_ray_should_return_the_outer_sphere() {
+        // Arrange
+        let world = World::default();
+        let outer = world.objects[0].clone();
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let miss = Ray::new(Tuple::point(0.0, 5.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let result = world.pick(&ray).unwrap();
+
+        // Assert
+        assert_eq!(outer.id(), result.object_id);
+        assert_eq!(Tuple::point(0.0, 0.0, -1.0), result.point);
+        assert_eq!(Tuple::vector(0.0, 0.0, -1.0), result.normal);
+        assert_eq!(4.0, result.distance);
+        assert!(world.pick(&miss).is_none());
+        assert_eq!(vec![Some(result), None], world.pick_all(&[ray, miss]));
+    }
+
+    #[test]
+    fn given_a_dim_light_below_the_importance_threshold_when_shading_should_skip_its_shadow_ray() {
+        // Arrange
+        let plane: Arc<dyn Shape> = Arc::new(Plane::default());
+
+        // Never reports a hit so can't block either light, just there to count the rays cast. It
+        // sits around the shaded point so that no shadow ray can skip it by its bounds.
+        let counter = Arc::new(TestShape::new_with_transform(Arc::new(Matrix::identity(4))));
+
+        let bright = PointLight::new(Tuple::point(0.0, 10.0, -10.0), Color::white());
+        let dim = PointLight::new(Tuple::point(-10.0, 10.0, 0.0), Color::new(0.02, 0.02, 0.02));
+
+        let mut world = World::new(
+            vec![plane.clone(), counter.clone()],
+            vec![Arc::new(bright), Arc::new(dim)],
+        );
+
+        let ray = Ray::new(
+            Tuple::point(0.0, 1.0, -1.0),
+            Tuple::vector(0.0, -SQRT_2 / 2.0, SQRT_2 / 2.0),
+        );
+        let intersection = Intersection::new(SQRT_2, plane);
+        let comps = World::prepare_computations(0, &ray, &vec![intersection]);
+
+        // Act
+        let exact = world.shade_hit(&comps, MAX_RAY_RECURSION_DEPTH);
+        let exact_rays = counter.intersect_count();
+
+        world.set_light_importance_threshold(0.1);
+
+        let approximate = world.shade_hit(&comps, MAX_RAY_RECURSION_DEPTH);
+        let approximate_rays = counter.intersect_count() - exact_rays;
+
+        // Assert
+        assert_eq!(2, exact_rays);
+        assert_eq!(1, approximate_rays);
+        assert!((exact.red - approximate.red).abs() < 0.02);
+        assert!((exact.green - approximate.green).abs() < 0.02);
+        assert!((exact.blue - approximate.blue).abs() < 0.02);
+    }
+
+    #[test]
+    fn given_a_glass_sphere_when_comparing_fresnel_with_schlick_should_match_head_on_and_diverge_at_grazing_angles(
+    ) {
+        // Arrange
+        let shape = Arc::new(Sphere::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::new(
+                Box::new(Solid::default()),
+                0.1,
+                0.9,
+                0.9,
+                200.0,
+                0.0,
+                1.0,
+                1.5,
+            )),
+            true,
+        ));
+
+        let head_on = Ray::new(Tuple::point(0.0, 0.0, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+        let grazing = Ray::new(Tuple::point(0.0, 0.99, -2.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        let head_on_hit = vec![Intersection::new(1.0, shape.clone())];
+        let grazing_hit = vec![Intersection::new(1.8589, shape.clone())];
+
+        // Act
+        let head_on_comps = World::prepare_computations(0, &head_on, &head_on_hit);
+        let grazing_comps = World::prepare_computations(0, &grazing, &grazing_hit);
+
+        let head_on_fresnel = World::fresnel(&head_on_comps);
+        let head_on_schlick = World::schlick(&head_on_comps);
+        let grazing_fresnel = World::fresnel(&grazing_comps);
+        let grazing_schlick = World::schlick(&grazing_comps);
+
+        // Assert
+        let r0 = ((1.0_f64 - 1.5) / (1.0 + 1.5)).powi(2);
+
+        assert!((head_on_fresnel - r0).abs() < EPSILON);
+        assert!((head_on_schlick - r0).abs() < EPSILON);
+
+        let difference = (grazing_fresnel - grazing_schlick).abs();
+
+        assert!(difference > 0.001);
+        assert!(difference < 0.1);
+    }
+
+    #[test]
+    fn given_a_clip_plane_through_a_sphere_when_coloring_rays_should_only_show_the_unclipped_half()
+    {
+        // Arrange
+        let sphere: Arc<dyn Shape> = Arc::new(Sphere::unit());
+        let light = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
+
+        let mut world = World::new(vec![sphere], vec![Arc::new(light)]);
+        world.add_clip_plane(Tuple::origin(), Tuple::vector(1.0, 0.0, 0.0));
+
+        let clipped_side = Ray::new(Tuple::point(0.5, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let kept_side = Ray::new(Tuple::point(-0.5, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let across = Ray::new(Tuple::point(5.0, 0.0, 0.0), Tuple::vector(-1.0, 0.0, 0.0));
+
+        // Act
+        let clipped_color = world.color_at(&clipped_side, MAX_RAY_RECURSION_DEPTH);
+        let kept_color = world.color_at(&kept_side, MAX_RAY_RECURSION_DEPTH);
+        let across_intersections = world.intersect_world(&across);
+
+        // Assert
+        assert_eq!(Color::black(), clipped_color);
+        assert_ne!(Color::black(), kept_color);
+
+        // The ray carries on through the clipped front of the sphere to the inside of the back
+        assert_eq!(1, across_intersections.len());
+        assert_eq!(6.0, across_intersections[0].time());
+    }
+
+    #[test]
+    fn given_a_reflective_surface_lit_by_two_lights_when_shading_hit_should_only_add_the_reflection_once(
+    ) {
+        // Arrange
+        let first = PointLight::new(Tuple::point(-10.0, 10.0, -10.0), Color::white());
+        let second = PointLight::new(Tuple::point(10.0, 10.0, -10.0), Color::new(0.5, 0.5, 0.5));
+
+        let plane = Arc::new(Plane::new(
+            Arc::new(Matrix::translation(0.0, -1.0, 0.0)),
+            Arc::new(Phong::new(
+                Box::new(Solid::default()),
+                0.1,
+                0.9,
+                0.9,
+                200.0,
+                0.5,
+                0.0,
+                1.0,
+            )),
+            true,
+        ));
+
+        let sphere: Arc<dyn Shape> = Arc::new(Sphere::unit());
+        let objects: Vec<Arc<dyn Shape>> = vec![sphere, plane.clone()];
+
+        let world = World::new(objects, vec![Arc::new(first), Arc::new(second)]);
+
+        let ray = Ray::new(
+            Tuple::point(0.0, 0.0, -3.0),
+            Tuple::vector(0.0, -SQRT_2 / 2.0, SQRT_2 / 2.0),
+        );
+
+        let intersections = vec![Intersection::new(SQRT_2, plane.clone())];
+        let comps = World::prepare_computations(0, &ray, &intersections);
+
+        let in_shadow = [
+            world.is_shadowed(comps.over_point, first),
+            world.is_shadowed(comps.over_point, second),
+        ];
+
+        // Act
+        let color = world.shade_hit(&comps, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        let surface =
+            plane
+                .get_material()
+                .lighting_multi_at_hit(&[first, second], &comps, &in_shadow);
+        let reflected = world.reflected_color(&comps, MAX_RAY_RECURSION_DEPTH);
+
+        assert_eq!(surface + reflected, color);
+    }
+
+    #[test]
+    fn given_an_underwater_scene_when_refracting_into_an_air_bubble_should_bend_the_ray() {
+        // Arrange
+        let bubble: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::new(
+                Box::new(Solid::default()),
+                0.0,
+                0.0,
+                0.0,
+                200.0,
+                0.0,
+                1.0,
+                1.0,
+            )),
+            true,
+        ));
+
+        let mut world = World::new(vec![bubble.clone()], vec![]);
+
+        let ray = Ray::new(Tuple::point(0.0, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let intersections = world.intersect_world(&ray);
+
+        // Act
+        let in_vacuum = world.computations_for(0, &ray, &intersections);
+
+        world.set_ambient_refractive_index(1.333);
+        let underwater = world.computations_for(0, &ray, &intersections);
+
+        // Assert
+        assert_eq!(1.0, in_vacuum.n1);
+        assert_eq!(1.333, underwater.n1);
+        assert_eq!(1.0, underwater.n2);
+
+        let straight_on = World::refracted_direction(&in_vacuum).unwrap();
+        let bent = World::refracted_direction(&underwater).unwrap();
+
+        assert_eq!(ray.direction(), straight_on.normalize());
+        assert_ne!(ray.direction(), bent.normalize());
+
+        // Going into the less dense air, the ray bends away from the normal
+        assert!(bent.normalize().y > 0.0);
+    }
+
+    #[test]
+    fn given_a_world_in_segmentation_mode_when_coloring_rays_should_draw_each_object_a_flat_color()
+    {
+        // Arrange
+        let mut world = World::default();
+        world.set_render_mode(RenderMode::Segmentation);
+
+        let outer_id = world.objects[0].id();
+
+        let through_centre = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let off_centre = Ray::new(Tuple::point(0.5, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let miss = Ray::new(Tuple::point(0.0, 5.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Inside the outer sphere, looking at the inner one
+        let inside = Ray::new(Tuple::point(0.0, 0.0, -0.75), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let centre_color = world.color_at(&through_centre, MAX_RAY_RECURSION_DEPTH);
+        let off_centre_color = world.color_at(&off_centre, MAX_RAY_RECURSION_DEPTH);
+        let miss_color = world.color_at(&miss, MAX_RAY_RECURSION_DEPTH);
+        let inside_color = world.color_at(&inside, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        assert_eq!(World::segmentation_color(outer_id), centre_color);
+        assert_eq!(centre_color, off_centre_color);
+        assert_eq!(Color::black(), miss_color);
+        assert_ne!(Color::black(), inside_color);
+        assert_ne!(centre_color, inside_color);
+        assert_eq!(
+            World::segmentation_color(outer_id),
+            World::segmentation_color(outer_id)
+        );
+    }
+
+    #[test]
+    fn given_a_foggy_world_when_coloring_rays_should_fade_further_objects_more_into_the_fog() {
+        // Arrange
+        let fog_color = Color::new(0.5, 0.5, 0.5);
+
+        let red_sphere = |x: f64, z: f64| -> Arc<dyn Shape> {
+            Arc::new(Sphere::new(
+                Arc::new(Matrix::translation(x, 0.0, z)),
+                Arc::new(Phong::new(
+                    Box::new(Solid::new(Color::red())),
+                    1.0,
+                    0.0,
+                    0.0,
+                    200.0,
+                    0.0,
+                    0.0,
+                    1.0,
+                )),
+                true,
+            ))
+        };
+
+        let light = PointLight::new(Tuple::point(0.0, 0.0, -10.0), Color::white());
+        let mut world = World::new(
+            vec![red_sphere(-2.0, 0.0), red_sphere(2.0, 20.0)],
+            vec![Arc::new(light)],
+        );
+        world.set_fog(Some((fog_color, 0.1)));
+
+        let towards =
+            |x: f64, y: f64| Ray::new(Tuple::point(x, y, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let near = world.color_at(&towards(-2.0, 0.0), MAX_RAY_RECURSION_DEPTH);
+        let far = world.color_at(&towards(2.0, 0.0), MAX_RAY_RECURSION_DEPTH);
+        let miss = world.color_at(&towards(0.0, 5.0), MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        let distance_to_fog = |color: Color| {
+            let difference = color - fog_color;
+
+            difference.red.abs() + difference.green.abs() + difference.blue.abs()
+        };
+
+        // The near sphere is 4 units away, so keeps e^-0.4 of its own color
+        let kept = (-0.4_f64).exp();
+        assert_eq!((Color::red() * kept) + (fog_color * (1.0 - kept)), near);
+        assert!(distance_to_fog(far) < distance_to_fog(near));
+        assert_eq!(fog_color, miss);
+    }
+
+    #[test]
+    fn given_the_default_world_when_adding_objects_should_grow_its_cached_bounds() {
+        // Arrange
+        let mut world = World::default();
+
+        let initial = world.bounds();
+
+        let distant = Sphere::new(
+            Arc::new(Matrix::translation(10.0, 0.0, 0.0)),
+            Arc::new(Phong::default()),
+            true,
+        );
+        let floor = Plane::new(
+            Arc::new(Matrix::translation(0.0, -1.0, 0.0)),
+            Arc::new(Phong::default()),
+            true,
+        );
+
+        // Act
+        world.add_object(Arc::new(distant));
+        let with_distant = world.bounds();
+
+        world.add_object(Arc::new(floor));
+        let with_floor = world.bounds();
+        let finite_with_floor = world.finite_bounds();
+
+        // Assert
+        assert_eq!(Tuple::point(-1.0, -1.0, -1.0), initial.min());
+        assert_eq!(Tuple::point(1.0, 1.0, 1.0), initial.max());
+
+        assert_eq!(Tuple::point(-1.0, -1.0, -1.0), with_distant.min());
+        assert_eq!(Tuple::point(11.0, 1.0, 1.0), with_distant.max());
+
+        // The floor stretches the full bounds out forever, but is left out of the finite ones
+        assert!(!with_floor.min().is_finite());
+        assert_eq!(with_distant.min(), finite_with_floor.min());
+        assert_eq!(with_distant.max(), finite_with_floor.max());
+    }
+
+    #[test]
+    fn given_a_group_in_the_world_when_moving_it_after_rendering_should_still_be_hit_where_it_moved_to(
+    ) {
+        // Arrange
+        let group = Arc::new(Group::default());
+        group.add_child(Arc::new(Sphere::unit()));
+
+        let light = Arc::new(PointLight::new(
+            Tuple::point(-10.0, 10.0, -10.0),
+            Color::white(),
+        ));
+        let world = World::new(vec![group.clone()], vec![light]);
+
+        let ray = Ray::new(Tuple::point(10.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Caches the world's bounds, which the ray misses at first
+        let before = world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
+
+        // Act
+        group.set_transform(Arc::new(Matrix::translation(10.0, 0.0, 0.0)));
+
+        let after = world.color_at(&ray, MAX_RAY_RECURSION_DEPTH);
+
+        // Assert
+        assert_eq!(Color::black(), before);
+        assert_eq!(2, world.intersect_world(&ray).len());
+        assert_ne!(Color::black(), after);
+        assert_eq!(11.0, world.bounds().max().x);
+    }
+
+    #[test]
+    fn given_a_sphere_shared_between_two_groups_when_deduplicating_intersections_should_only_count_its_hits_once(
+    ) {
+        // Arrange
+        let sphere: Arc<dyn Shape> = Arc::new(Sphere::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(Phong::default()),
+            true,
+        ));
+
+        let first = Arc::new(Group::default());
+        first.add_child(sphere.clone());
+
+        let second = Arc::new(Group::default());
+        second.add_child(sphere.clone());
+
+        let objects: Vec<Arc<dyn Shape>> = vec![first, second];
+        let mut world = World::new(objects, vec![]);
+
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let doubled = world.intersect_world(&ray);
+
+        world.set_deduplicate_intersections(true);
+        let deduplicated = world.intersect_world(&ray);
+
+        // Assert
+        assert_eq!(4, doubled.len());
+        assert_eq!(2, deduplicated.len());
+        assert_eq!(4.0, deduplicated[0].time());
+        assert_eq!(6.0, deduplicated[1].time());
+    }
+
+    #[test]
+    fn given_a_fixed_blend_mode_when_shading_glass_should_mix_reflection_and_refraction_by_that_ratio_at_any_angle(
+    ) {
+        // Arrange
+        let glass: Arc<dyn Shape> = Arc::new(Plane::new(
+            Arc::new(Matrix::identity(4)),
+            Arc::new(
+                Phong::new(
+                    Box::new(Solid::default()),
+                    0.1,
+                    0.9,
+                    0.0,
+                    200.0,
+                    0.5,
+                    0.5,
+                    1.5,
+                )
+                .with_blend_mode(BlendMode::Fixed(0.3)),
+            ),
+            true,
+        ));
+
+        let floor: Arc<dyn Shape> = Arc::new(Plane::new(
+            Arc::new(Matrix::translation(0.0, -3.0, 0.0)),
+            Arc::new(Phong::new(
+                Box::new(Solid::new(Color::red())),
+                1.0,
+                0.0,
+                0.0,
+                200.0,
+                0.0,
+                0.0,
+                1.0,
+            )),
+            true,
+        ));
+
+        let light = PointLight::new(Tuple::point(0.0, 10.0, 0.0), Color::white());
+        let mut world = World::new(vec![glass.clone(), floor], vec![Arc::new(light)]);
+        world.set_background(Some(Box::new(Solid::new(Color::blue()))));
+
+        let steep = Ray::new(Tuple::point(0.0, 1.0, -1.0), Tuple::vector(0.0, -1.0, 1.0));
+        let grazing = Ray::new(
+            Tuple::point(0.0, 1.0, -10.0),
+            Tuple::vector(0.0, -1.0, 10.0),
+        );
+
+        for ray in [steep, grazing] {
+            let intersections = world.intersect_world(&ray);
+            let comps = World::prepare_computations(0, &ray, &intersections);
+
+            let surface = glass
+                .get_material()
+                .lighting_at_hit(*world.lights[0], &comps, false);
+            let reflected = world.reflected_color(&comps, MAX_RAY_RECURSION_DEPTH);
+            let refracted = world.refracted_color(&comps, MAX_RAY_RECURSION_DEPTH);
+
+            // Act
+            let result = world.shade_hit(&comps, MAX_RAY_RECURSION_DEPTH);
+
+            // Assert
+            assert_ne!(Color::black(), reflected);
+            assert_ne!(Color::black(), refracted);
+            assert_eq!(surface + (reflected * 0.3) + (refracted * 0.7), result);
+        }
+    }
+
+    #[test]
+    fn given_an_object_far_from_a_shadow_ray_when_checking_for_shadows_should_skip_it_by_its_bounding_sphere(
+    ) {
+        // Arrange
+        let far = Arc::new(TestShape::new_with_transform(Arc::new(
+            Matrix::translation(100.0, 0.0, 0.0),
+        )));
+        let near = Arc::new(TestShape::new_with_transform(Arc::new(
+            Matrix::translation(0.0, 5.0, 0.0),
+        )));
+
+        let light = PointLight::new(Tuple::point(0.0, 10.0, 0.0), Color::white());
+        let world = World::new(vec![far.clone(), near.clone()], vec![Arc::new(light)]);
+
+        // Act
+        let shadowed = world.is_shadowed(Tuple::origin(), light);
+
+        // Assert
+        assert!(!shadowed);
+        assert_eq!(0, far.intersect_count());
+        assert_eq!(1, near.intersect_count());
+    }
+
+    #[test]
+    fn given_default_world_when_finding_the_lights_illuminating_a_point_should_leave_out_blocked_ones(
+    ) {
+        // Arrange
+        let mut world = World::default();
+
+        // A second light straight below the spheres
+        world.lights.push(Arc::new(PointLight::new(
+            Tuple::point(0.0, -10.0, 0.0),
+            Color::white(),
+        )));
+
+        // Act
+        let facing_both = world.illuminating_lights(Tuple::point(-5.0, 0.0, -5.0));
+        let behind_spheres = world.illuminating_lights(Tuple::point(10.0, -10.0, 10.0));
+        let above_spheres = world.illuminating_lights(Tuple::point(0.0, 10.0, 0.0));
+
+        // Assert
+        assert_eq!(vec![0, 1], facing_both);
+        assert_eq!(vec![1], behind_spheres);
+        assert_eq!(vec![0], above_spheres);
+        assert!(World::default()
+            .illuminating_lights(Tuple::point(10.0, -10.0, 10.0))
+            .is_empty());
+    }
 }
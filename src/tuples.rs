@@ -1,6 +1,9 @@
+pub mod area_light;
 pub mod bounding_box;
 pub mod color;
 pub mod intersection;
 pub mod point_light;
 pub mod ray;
+pub mod sampling;
+pub mod spot_light;
 pub mod tuple;
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use crate::geometry::shape::Shape;
+use crate::tuples::color::Color;
+use crate::tuples::sampling::SeededRng;
+use crate::tuples::tuple::Tuple;
+
+/*
+    A shape that gives off light from its whole surface, rather than from a single point. Only
+    used by the path traced global illumination mode, where rays that hit the shape pick up its
+    emission, and with next event estimation points on its surface are sampled directly.
+
+    The area is that of the shape's surface in world space (e.g. 4 * pi * r^2 for a sphere scaled
+    by r), which is needed to weight the samples since the shape can only pick points uniformly.
+*/
+pub struct AreaLight {
+    shape: Arc<dyn Shape>,
+    emission: Color,
+    area: f64,
+}
+
+impl AreaLight {
+    pub fn new(shape: Arc<dyn Shape>, emission: Color, area: f64) -> AreaLight {
+        assert!(area > 0.0);
+
+        AreaLight {
+            shape,
+            emission,
+            area,
+        }
+    }
+
+    pub fn shape(&self) -> Arc<dyn Shape> {
+        self.shape.clone()
+    }
+
+    pub fn emission(&self) -> Color {
+        self.emission
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+
+    // Picks a random point on the light, along with its surface normal there (both in world space),
+    // or None if the light's shape can't be sampled (see Shape::sample_surface)
+    pub fn sample(&self, rng: &mut SeededRng) -> Option<(Tuple, Tuple)> {
+        self.shape.sample_surface(rng)
+    }
+}
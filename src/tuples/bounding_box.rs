@@ -23,17 +23,8 @@ impl BoundingBox {
     }
 
     pub fn add_point(mut self, point: Tuple) -> Self {
-        self.min = Tuple::point(
-            f64::min(self.min.x, point.x),
-            f64::min(self.min.y, point.y),
-            f64::min(self.min.z, point.z),
-        );
-
-        self.max = Tuple::point(
-            f64::max(self.max.x, point.x),
-            f64::max(self.max.y, point.y),
-            f64::max(self.max.z, point.z),
-        );
+        self.min = Tuple::component_min(self.min, point);
+        self.max = Tuple::component_max(self.max, point);
 
         self
     }
@@ -78,43 +69,64 @@ impl BoundingBox {
     }
 
     pub fn intersects(&self, ray: &Ray) -> bool {
+        self.hit_range(ray).is_some()
+    }
+
+    // Unlike intersects, which treats the ray as a line extending in both directions, this also
+    // requires some of the box to be in front of the ray's origin
+    pub fn intersects_ahead(&self, ray: &Ray) -> bool {
+        matches!(self.hit_range(ray), Some((_, tmax)) if tmax >= 0.0)
+    }
+
+    // Finds the range of t values over which the ray's line passes through the box, if it does
+    fn hit_range(&self, ray: &Ray) -> Option<(f64, f64)> {
         let origin = ray.origin();
         let direction = ray.direction();
 
-        // For each axes of the cube, check where ray intersects the corresponding plane
-        let (xtmin, xtmax) = BoundingBox::check_axis(origin.x, direction.x, self.min.x, self.max.x);
-        let (ytmin, ytmax) = BoundingBox::check_axis(origin.y, direction.y, self.min.y, self.max.y);
-        let (ztmin, ztmax) = BoundingBox::check_axis(origin.z, direction.z, self.min.z, self.max.z);
-
-        // Return the largest minimum t value and the smallest maximum t value
-        let tmin = f64::max(f64::max(xtmin, ytmin), ztmin);
-        let tmax = f64::min(f64::min(xtmax, ytmax), ztmax);
+        let axes = [
+            (origin.x, direction.x, self.min.x, self.max.x),
+            (origin.y, direction.y, self.min.y, self.max.y),
+            (origin.z, direction.z, self.min.z, self.max.z),
+        ];
 
-        if tmin > tmax {
-            false
-        } else {
-            true
+        // Track the largest minimum t value and the smallest maximum t value across the axes
+        let mut tmin = f64::NEG_INFINITY;
+        let mut tmax = f64::INFINITY;
+
+        for (origin, direction, min, max) in axes {
+            // Fast path for axis aligned rays (which most shadow and camera rays have at least one
+            // of). If the ray runs parallel to this pair of planes then it can only hit the box
+            // if it starts between them, in which case this axis places no limit on t at all.
+            if direction.abs() < EPSILON {
+                if origin < min || origin > max {
+                    return None;
+                }
+
+                continue;
+            }
+
+            // Otherwise check where ray intersects the corresponding planes. f64::min/max are used
+            // as they ignore NaN values rather than letting them poison the result.
+            let (axis_tmin, axis_tmax) = BoundingBox::check_axis(origin, direction, min, max);
+
+            tmin = f64::max(tmin, axis_tmin);
+            tmax = f64::min(tmax, axis_tmax);
+
+            // The range can only shrink from here, so stop as soon as the ray has missed
+            if tmin > tmax {
+                return None;
+            }
         }
+
+        Some((tmin, tmax))
     }
 
     // Takes the ray-plane intersection formula and generalizes it to support planes that are offset
-    // from the origin
+    // from the origin. Expects the direction to be non-zero, parallel rays are handled by the
+    // caller.
     fn check_axis(origin: f64, direction: f64, min: f64, max: f64) -> (f64, f64) {
-        let tmin_numerator = min - origin;
-        let tmax_numerator = max - origin;
-
-        let tmin: f64;
-        let tmax: f64;
-
-        // If the denominator is effectively 0 we don't want to divide by it. So we multiply by INF
-        // to make sure that tmin and tmax - while both being INF - have the correct sign
-        if direction.abs() >= EPSILON {
-            tmin = tmin_numerator / direction;
-            tmax = tmax_numerator / direction;
-        } else {
-            tmin = tmin_numerator * f64::INFINITY;
-            tmax = tmax_numerator * f64::INFINITY;
-        }
+        let tmin = (min - origin) / direction;
+        let tmax = (max - origin) / direction;
 
         if tmin > tmax {
             (tmax, tmin)
@@ -183,6 +195,7 @@ mod tests {
     use crate::tuples::bounding_box::BoundingBox;
     use crate::tuples::ray::Ray;
     use crate::tuples::tuple::Tuple;
+    use crate::EPSILON;
     use std::f64::consts::PI;
 
     #[test]
@@ -465,6 +478,98 @@ mod tests {
         }
     }
 
+    #[test]
+    fn given_an_axis_aligned_ray_grazing_a_box_face_when_intersecting_should_count_as_a_hit() {
+        // Arrange
+        let bounding_box =
+            BoundingBox::new(Tuple::point(-1.0, -1.0, -1.0), Tuple::point(1.0, 1.0, 1.0));
+
+        let grazing = Ray::new(Tuple::point(1.0, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+        let outside = Ray::new(Tuple::point(1.001, 0.5, -5.0), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Act
+        let grazing_result = bounding_box.intersects(&grazing);
+        let outside_result = bounding_box.intersects(&outside);
+
+        // Assert
+        assert!(grazing_result);
+        assert!(!outside_result);
+    }
+
+    // The slab test as it was before axis aligned rays were given a fast path
+    fn reference_intersects(bounding_box: &BoundingBox, ray: &Ray) -> bool {
+        let check_axis = |origin: f64, direction: f64, min: f64, max: f64| {
+            let (tmin, tmax) = if direction.abs() >= EPSILON {
+                ((min - origin) / direction, (max - origin) / direction)
+            } else {
+                (
+                    (min - origin) * f64::INFINITY,
+                    (max - origin) * f64::INFINITY,
+                )
+            };
+
+            if tmin > tmax {
+                (tmax, tmin)
+            } else {
+                (tmin, tmax)
+            }
+        };
+
+        let (min, max) = (bounding_box.min(), bounding_box.max());
+        let (origin, direction) = (ray.origin(), ray.direction());
+
+        let (xtmin, xtmax) = check_axis(origin.x, direction.x, min.x, max.x);
+        let (ytmin, ytmax) = check_axis(origin.y, direction.y, min.y, max.y);
+        let (ztmin, ztmax) = check_axis(origin.z, direction.z, min.z, max.z);
+
+        let tmin = f64::max(f64::max(xtmin, ytmin), ztmin);
+        let tmax = f64::min(f64::min(xtmax, ytmax), ztmax);
+
+        tmin <= tmax
+    }
+
+    #[test]
+    fn given_many_rays_when_intersecting_with_the_fast_path_should_match_the_original_slab_test() {
+        // Arrange
+        let bounding_box =
+            BoundingBox::new(Tuple::point(-1.0, -2.0, 0.0), Tuple::point(3.0, 1.0, 2.0));
+
+        let coordinates = [-4.0, -2.0, -1.0, 0.0, 0.5, 1.0, 2.0, 3.0, 5.0];
+        let components = [-1.0, -0.3, 0.0, 0.4, 1.0];
+
+        let mut rays = vec![];
+        for x in coordinates {
+            for y in coordinates {
+                for z in coordinates {
+                    for dx in components {
+                        for dy in components {
+                            for dz in components {
+                                if dx == 0.0 && dy == 0.0 && dz == 0.0 {
+                                    continue;
+                                }
+
+                                rays.push(Ray::new(
+                                    Tuple::point(x, y, z),
+                                    Tuple::vector(dx, dy, dz).normalize(),
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Act
+        let results: Vec<bool> = rays.iter().map(|r| bounding_box.intersects(r)).collect();
+        let expected: Vec<bool> = rays
+            .iter()
+            .map(|r| reference_intersects(&bounding_box, r))
+            .collect();
+
+        // Assert
+        assert_eq!(expected, results);
+    }
+
     #[test]
     fn given_a_perfect_cube_when_splitting_it_should_return_correct_bounds() {
         // Arrange
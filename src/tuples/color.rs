@@ -2,6 +2,14 @@ use std::ops::{Add, Div, Mul, Sub};
 
 use crate::EPSILON;
 
+// Tags which encoding a set of color values are stored in so that gamma correction is only
+// ever applied once. Lighting math is done in linear space while images on disk are sRGB.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Color {
     pub red: f64,
@@ -33,6 +41,65 @@ impl Color {
     pub fn black() -> Color {
         Color::new(0.0, 0.0, 0.0)
     }
+
+    // Takes the larger of each pair of channels
+    pub fn max(self, other: Color) -> Color {
+        Color::new(
+            self.red.max(other.red),
+            self.green.max(other.green),
+            self.blue.max(other.blue),
+        )
+    }
+
+    // Subtracting colors (e.g. when interpolating between them) can leave channels below zero,
+    // which don't mean anything physically and throw off anything that sums or scales them later
+    pub fn clamp_non_negative(self) -> Color {
+        self.max(Color::black())
+    }
+
+    // Perceived brightness of a linear color, using the Rec. 709 weights since the eye is far more
+    // sensitive to green than it is to blue
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+
+    // Decodes an sRGB encoded color (e.g. from an image file) into linear space
+    pub fn srgb_to_linear(self) -> Color {
+        Color::new(
+            Color::srgb_channel_to_linear(self.red),
+            Color::srgb_channel_to_linear(self.green),
+            Color::srgb_channel_to_linear(self.blue),
+        )
+    }
+
+    // Encodes a linear color (e.g. from the lighting calculations) into sRGB space for display
+    pub fn linear_to_srgb(self) -> Color {
+        Color::new(
+            Color::linear_channel_to_srgb(self.red),
+            Color::linear_channel_to_srgb(self.green),
+            Color::linear_channel_to_srgb(self.blue),
+        )
+    }
+
+    /*
+        The sRGB transfer function is piecewise, it has a short linear segment near black to avoid
+        an infinite slope at zero, then follows a power curve with an exponent of 2.4 for the rest
+    */
+    fn srgb_channel_to_linear(value: f64) -> f64 {
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn linear_channel_to_srgb(value: f64) -> f64 {
+        if value <= 0.0031308 {
+            value * 12.92
+        } else {
+            1.055 * value.powf(1.0 / 2.4) - 0.055
+        }
+    }
 }
 
 impl PartialEq for Color {
@@ -177,4 +244,61 @@ mod tests {
 
         assert_eq!(expected, result);
     }
+
+    #[test]
+    fn given_an_srgb_color_when_converting_to_linear_and_back_should_return_the_original_color() {
+        let colors = [
+            Color::black(),
+            Color::white(),
+            Color::new(0.02, 0.5, 0.9),
+            Color::new(0.04045, 0.0031308, 0.735),
+        ];
+
+        for color in colors {
+            let result = color.srgb_to_linear().linear_to_srgb();
+
+            assert_eq!(color, result);
+        }
+    }
+
+    #[test]
+    fn given_mid_gray_when_converting_between_color_spaces_should_match_the_srgb_curve() {
+        let linear_mid_gray = Color::new(0.5, 0.5, 0.5);
+        let srgb_mid_gray = Color::new(0.5, 0.5, 0.5);
+
+        let encoded = linear_mid_gray.linear_to_srgb();
+        let decoded = srgb_mid_gray.srgb_to_linear();
+
+        assert!((encoded.red - 0.735357).abs() < 0.000001);
+        assert!((decoded.red - 0.214041).abs() < 0.000001);
+        assert_eq!(encoded.red, encoded.green);
+        assert_eq!(decoded.red, decoded.blue);
+    }
+
+    #[test]
+    fn given_primary_colors_when_calculating_luminance_should_weight_each_channel() {
+        assert_eq!(1.0, Color::white().luminance());
+        assert_eq!(0.2126, Color::red().luminance());
+        assert_eq!(0.7152, Color::green().luminance());
+        assert_eq!(0.0722, Color::blue().luminance());
+    }
+
+    #[test]
+    fn given_white_and_black_when_subtracting_them_should_clamp_any_negative_channels_to_zero() {
+        let white_minus_black = Color::white() - Color::black();
+        let black_minus_white = Color::black() - Color::white();
+
+        assert_eq!(Color::white(), white_minus_black);
+        assert_eq!(Color::new(-1.0, -1.0, -1.0), black_minus_white);
+        assert_eq!(Color::black(), black_minus_white.clamp_non_negative());
+    }
+
+    #[test]
+    fn given_a_partly_negative_color_when_clamping_should_only_change_the_negative_channels() {
+        let color = Color::new(0.2, 0.5, 0.1) - Color::new(0.4, 0.1, 0.1);
+
+        let result = color.clamp_non_negative();
+
+        assert_eq!(Color::new(0.0, 0.4, 0.0), result);
+    }
 }
@@ -8,8 +8,9 @@ use crate::EPSILON;
 pub struct Intersection {
     time: f64,
     object: Arc<dyn Shape>,
-    // Properties specific to a SmoothTriangle, used to help identify where on the triangle the
-    // intersection occurred, relative to the triangle's corners.
+    // The barycentric coordinates of the hit on a triangle (smooth or flat), i.e. where on it the
+    // intersection occurred relative to its corners. Used to interpolate the normals of a smooth
+    // triangle and the texture coordinates of any triangle that has them, zero for other shapes.
     u: f64,
     v: f64,
 }
@@ -57,9 +58,20 @@ impl Intersection {
     }
 }
 
+/*
+    Intersections are ordered by time using f64::total_cmp, so that negative times come before
+    positive ones and sorting never panics, even if a NaN slips through (they sort to the very end,
+    or the very start if negative). Intersections at exactly the same time are then ordered by the
+    id of their objects, so that coincident hits come out in the same order no matter which order
+    they were found in. This is the ordering that Group, CSG and World all rely on when sorting.
+
+    Note: equality is looser than this ordering, it treats times within EPSILON as the same.
+*/
 impl Ord for Intersection {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.time.total_cmp(&other.time)
+        self.time
+            .total_cmp(&other.time)
+            .then_with(|| self.object.id().cmp(&other.object.id()))
     }
 }
 
@@ -83,7 +95,14 @@ impl PartialEq<Self> for Intersection {
 
 #[cfg(test)]
 mod tests {
+    use crate::geometry::csg::{Operation, CSG};
+    use crate::geometry::group::Group;
     use crate::geometry::sphere::Sphere;
+    use crate::materials::phong::Phong;
+    use crate::matrices::matrix::Matrix;
+    use crate::scene::world::World;
+    use crate::tuples::ray::Ray;
+    use crate::tuples::tuple::Tuple;
 
     use super::*;
 
@@ -137,4 +156,75 @@ mod tests {
 
         assert!(hit.is_none());
     }
+
+    fn sphere_at(z: f64) -> Arc<dyn Shape> {
+        Arc::new(Sphere::new(
+            Arc::new(Matrix::translation(0.0, 0.0, z)),
+            Arc::new(Phong::default()),
+            true,
+        ))
+    }
+
+    #[test]
+    fn given_intersections_at_the_same_time_when_sorting_should_order_them_by_object_id() {
+        // Arrange
+        let first = sphere_at(0.0);
+        let second = sphere_at(0.0);
+
+        let mut forwards = [
+            Intersection::new(1.0, first.clone()),
+            Intersection::new(1.0, second.clone()),
+        ];
+        let mut backwards = [
+            Intersection::new(1.0, second.clone()),
+            Intersection::new(1.0, first.clone()),
+        ];
+
+        // Act
+        forwards.sort();
+        backwards.sort();
+
+        // Assert
+        assert!(forwards[0].object().id() < forwards[1].object().id());
+        assert!(Arc::ptr_eq(&forwards[0].object(), &backwards[0].object()));
+        assert!(Arc::ptr_eq(&forwards[1].object(), &backwards[1].object()));
+    }
+
+    #[test]
+    fn given_shapes_found_out_of_order_when_intersecting_groups_csgs_and_worlds_should_sort_consistently(
+    ) {
+        // Arrange
+        let ray = Ray::new(Tuple::point(0.0, 0.0, -2.5), Tuple::vector(0.0, 0.0, 1.0));
+
+        // Listed furthest first, so they come back unsorted and with some negative times
+        let group = Arc::new(Group::default());
+        group.add_children(vec![sphere_at(5.0), sphere_at(0.0), sphere_at(-5.0)]);
+
+        let csg = CSG::default(
+            Operation::Union,
+            sphere_at(5.0),
+            CSG::default(Operation::Union, sphere_at(0.0), sphere_at(-5.0)),
+        );
+
+        let world = World::new(
+            vec![sphere_at(5.0), sphere_at(0.0), sphere_at(-5.0)],
+            vec![],
+        );
+
+        let expected = vec![-3.5, -1.5, 1.5, 3.5, 6.5, 8.5];
+
+        // Act
+        let results = vec![
+            group.intersect(&ray),
+            csg.intersect(&ray),
+            world.intersect_world(&ray),
+        ];
+
+        // Assert
+        for result in results {
+            let times: Vec<f64> = result.iter().map(|i| i.time()).collect();
+
+            assert_eq!(expected, times);
+        }
+    }
 }
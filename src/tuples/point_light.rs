@@ -1,9 +1,13 @@
-use super::{color::Color, tuple::Tuple};
+use super::{color::Color, sampling::SeededRng, tuple::Tuple};
 
 #[derive(Debug, Copy, Clone)]
 pub struct PointLight {
     pub position: Tuple,
     pub intensity: Color,
+    // Fill lights can turn this off, so that they brighten surfaces without adding highlights
+    pub specular_enabled: bool,
+    // How far the light wanders from its position between the passes of a soft shadow render
+    pub jitter_radius: f64,
 }
 
 impl PointLight {
@@ -15,6 +19,47 @@ impl PointLight {
         PointLight {
             position,
             intensity,
+            specular_enabled: true,
+            jitter_radius: 0.0,
+        }
+    }
+
+    pub fn with_specular_enabled(mut self, specular_enabled: bool) -> PointLight {
+        self.specular_enabled = specular_enabled;
+        self
+    }
+
+    // A cheap stand in for an area light. Averaging renders with the light moved to a different
+    // random point within the radius each time (see render_soft_shadows) softens the edges of its
+    // shadows, without any change to how each render is shaded.
+    pub fn with_jitter_radius(mut self, jitter_radius: f64) -> PointLight {
+        assert!(jitter_radius >= 0.0);
+
+        self.jitter_radius = jitter_radius;
+        self
+    }
+
+    // A copy of the light moved to a random point within its jitter radius, picked uniformly from
+    // the ball around its position
+    pub fn jittered(&self, rng: &mut SeededRng) -> PointLight {
+        if self.jitter_radius == 0.0 {
+            return *self;
+        }
+
+        loop {
+            let offset = Tuple::vector(
+                2.0 * rng.next_f64() - 1.0,
+                2.0 * rng.next_f64() - 1.0,
+                2.0 * rng.next_f64() - 1.0,
+            );
+
+            // Reject any that land outside the unit ball, to keep from bunching up in the corners
+            if offset.magnitude() <= 1.0 {
+                return PointLight {
+                    position: self.position + offset * self.jitter_radius,
+                    ..*self
+                };
+            }
         }
     }
 }
@@ -34,4 +79,24 @@ mod tests {
         assert_eq!(intensity, result.intensity);
         assert_eq!(position, result.position);
     }
+
+    #[test]
+    fn given_a_light_with_a_jitter_radius_when_jittering_should_stay_within_the_radius() {
+        // Arrange
+        let light = PointLight::new(Tuple::point(1.0, 2.0, 3.0), Color::white());
+        let mut rng = SeededRng::new(7);
+
+        // Act
+        let unmoved = light.jittered(&mut rng);
+        let positions: Vec<Tuple> = (0..100)
+            .map(|_| light.with_jitter_radius(0.5).jittered(&mut rng).position)
+            .collect();
+
+        // Assert
+        assert_eq!(light.position, unmoved.position);
+        assert!(positions
+            .iter()
+            .all(|position| (*position - light.position).magnitude() <= 0.5));
+        assert!(positions.iter().any(|position| *position != light.position));
+    }
 }
@@ -0,0 +1,346 @@
+use std::f64::consts::PI;
+
+use crate::tuples::tuple::Tuple;
+
+// Small seeded pseudo random number generator, so that anything stochastic in the renderer (e.g.
+// sampling points on a surface) is reproducible from one run to the next. Uses the SplitMix64
+// algorithm, which is fast and has good enough statistical properties for picking samples.
+#[derive(Debug, Copy, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+
+        z ^ (z >> 31)
+    }
+
+    // Returns a value in the range [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        // Only the top 53 bits fit in the mantissa of an f64, so use those to get an even spread
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Seeds a generator from a position, so that anything sampled at a point in the scene comes out
+    // the same no matter which thread or in which order it gets rendered
+    pub fn from_tuple(tuple: Tuple) -> SeededRng {
+        let mut seed: u64 = 0;
+
+        for value in [tuple.x, tuple.y, tuple.z, tuple.w] {
+            seed = SeededRng::new(seed ^ value.to_bits()).next_u64();
+        }
+
+        SeededRng::new(seed)
+    }
+}
+
+/*
+    Ways of spreading a number of samples over a pixel for antialiasing, as offsets in the range
+    [0, 1) from the pixel's top left corner. A regular grid is the simplest but it lines samples
+    up along the pixel edges, so near horizontal or vertical edges still alias. Rotating the grid
+    staggers them, and the Halton sequence gives well spread out (low discrepancy) points for any
+    number of samples, so it converges faster. Jittering puts each sample at a random point within
+    its grid cell instead, seeded so that the same seed always gives the same points.
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SamplePattern {
+    Grid,
+    RotatedGrid,
+    Halton,
+    Jittered(u64),
+}
+
+impl SamplePattern {
+    pub fn offsets(&self, count: usize) -> Vec<(f64, f64)> {
+        // The grids are as close to square as possible, any samples past the last full row are
+        // just placed on the next one
+        let side = (count as f64).sqrt().ceil() as usize;
+        let grid = |i: usize| {
+            (
+                ((i % side) as f64 + 0.5) / side as f64,
+                ((i / side) as f64 + 0.5) / side as f64,
+            )
+        };
+
+        match self {
+            SamplePattern::Grid => (0..count).map(grid).collect(),
+            SamplePattern::RotatedGrid => {
+                // Rotating by atan(1/side) means no two samples share a row or column of the pixel,
+                // e.g. atan(1/2) for the classic four sample rotated grid
+                let angle = (1.0 / side as f64).atan();
+                let (sin, cos) = angle.sin_cos();
+
+                (0..count)
+                    .map(|i| {
+                        let (x, y) = grid(i);
+                        let (dx, dy) = (x - 0.5, y - 0.5);
+
+                        (
+                            (0.5 + dx * cos - dy * sin).rem_euclid(1.0),
+                            (0.5 + dx * sin + dy * cos).rem_euclid(1.0),
+                        )
+                    })
+                    .collect()
+            }
+            // Skip the first element, since it's zero in every base
+            SamplePattern::Halton => (1..=count).map(|i| (halton(i, 2), halton(i, 3))).collect(),
+            SamplePattern::Jittered(seed) => {
+                let mut rng = SeededRng::new(*seed);
+
+                (0..count)
+                    .map(|i| {
+                        (
+                            ((i % side) as f64 + rng.next_f64()) / side as f64,
+                            ((i / side) as f64 + rng.next_f64()) / side as f64,
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+// The index'th element of the Halton sequence in the given base, i.e. the digits of index in that
+// base mirrored around the decimal point (so 6 = 110 in base 2 becomes 0.011 = 0.375)
+pub fn halton(index: usize, base: usize) -> f64 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    let mut remaining = index;
+
+    while remaining > 0 {
+        fraction /= base as f64;
+        result += fraction * (remaining % base) as f64;
+        remaining /= base;
+    }
+
+    result
+}
+
+// Picks a random direction uniformly within a cone around the given axis, where half_angle is the
+// angle in radians between the axis and the edge of the cone
+pub fn sample_cone(axis: Tuple, half_angle: f64, rng: &mut SeededRng) -> Tuple {
+    assert!(axis.is_vector());
+
+    let axis = axis.normalize();
+
+    // Choosing cos(theta) uniformly between cos(half_angle) and 1 spreads the samples evenly over
+    // the cap of the sphere that the cone cuts out
+    let cos_theta = 1.0 - rng.next_f64() * (1.0 - half_angle.cos());
+    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+    let phi = 2.0 * PI * rng.next_f64();
+
+    let (tangent, bitangent) = orthonormal_basis(axis);
+
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta)
+        .normalize()
+}
+
+// Picks a random direction in the hemisphere around the normal, more likely near the normal than
+// at the horizon in proportion to the cosine of the angle between them. That matches how much light
+// a matte surface receives from each direction, so it's the ideal way to sample diffuse bounces.
+pub fn sample_cosine_hemisphere(normal: Tuple, rng: &mut SeededRng) -> Tuple {
+    assert!(normal.is_vector());
+
+    let normal = normal.normalize();
+
+    // Pick a point uniformly on the unit disk and project it up onto the hemisphere
+    let radius = rng.next_f64().sqrt();
+    let phi = 2.0 * PI * rng.next_f64();
+    let height = (1.0 - radius * radius).max(0.0).sqrt();
+
+    let (tangent, bitangent) = orthonormal_basis(normal);
+
+    (tangent * (radius * phi.cos()) + bitangent * (radius * phi.sin()) + normal * height)
+        .normalize()
+}
+
+// Finds two vectors that are perpendicular to the given one and to each other
+pub fn orthonormal_basis(axis: Tuple) -> (Tuple, Tuple) {
+    // Pick whichever world axis is least aligned with the given one to avoid a degenerate cross
+    let helper = if axis.x.abs() > 0.9 {
+        Tuple::vector(0.0, 1.0, 0.0)
+    } else {
+        Tuple::vector(1.0, 0.0, 0.0)
+    };
+
+    let tangent = Tuple::cross(helper, axis).normalize();
+    let bitangent = Tuple::cross(axis, tangent);
+
+    (tangent, bitangent)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tuples::sampling::{
+        halton, sample_cone, sample_cosine_hemisphere, SamplePattern, SeededRng,
+    };
+    use crate::tuples::tuple::Tuple;
+
+    #[test]
+    fn given_two_generators_with_the_same_seed_when_sampling_should_produce_the_same_sequence() {
+        // Arrange
+        let mut first = SeededRng::new(42);
+        let mut second = SeededRng::new(42);
+
+        // Act
+        let first_values: Vec<u64> = (0..100).map(|_| first.next_u64()).collect();
+        let second_values: Vec<u64> = (0..100).map(|_| second.next_u64()).collect();
+
+        // Assert
+        assert_eq!(first_values, second_values);
+    }
+
+    #[test]
+    fn given_a_generator_when_sampling_floats_should_stay_within_the_unit_interval() {
+        // Arrange
+        let mut rng = SeededRng::new(7);
+
+        // Act
+        let values: Vec<f64> = (0..10000).map(|_| rng.next_f64()).collect();
+        let average = values.iter().sum::<f64>() / values.len() as f64;
+
+        // Assert
+        assert!(values.iter().all(|v| (0.0..1.0).contains(v)));
+        assert!((average - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn given_a_cone_when_sampling_directions_should_stay_within_the_half_angle() {
+        // Arrange
+        let axis = Tuple::vector(1.0, 1.0, 0.0).normalize();
+        let half_angle: f64 = 0.2;
+        let mut rng = SeededRng::new(3);
+
+        // Act
+        let samples: Vec<Tuple> = (0..1000)
+            .map(|_| sample_cone(axis, half_angle, &mut rng))
+            .collect();
+
+        // Assert
+        for sample in samples {
+            assert!((sample.magnitude() - 1.0).abs() < 0.00001);
+            assert!(Tuple::dot(sample, axis) >= half_angle.cos() - 0.00001);
+        }
+    }
+
+    #[test]
+    fn given_a_normal_when_sampling_the_cosine_weighted_hemisphere_should_favour_directions_near_it(
+    ) {
+        // Arrange
+        let normal = Tuple::vector(0.0, 0.0, 1.0);
+        let mut rng = SeededRng::new(11);
+
+        // Act
+        let cosines: Vec<f64> = (0..10000)
+            .map(|_| Tuple::dot(sample_cosine_hemisphere(normal, &mut rng), normal))
+            .collect();
+
+        // Assert
+        assert!(cosines.iter().all(|cosine| *cosine >= 0.0));
+
+        // The average cosine of a cosine weighted distribution is 2/3, vs 1/2 if it were uniform
+        let average = cosines.iter().sum::<f64>() / cosines.len() as f64;
+
+        assert!((average - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn given_the_same_point_when_seeding_generators_should_produce_the_same_sequence() {
+        // Arrange
+        let point = Tuple::point(0.5, -2.0, 3.25);
+
+        // Act
+        let first = SeededRng::from_tuple(point).next_u64();
+        let second = SeededRng::from_tuple(point).next_u64();
+        let other = SeededRng::from_tuple(Tuple::point(0.5, -2.0, 3.0)).next_u64();
+
+        // Assert
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn given_a_base_when_generating_the_halton_sequence_should_mirror_the_digits_of_the_index() {
+        // Arrange
+        let indices = [1, 2, 3, 4, 5, 6];
+
+        // Act
+        let base_two: Vec<f64> = indices.iter().map(|i| halton(*i, 2)).collect();
+        let base_three: Vec<f64> = indices.iter().map(|i| halton(*i, 3)).collect();
+
+        // Assert
+        assert_eq!(vec![0.5, 0.25, 0.75, 0.125, 0.625, 0.375], base_two);
+        assert!((base_three[3] - 4.0 / 9.0).abs() < 0.00001);
+    }
+
+    #[test]
+    fn given_sixteen_samples_when_using_halton_should_spread_them_out_more_than_a_regular_grid() {
+        // Arrange
+        let count = 16;
+
+        // Counts how many different columns of the pixel the samples fall in
+        let distinct_columns = |offsets: &[(f64, f64)]| {
+            let mut columns: Vec<f64> = offsets.iter().map(|(x, _)| *x).collect();
+            columns.sort_by(f64::total_cmp);
+            columns.dedup_by(|a, b| (*a - *b).abs() < 0.00001);
+            columns.len()
+        };
+
+        // Act
+        let grid = SamplePattern::Grid.offsets(count);
+        let rotated = SamplePattern::RotatedGrid.offsets(count);
+        let halton = SamplePattern::Halton.offsets(count);
+
+        // Assert
+        for offsets in [&grid, &rotated, &halton] {
+            assert_eq!(count, offsets.len());
+            assert!(offsets
+                .iter()
+                .all(|(x, y)| (0.0..1.0).contains(x) && (0.0..1.0).contains(y)));
+        }
+
+        for (i, (x1, y1)) in halton.iter().enumerate() {
+            for (x2, y2) in halton.iter().skip(i + 1) {
+                assert!(((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt() > 0.05);
+            }
+        }
+
+        assert_eq!(4, distinct_columns(&grid));
+        assert_eq!(count, distinct_columns(&rotated));
+        assert_eq!(count, distinct_columns(&halton));
+    }
+
+    #[test]
+    fn given_a_seed_when_jittering_samples_should_put_one_in_each_grid_cell_reproducibly() {
+        // Arrange
+        let count = 9;
+
+        // Act
+        let jittered = SamplePattern::Jittered(7).offsets(count);
+        let repeated = SamplePattern::Jittered(7).offsets(count);
+        let reseeded = SamplePattern::Jittered(8).offsets(count);
+
+        // Assert
+        assert_eq!(count, jittered.len());
+
+        for (i, (x, y)) in jittered.iter().enumerate() {
+            let (column, row) = ((i % 3) as f64, (i / 3) as f64);
+
+            assert!(*x >= column / 3.0 && *x < (column + 1.0) / 3.0);
+            assert!(*y >= row / 3.0 && *y < (row + 1.0) / 3.0);
+        }
+
+        assert_eq!(jittered, repeated);
+        assert_ne!(jittered, reseeded);
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::Arc;
+
+use crate::geometry::cone::Cone;
+use crate::materials::material::Material;
+use crate::matrices::matrix::Matrix;
+use crate::patterns::pattern::Pattern;
+use crate::tuples::color::Color;
+use crate::tuples::point_light::PointLight;
+use crate::tuples::sampling::orthonormal_basis;
+use crate::tuples::tuple::Tuple;
+
+/*
+    A point light that only shines within a cone around the direction it's pointing in. It can
+    also be given a gobo, a pattern that the light is projected through (like the slats of window
+    blinds), which tints the light by the pattern's color wherever it lands.
+
+    The gobo is sampled in the light's own 2D projection plane, where (u, v) runs from -1 to 1
+    across the cone's circular cross section. Points are handed to the pattern as (u, v, 0), so
+    the gobo's transform can be used to scale or move it around within the cone.
+*/
+#[derive(Debug)]
+pub struct SpotLight {
+    position: Tuple,
+    direction: Tuple,
+    intensity: Color,
+    half_angle: f64,
+    gobo: Option<Box<dyn Pattern>>,
+}
+
+impl SpotLight {
+    // The half angle is in radians, between the direction and the edge of the cone
+    pub fn new(position: Tuple, direction: Tuple, intensity: Color, half_angle: f64) -> SpotLight {
+        assert!(position.is_point());
+        assert!(direction.is_vector());
+        assert!(half_angle > 0.0 && half_angle < std::f64::consts::FRAC_PI_2);
+
+        SpotLight {
+            position,
+            direction: direction.normalize(),
+            intensity,
+            half_angle,
+            gobo: None,
+        }
+    }
+
+    pub fn with_gobo(mut self, gobo: Box<dyn Pattern>) -> SpotLight {
+        self.gobo = Some(gobo);
+        self
+    }
+
+    pub fn position(&self) -> Tuple {
+        self.position
+    }
+
+    pub fn direction(&self) -> Tuple {
+        self.direction
+    }
+
+    pub fn intensity(&self) -> Color {
+        self.intensity
+    }
+
+    pub fn half_angle(&self) -> f64 {
+        self.half_angle
+    }
+
+    pub fn gobo(&self) -> Option<&dyn Pattern> {
+        self.gobo.as_deref()
+    }
+
+    // Projects the point onto the light's projection plane, returning None if it's behind the
+    // light. The result is only within the cone if it's inside the unit circle.
+    pub fn light_space_uv(&self, world_point: Tuple) -> Option<(f64, f64)> {
+        assert!(world_point.is_point());
+
+        let to_point = world_point - self.position;
+        let depth = Tuple::dot(to_point, self.direction);
+
+        if depth <= 0.0 {
+            return None;
+        }
+
+        let (tangent, bitangent) = orthonormal_basis(self.direction);
+        let radius = depth * self.half_angle.tan();
+
+        Some((
+            Tuple::dot(to_point, tangent) / radius,
+            Tuple::dot(to_point, bitangent) / radius,
+        ))
+    }
+
+    // The light arriving at the point, as a point light that can be shaded and shadowed like any
+    // other. Returns None if the point is outside of the cone.
+    pub fn light_at(&self, world_point: Tuple) -> Option<PointLight> {
+        let (u, v) = self.light_space_uv(world_point)?;
+
+        if u * u + v * v > 1.0 {
+            return None;
+        }
+
+        let intensity = match &self.gobo {
+            Some(gobo) => self.intensity * gobo.local_pattern_at(Tuple::point(u, v, 0.0)),
+            None => self.intensity,
+        };
+
+        Some(PointLight::new(self.position, intensity))
+    }
+
+    // Builds an open cone outlining the light's beam out to the given distance, e.g. to see where
+    // a spotlight is aiming while setting up a scene. It doesn't cast shadows, otherwise it would
+    // block the light it surrounds.
+    pub fn cone(&self, length: f64, material: Arc<dyn Material>) -> Arc<Cone> {
+        assert!(length > 0.0);
+
+        let (tangent, bitangent) = orthonormal_basis(self.direction);
+        let radius = length * self.half_angle.tan();
+
+        // The cone opens up along +y from its tip, so turn that onto the light's direction
+        let orientation = Matrix::from_columns(&[
+            vec![bitangent.x, bitangent.y, bitangent.z, 0.0],
+            vec![self.direction.x, self.direction.y, self.direction.z, 0.0],
+            vec![tangent.x, tangent.y, tangent.z, 0.0],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ])
+        .unwrap();
+
+        let translation = Matrix::translation(self.position.x, self.position.y, self.position.z);
+        let placement = (&translation * &orientation).unwrap();
+        let transform = (&placement * &Matrix::scaling(radius, length, radius)).unwrap();
+
+        Arc::new(Cone::new(
+            Arc::new(transform),
+            material,
+            false,
+            0.0,
+            1.0,
+            false,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::shape::Shape;
+    use crate::materials::phong::Phong;
+    use crate::tuples::color::Color;
+    use crate::tuples::ray::Ray;
+    use crate::tuples::spot_light::SpotLight;
+    use crate::tuples::tuple::Tuple;
+    use std::f64::consts::FRAC_PI_4;
+    use std::sync::Arc;
+
+    #[test]
+    fn given_a_spotlight_when_finding_the_light_at_points_should_only_light_those_inside_the_cone()
+    {
+        // Arrange
+        let light = SpotLight::new(
+            Tuple::point(0.0, 10.0, 0.0),
+            Tuple::vector(0.0, -1.0, 0.0),
+            Color::white(),
+            FRAC_PI_4,
+        );
+
+        // Act
+        let centre = light.light_at(Tuple::point(0.0, 0.0, 0.0));
+        let edge = light.light_at(Tuple::point(9.9, 0.0, 0.0));
+        let outside = light.light_at(Tuple::point(10.1, 0.0, 0.0));
+        let behind = light.light_at(Tuple::point(0.0, 20.0, 0.0));
+
+        // Assert
+        assert_eq!(Color::white(), centre.unwrap().intensity);
+        assert_eq!(Tuple::point(0.0, 10.0, 0.0), centre.unwrap().position);
+        assert!(edge.is_some());
+        assert!(outside.is_none());
+        assert!(behind.is_none());
+    }
+
+    #[test]
+    fn given_a_spotlight_when_building_its_cone_should_outline_the_beam() {
+        // Arrange
+        let light = SpotLight::new(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::vector(0.0, 0.0, 1.0),
+            Color::white(),
+            FRAC_PI_4,
+        );
+
+        let ray = Ray::new(Tuple::point(-10.0, 0.0, 0.0), Tuple::vector(1.0, 0.0, 0.0));
+
+        // Act
+        let cone = light.cone(10.0, Arc::new(Phong::default()));
+        let hits = cone.clone().intersect(&ray);
+
+        // Assert
+        assert!(!cone.casts_shadow());
+        assert_eq!(2, hits.len());
+        assert!((hits[0].time() - 5.0).abs() < 0.0001);
+        assert!((hits[1].time() - 15.0).abs() < 0.0001);
+    }
+}
@@ -46,6 +46,10 @@ impl Tuple {
         (1.0 - self.w).abs() < EPSILON
     }
 
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite() && self.w.is_finite()
+    }
+
     pub fn magnitude(&self) -> f64 {
         assert!(self.is_vector());
 
@@ -97,6 +101,31 @@ impl Tuple {
 
         incoming - (normal * 2.0 * Tuple::dot(incoming, normal))
     }
+
+    // The smaller of each component of the two, e.g. for growing a bounding box
+    pub fn component_min(lhs: Tuple, rhs: Tuple) -> Tuple {
+        Tuple::new(
+            lhs.x.min(rhs.x),
+            lhs.y.min(rhs.y),
+            lhs.z.min(rhs.z),
+            lhs.w.min(rhs.w),
+        )
+    }
+
+    // The larger of each component of the two
+    pub fn component_max(lhs: Tuple, rhs: Tuple) -> Tuple {
+        Tuple::new(
+            lhs.x.max(rhs.x),
+            lhs.y.max(rhs.y),
+            lhs.z.max(rhs.z),
+            lhs.w.max(rhs.w),
+        )
+    }
+
+    // Takes the absolute value of x, y and z, leaving w alone so points stay points
+    pub fn abs(self) -> Tuple {
+        Tuple::new(self.x.abs(), self.y.abs(), self.z.abs(), self.w)
+    }
 }
 
 impl PartialEq for Tuple {
@@ -415,4 +444,34 @@ mod tests {
 
         assert_eq!(expected, result);
     }
+
+    #[test]
+    fn given_two_tuples_when_taking_the_component_min_and_max_should_pick_per_component() {
+        // Arrange
+        let a = Tuple::point(1.0, 5.0, 3.0);
+        let b = Tuple::point(4.0, 2.0, 6.0);
+
+        // Act
+        let min = Tuple::component_min(a, b);
+        let max = Tuple::component_max(a, b);
+
+        // Assert
+        assert_eq!(Tuple::point(1.0, 2.0, 3.0), min);
+        assert_eq!(Tuple::point(4.0, 5.0, 6.0), max);
+    }
+
+    #[test]
+    fn given_a_tuple_with_negative_components_when_taking_the_abs_should_preserve_w() {
+        // Arrange
+        let point = Tuple::point(-1.0, 2.0, -3.0);
+        let vector = Tuple::vector(-1.0, 2.0, -3.0);
+
+        // Act
+        let point_result = point.abs();
+        let vector_result = vector.abs();
+
+        // Assert
+        assert_eq!(Tuple::point(1.0, 2.0, 3.0), point_result);
+        assert_eq!(Tuple::vector(1.0, 2.0, 3.0), vector_result);
+    }
 }
@@ -1,44 +1,386 @@
-use std::{error::Error, fmt::Write, fs};
+use std::path::Path;
+use std::{fmt::Write, fs};
 
 use array2d::Array2D;
 
-use crate::tuples::color::Color;
+use crate::error::RaytracerError;
+use crate::tuples::color::{Color, ColorSpace};
 
 pub struct Canvas {
     grid: Array2D<Color>,
+    color_space: ColorSpace,
+    warn_on_invalid_pixels: bool,
 }
 
 impl Canvas {
     pub fn new(width: usize, height: usize) -> Canvas {
-        Canvas::filled_with(Color::new(0.0, 0.0, 0.0), width, height)
+        Canvas::new_filled(width, height, Color::new(0.0, 0.0, 0.0))
     }
 
-    pub fn from_columns(columns: &[Vec<Color>]) -> Result<Canvas, array2d::Error> {
+    pub fn from_columns(columns: &[Vec<Color>]) -> Result<Canvas, RaytracerError> {
         let grid = Array2D::from_columns(columns)?;
 
-        Ok(Canvas { grid })
+        Ok(Canvas {
+            grid,
+            color_space: ColorSpace::Srgb,
+            warn_on_invalid_pixels: false,
+        })
     }
 
-    pub fn from_rows(rows: &[Vec<Color>]) -> Result<Canvas, array2d::Error> {
+    pub fn from_rows(rows: &[Vec<Color>]) -> Result<Canvas, RaytracerError> {
         let grid = Array2D::from_rows(rows)?;
 
-        Ok(Canvas { grid })
+        Ok(Canvas {
+            grid,
+            color_space: ColorSpace::Srgb,
+            warn_on_invalid_pixels: false,
+        })
     }
 
-    pub fn filled_with(color: Color, width: usize, height: usize) -> Canvas {
+    // Takes over the pixels, given row by row from the top left, without copying them
+    pub fn from_row_major(
+        width: usize,
+        height: usize,
+        pixels: Vec<Color>,
+    ) -> Result<Canvas, RaytracerError> {
+        if pixels.len() != width * height {
+            return Err(RaytracerError::Invalid(
+                "canvas must be given exactly one color per pixel".to_string(),
+            ));
+        }
+
+        let grid = Array2D::from_iter_row_major(pixels.into_iter(), height, width)?;
+
+        Ok(Canvas {
+            grid,
+            color_space: ColorSpace::Srgb,
+            warn_on_invalid_pixels: false,
+        })
+    }
+
+    /*
+        Reads back a plain (P3) PPM image, e.g. a reference render to compare against. Channel
+        values are scaled down by the maximum value given in the header, and the canvas is tagged
+        as sRGB since that's how to_ppm writes them out. Comments (from a # to the end of the line)
+        are skipped.
+    */
+    pub fn from_ppm(contents: &str) -> Result<Canvas, RaytracerError> {
+        let mut tokens = contents
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| line.split_whitespace());
+
+        if tokens.next() != Some("P3") {
+            return Err(RaytracerError::Parse(
+                "Only plain PPM files (P3) are supported".to_string(),
+            ));
+        }
+
+        let mut next_number = || -> Result<usize, RaytracerError> {
+            let token = tokens
+                .next()
+                .ok_or_else(|| RaytracerError::Parse("PPM file ended early".to_string()))?;
+
+            token
+                .parse::<usize>()
+                .map_err(|_| RaytracerError::Parse(format!("Invalid value in PPM file: {}", token)))
+        };
+
+        let width = next_number()?;
+        let height = next_number()?;
+        let max_value = next_number()? as f64;
+
+        let mut canvas = Canvas::new(width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let red = next_number()? as f64 / max_value;
+                let green = next_number()? as f64 / max_value;
+                let blue = next_number()? as f64 / max_value;
+
+                canvas.write_pixel(x, y, Color::new(red, green, blue))?;
+            }
+        }
+
+        Ok(canvas)
+    }
+
+    pub fn new_filled(width: usize, height: usize, color: Color) -> Canvas {
         Canvas {
             grid: Array2D::filled_with(color, height, width),
+            color_space: ColorSpace::Srgb,
+            warn_on_invalid_pixels: false,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.grid.num_columns()
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.num_rows()
+    }
+
+    // Canvases default to sRGB, i.e. the pixel values are written out exactly as they are. Tag a
+    // canvas as linear when it holds the raw output of the lighting calculations, so that it gets
+    // gamma encoded exactly once when written out.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+    }
+
+    // When set, writing the canvas out to a file reports any pixels that hold NaN or infinite
+    // values (see find_invalid_pixels), since they'd otherwise be silently clamped
+    pub fn set_warn_on_invalid_pixels(&mut self, warn_on_invalid_pixels: bool) {
+        self.warn_on_invalid_pixels = warn_on_invalid_pixels;
+    }
+
+    // Lists the coordinates of any pixels with a NaN or infinite channel. These come from numerical
+    // bugs in the shading (e.g. normalizing a zero length vector), so it's a useful check when
+    // developing, since the PPM writer would just clamp them to black or white.
+    pub fn find_invalid_pixels(&self) -> Vec<(usize, usize)> {
+        let mut result = Vec::new();
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let color = self.pixel_at(x, y).unwrap();
+
+                if !(color.red.is_finite() && color.green.is_finite() && color.blue.is_finite()) {
+                    result.push((x, y));
+                }
+            }
         }
+
+        result
     }
 
-    pub fn write_pixel(&mut self, x: usize, y: usize, color: Color) -> Result<(), array2d::Error> {
-        self.grid.set(y, x, color)
+    pub fn write_pixel(&mut self, x: usize, y: usize, color: Color) -> Result<(), RaytracerError> {
+        Ok(self.grid.set(y, x, color)?)
     }
 
     pub fn pixel_at(&self, x: usize, y: usize) -> Option<&Color> {
         self.grid.get(y, x)
     }
 
+    // Blends every pixel toward a gray of the same luminance, where an amount of 0.0 leaves the
+    // canvas as it is and 1.0 makes it completely grayscale
+    pub fn desaturate(&mut self, amount: f64) {
+        self.map(|color| {
+            let luminance = color.luminance();
+            let gray = Color::new(luminance, luminance, luminance);
+
+            color + (gray - color) * amount
+        });
+    }
+
+    // Counts how many pixels fall into each of the given number of equal bands of luminance between
+    // 0.0 and 1.0, e.g. to pick an exposure for the image. Anything brighter than 1.0 is counted in
+    // the top band, and anything darker than 0.0 in the bottom one.
+    pub fn luminance_histogram(&self, bins: usize) -> Vec<usize> {
+        assert!(bins > 0);
+
+        let mut histogram = vec![0; bins];
+
+        for color in self.grid.elements_row_major_iter() {
+            let bin = (color.luminance() * bins as f64).clamp(0.0, (bins - 1) as f64) as usize;
+
+            histogram[bin] += 1;
+        }
+
+        histogram
+    }
+
+    pub fn average_luminance(&self) -> f64 {
+        let total: f64 = self
+            .grid
+            .elements_row_major_iter()
+            .map(|color| color.luminance())
+            .sum();
+
+        total / (self.width() * self.height()) as f64
+    }
+
+    // Brightens or darkens the whole image by the given number of stops, where each stop doubles
+    // (or halves, if negative) the amount of light. Should be applied to the linear values before
+    // any tone mapping or gamma encoding.
+    pub fn apply_exposure(&mut self, stops: f64) {
+        let scale = 2.0_f64.powf(stops);
+
+        self.map(|color| color * scale);
+    }
+
+    /*
+        Compresses high dynamic range values into the 0.0 to 1.0 range that can be displayed using
+        the filmic curve from the Academy Color Encoding System (Krzysztof Narkowicz's fit to it).
+        Compared to Reinhard it keeps more contrast in the mid-tones and rolls off the highlights
+        more gently. Each channel is scaled by the exposure before being mapped.
+    */
+    pub fn tone_map_aces(&mut self, exposure: f64) {
+        let curve = |value: f64| {
+            let x = value * exposure;
+
+            ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+        };
+
+        self.map(|color| Color::new(curve(color.red), curve(color.green), curve(color.blue)));
+    }
+
+    // The simplest tone mapping operator, x / (1 + x), which maps 0.0 to infinity onto 0.0 to 1.0
+    pub fn tone_map_reinhard(&mut self, exposure: f64) {
+        let curve = |value: f64| {
+            let x = (value * exposure).max(0.0);
+
+            x / (1.0 + x)
+        };
+
+        self.map(|color| Color::new(curve(color.red), curve(color.green), curve(color.blue)));
+    }
+
+    // Applies the given function to every pixel in the canvas
+    fn map<F: Fn(Color) -> Color>(&mut self, f: F) {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let color = *self.pixel_at(x, y).unwrap();
+
+                self.write_pixel(x, y, f(color)).unwrap();
+            }
+        }
+    }
+
+    /*
+        Compares two canvases of the same size pixel by pixel, e.g. to check a render against a
+        stored reference image. Returns the number of pixels that differ (by more than EPSILON in
+        any channel), the largest difference seen in any one channel, and an image where each pixel
+        is the absolute difference between the two so that any changes stand out against black.
+    */
+    pub fn diff(&self, other: &Canvas) -> Result<(usize, f64, Canvas), RaytracerError> {
+        if self.width() != other.width() || self.height() != other.height() {
+            return Err(RaytracerError::Invalid(
+                "compared canvases must be the same size".to_string(),
+            ));
+        }
+
+        let mut differing_pixels = 0;
+        let mut max_delta: f64 = 0.0;
+        let mut image = Canvas::new(self.width(), self.height());
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let a = self.pixel_at(x, y).unwrap();
+                let b = other.pixel_at(x, y).unwrap();
+
+                let delta = Color::new(
+                    (a.red - b.red).abs(),
+                    (a.green - b.green).abs(),
+                    (a.blue - b.blue).abs(),
+                );
+
+                if *a != *b {
+                    differing_pixels += 1;
+                }
+
+                max_delta = max_delta.max(delta.red).max(delta.green).max(delta.blue);
+
+                image.write_pixel(x, y, delta).unwrap();
+            }
+        }
+
+        Ok((differing_pixels, max_delta, image))
+    }
+
+    // Copies out a width by height region, with its top left corner at (x, y)
+    pub fn crop(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Canvas, RaytracerError> {
+        if x + width > self.width() || y + height > self.height() {
+            return Err(RaytracerError::Invalid(
+                "crop region must fit within the canvas".to_string(),
+            ));
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        canvas.set_color_space(self.color_space);
+
+        for row in 0..height {
+            for column in 0..width {
+                let color = *self.pixel_at(x + column, y + row).unwrap();
+
+                canvas.write_pixel(column, row, color).unwrap();
+            }
+        }
+
+        Ok(canvas)
+    }
+
+    // Overwrites the region of this canvas starting at (x, y) with the source canvas, e.g. to put
+    // separately rendered tiles back together into one image
+    pub fn paste(&mut self, source: &Canvas, x: usize, y: usize) -> Result<(), RaytracerError> {
+        if x + source.width() > self.width() || y + source.height() > self.height() {
+            return Err(RaytracerError::Invalid(
+                "pasted canvas must fit within the canvas".to_string(),
+            ));
+        }
+
+        if source.color_space != self.color_space {
+            return Err(RaytracerError::Invalid(
+                "pasted canvas must be in the same color space".to_string(),
+            ));
+        }
+
+        for row in 0..source.height() {
+            for column in 0..source.width() {
+                let color = *source.pixel_at(column, row).unwrap();
+
+                self.write_pixel(x + column, y + row, color).unwrap();
+            }
+        }
+
+        Ok(())
+    }
+
+    // Combines a stereo pair of renders into a single red/cyan image, where the red channel comes
+    // from the left eye and the green and blue channels from the right eye
+    pub fn anaglyph(left: &Canvas, right: &Canvas) -> Result<Canvas, RaytracerError> {
+        let width = left.width();
+        let height = left.height();
+
+        if width != right.width() || height != right.height() {
+            return Err(RaytracerError::Invalid(
+                "left and right canvases must be the same size".to_string(),
+            ));
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        canvas.set_color_space(left.color_space);
+
+        for y in 0..height {
+            for x in 0..width {
+                let l = left.pixel_at(x, y).unwrap();
+                let r = right.pixel_at(x, y).unwrap();
+
+                canvas
+                    .write_pixel(x, y, Color::new(l.red, r.green, r.blue))
+                    .unwrap();
+            }
+        }
+
+        Ok(canvas)
+    }
+
+    fn encode_color(&self, color: Color) -> Color {
+        match self.color_space {
+            ColorSpace::Linear => color.linear_to_srgb(),
+            ColorSpace::Srgb => color,
+        }
+    }
+
     fn clamp_color(color: Color) -> (u8, u8, u8) {
         (
             Canvas::clamp(color.red),
@@ -59,7 +401,21 @@ impl Canvas {
         };
     }
 
-    pub fn to_ppm(&self) -> Result<String, std::fmt::Error> {
+    // Packs the pixels into a row major buffer of RGBA bytes, e.g. for handing over to a GUI to
+    // display. Pixels are encoded and clamped the same way as when writing a PPM, and are opaque.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.grid.num_elements() * 4);
+
+        for color in self.grid.elements_row_major_iter() {
+            let (red, green, blue) = Canvas::clamp_color(self.encode_color(*color));
+
+            output.extend_from_slice(&[red, green, blue, 255]);
+        }
+
+        output
+    }
+
+    pub fn to_ppm(&self) -> Result<String, RaytracerError> {
         let mut output = String::new();
 
         // Write header information at the start of the file
@@ -75,7 +431,7 @@ impl Canvas {
             let mut current_line_len: usize = 0;
 
             for x in 0..self.grid.num_columns() {
-                let color = *self.pixel_at(x, y).unwrap();
+                let color = self.encode_color(*self.pixel_at(x, y).unwrap());
 
                 let (red, green, blue) = Canvas::clamp_color(color);
 
@@ -125,7 +481,130 @@ impl Canvas {
         Ok(())
     }
 
-    pub fn write_to_file(&self, file_path: String) -> Result<(), Box<dyn Error>> {
+    /*
+        Encodes the canvas as an 8-bit RGB PNG, with the pixels encoded and clamped the same way as
+        when writing a PPM. There's no compression library to hand, so the image data is stored
+        uncompressed, which makes for files around the size of the raw pixels (still a good deal
+        smaller than a PPM).
+    */
+    pub fn to_png(&self) -> Vec<u8> {
+        // Each row starts with the filter type, which is always none
+        let mut raw = Vec::with_capacity(self.height() * (1 + self.width() * 3));
+
+        for y in 0..self.height() {
+            raw.push(0);
+
+            for x in 0..self.width() {
+                let (red, green, blue) =
+                    Canvas::clamp_color(self.encode_color(*self.pixel_at(x, y).unwrap()));
+
+                raw.extend_from_slice(&[red, green, blue]);
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width() as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height() as u32).to_be_bytes());
+        // 8 bits per channel, RGB, then the standard compression, filtering and no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut output = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+        Canvas::write_png_chunk(&mut output, b"IHDR", &header);
+        Canvas::write_png_chunk(&mut output, b"IDAT", &Canvas::zlib_stored(&raw));
+        Canvas::write_png_chunk(&mut output, b"IEND", &[]);
+
+        output
+    }
+
+    pub fn write_png<P: AsRef<Path>>(&self, path: P) -> Result<(), RaytracerError> {
+        fs::write(path, self.to_png())?;
+
+        Ok(())
+    }
+
+    // Each chunk is its length, type and data followed by a checksum of the type and data
+    fn write_png_chunk(output: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+        output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        output.extend_from_slice(chunk_type);
+        output.extend_from_slice(data);
+
+        let crc = Canvas::crc32(chunk_type.iter().chain(data));
+        output.extend_from_slice(&crc.to_be_bytes());
+    }
+
+    // Wraps the data in a zlib stream without compressing it, i.e. as a series of stored deflate
+    // blocks, each of which can hold up to 65535 bytes
+    fn zlib_stored(data: &[u8]) -> Vec<u8> {
+        let mut output = vec![0x78, 0x01];
+
+        let mut blocks = data.chunks(u16::MAX as usize).peekable();
+
+        // Even no data at all still needs one (empty) final block
+        if blocks.peek().is_none() {
+            output.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+        }
+
+        while let Some(block) = blocks.next() {
+            let is_final = blocks.peek().is_none();
+            let length = block.len() as u16;
+
+            output.push(is_final as u8);
+            output.extend_from_slice(&length.to_le_bytes());
+            output.extend_from_slice(&(!length).to_le_bytes());
+            output.extend_from_slice(block);
+        }
+
+        // Adler-32 checksum of the uncompressed data
+        let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+            let a = (a + *byte as u32) % 65521;
+            (a, (b + a) % 65521)
+        });
+        output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+
+        output
+    }
+
+    fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+        let mut crc = 0xffffffffu32;
+
+        for byte in bytes {
+            crc ^= *byte as u32;
+
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xedb88320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+
+        !crc
+    }
+
+    // Writes a PNG if the file name ends in .png, otherwise a PPM
+    pub fn write_to_file(&self, file_path: String) -> Result<(), RaytracerError> {
+        if self.warn_on_invalid_pixels {
+            let invalid_pixels = self.find_invalid_pixels();
+
+            if !invalid_pixels.is_empty() {
+                eprintln!(
+                    "Warning: {} pixels have NaN or infinite values, starting at {:?}",
+                    invalid_pixels.len(),
+                    invalid_pixels[0]
+                );
+            }
+        }
+
+        let is_png = Path::new(&file_path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+
+        if is_png {
+            return self.write_png(file_path);
+        }
+
         let output = self.to_ppm()?;
 
         fs::write(file_path, output)?;
@@ -137,6 +616,7 @@ impl Canvas {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::EPSILON;
 
     #[test]
     fn given_a_typical_size_when_creating_a_blank_canvas_should_expect_every_color_to_be_zeroed() {
@@ -168,6 +648,47 @@ mod tests {
         assert_eq!(red, *canvas.pixel_at(2, 3).unwrap())
     }
 
+    #[test]
+    fn given_a_clear_color_when_creating_a_filled_canvas_should_leave_unwritten_pixels_that_color()
+    {
+        // Arrange
+        let magenta = Color::new(1.0, 0.0, 1.0);
+        let mut canvas = Canvas::new_filled(3, 2, magenta);
+
+        // Act
+        canvas.write_pixel(1, 1, Color::white()).unwrap();
+
+        // Assert
+        for y in 0..2 {
+            for x in 0..3 {
+                let expected = if (x, y) == (1, 1) {
+                    Color::white()
+                } else {
+                    magenta
+                };
+
+                assert_eq!(expected, *canvas.pixel_at(x, y).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn given_pixels_in_row_order_when_creating_a_canvas_from_them_should_fill_each_row_in_turn() {
+        // Arrange
+        let pixels: Vec<Color> = (0..6).map(|i| Color::new(i as f64, 0.0, 0.0)).collect();
+
+        // Act
+        let canvas = Canvas::from_row_major(3, 2, pixels.clone()).unwrap();
+        let too_few = Canvas::from_row_major(4, 2, pixels);
+
+        // Assert
+        assert_eq!(3, canvas.width());
+        assert_eq!(2, canvas.height());
+        assert_eq!(Color::new(2.0, 0.0, 0.0), *canvas.pixel_at(2, 0).unwrap());
+        assert_eq!(Color::new(3.0, 0.0, 0.0), *canvas.pixel_at(0, 1).unwrap());
+        assert!(too_few.is_err());
+    }
+
     #[test]
     fn given_a_canvas_with_a_few_color_pixels_when_converting_to_ppm_should_output_file_correctly()
     {
@@ -201,7 +722,7 @@ mod tests {
     {
         let width = 10;
         let height = 2;
-        let canvas = Canvas::filled_with(Color::new(1.0, 0.8, 0.6), width, height);
+        let canvas = Canvas::new_filled(width, height, Color::new(1.0, 0.8, 0.6));
 
         let expected = "P3
 10 2
@@ -227,4 +748,485 @@ mod tests {
 
         assert_eq!(true, result.ends_with("\n"));
     }
+
+    #[test]
+    fn given_a_red_and_green_canvas_when_converting_to_rgba_should_output_each_pixel_in_row_order()
+    {
+        // Arrange
+        let canvas = Canvas::from_rows(&[vec![Color::red(), Color::green()]]).unwrap();
+
+        // Act
+        let result = canvas.to_rgba8();
+
+        // Assert
+        assert_eq!(vec![255, 0, 0, 255, 0, 255, 0, 255], result);
+    }
+
+    #[test]
+    fn given_a_linear_canvas_when_converting_to_rgba_should_gamma_encode_and_clamp_each_channel() {
+        // Arrange
+        let mut canvas = Canvas::from_rows(&[vec![Color::new(0.5, -0.5, 1.5)]]).unwrap();
+        canvas.set_color_space(ColorSpace::Linear);
+
+        // Act
+        let result = canvas.to_rgba8();
+
+        // Assert
+        assert_eq!(vec![188, 0, 255, 255], result);
+    }
+
+    #[test]
+    fn given_a_ppm_written_from_a_canvas_when_reading_it_back_should_match_the_original_pixels() {
+        // Arrange
+        let mut canvas = Canvas::new(3, 2);
+        canvas.write_pixel(0, 0, Color::new(1.0, 0.2, 0.0)).unwrap();
+        canvas.write_pixel(2, 1, Color::new(0.0, 0.6, 1.0)).unwrap();
+
+        // Act
+        let result = Canvas::from_ppm(&canvas.to_ppm().unwrap()).unwrap();
+
+        // Assert
+        assert_eq!(3, result.width());
+        assert_eq!(2, result.height());
+        assert_eq!(0, canvas.diff(&result).unwrap().0);
+    }
+
+    #[test]
+    fn given_a_ppm_with_comments_and_a_different_maximum_when_reading_it_should_scale_each_value() {
+        // Arrange
+        let ppm = "P3\n# made by hand\n2 1\n100\n100 50 0 # first pixel\n0 0 25\n";
+
+        // Act
+        let result = Canvas::from_ppm(ppm).unwrap();
+
+        // Assert
+        assert_eq!(Color::new(1.0, 0.5, 0.0), *result.pixel_at(0, 0).unwrap());
+        assert_eq!(Color::new(0.0, 0.0, 0.25), *result.pixel_at(1, 0).unwrap());
+    }
+
+    #[test]
+    fn given_a_binary_or_truncated_ppm_when_reading_it_should_return_an_error() {
+        // Arrange
+        let binary = "P6\n1 1\n255\n";
+        let truncated = "P3\n2 1\n255\n255 0 0\n";
+
+        // Act
+        let binary_result = Canvas::from_ppm(binary);
+        let truncated_result = Canvas::from_ppm(truncated);
+
+        // Assert
+        assert!(binary_result.is_err());
+        assert!(truncated_result.is_err());
+    }
+
+    #[test]
+    fn given_a_new_canvas_when_checking_its_color_space_should_default_to_srgb() {
+        // Arrange
+        let canvas = Canvas::new(2, 2);
+
+        // Act
+        let result = canvas.color_space();
+
+        // Assert
+        assert_eq!(ColorSpace::Srgb, result);
+    }
+
+    #[test]
+    fn given_a_linear_canvas_when_converting_to_ppm_should_gamma_encode_each_pixel_once() {
+        // Arrange
+        let mut canvas = Canvas::new_filled(1, 1, Color::new(0.5, 0.0, 1.0));
+        canvas.set_color_space(ColorSpace::Linear);
+
+        let expected = "P3
+1 1
+255
+188 0 255
+";
+
+        // Act
+        let result = canvas.to_ppm().unwrap();
+
+        // Assert
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn given_a_stereo_pair_of_canvases_when_creating_an_anaglyph_should_take_red_from_left_and_cyan_from_right(
+    ) {
+        // Arrange
+        let left = Canvas::new_filled(2, 2, Color::new(0.2, 0.4, 0.6));
+        let right = Canvas::new_filled(2, 2, Color::new(0.8, 0.5, 0.1));
+
+        // Act
+        let result = Canvas::anaglyph(&left, &right).unwrap();
+        let mismatched = Canvas::anaglyph(&left, &Canvas::new(3, 2));
+
+        // Assert
+        assert_eq!(Color::new(0.2, 0.5, 0.1), *result.pixel_at(1, 1).unwrap());
+        assert!(mismatched.is_err());
+    }
+
+    #[test]
+    fn given_a_region_cropped_from_a_gradient_when_pasting_it_back_should_reconstruct_the_original()
+    {
+        // Arrange
+        let mut canvas = Canvas::new(4, 4);
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let color = Color::new(x as f64 / 3.0, y as f64 / 3.0, 0.5);
+                canvas.write_pixel(x, y, color).unwrap();
+            }
+        }
+
+        // Act
+        let region = canvas.crop(1, 2, 2, 2).unwrap();
+
+        let mut reconstructed = Canvas::new(4, 4);
+        reconstructed.paste(&region, 1, 2).unwrap();
+
+        // Assert
+        assert_eq!(2, region.width());
+        assert_eq!(2, region.height());
+        assert_eq!(
+            Color::new(1.0 / 3.0, 2.0 / 3.0, 0.5),
+            *region.pixel_at(0, 0).unwrap()
+        );
+
+        for y in 2..4 {
+            for x in 1..3 {
+                assert_eq!(canvas.pixel_at(x, y), reconstructed.pixel_at(x, y));
+            }
+        }
+
+        assert_eq!(Color::black(), *reconstructed.pixel_at(0, 0).unwrap());
+    }
+
+    #[test]
+    fn given_a_region_past_the_edge_when_cropping_or_pasting_should_return_an_error() {
+        // Arrange
+        let mut canvas = Canvas::new(4, 4);
+        let region = Canvas::new(2, 2);
+
+        // Act
+        let crop_past_right = canvas.crop(3, 0, 2, 2);
+        let crop_past_bottom = canvas.crop(0, 0, 4, 5);
+        let paste_past_corner = canvas.paste(&region, 3, 3);
+        let paste_inside = canvas.paste(&region, 2, 2);
+
+        // Assert
+        assert!(crop_past_right.is_err());
+        assert!(crop_past_bottom.is_err());
+        assert!(paste_past_corner.is_err());
+        assert!(paste_inside.is_ok());
+    }
+
+    #[test]
+    fn given_two_identical_canvases_when_diffing_should_report_no_differences() {
+        // Arrange
+        let a = Canvas::new_filled(4, 3, Color::new(0.2, 0.4, 0.6));
+        let b = Canvas::new_filled(4, 3, Color::new(0.2, 0.4, 0.6));
+
+        // Act
+        let (differing_pixels, max_delta, image) = a.diff(&b).unwrap();
+
+        // Assert
+        assert_eq!(0, differing_pixels);
+        assert_eq!(0.0, max_delta);
+        assert_eq!(4, image.width());
+        assert_eq!(3, image.height());
+        assert_eq!(Color::black(), *image.pixel_at(2, 1).unwrap());
+    }
+
+    #[test]
+    fn given_two_canvases_differing_by_one_pixel_when_diffing_should_detect_it_with_the_max_delta()
+    {
+        // Arrange
+        let a = Canvas::new_filled(4, 3, Color::new(0.2, 0.4, 0.6));
+        let mut b = Canvas::new_filled(4, 3, Color::new(0.2, 0.4, 0.6));
+        b.write_pixel(3, 2, Color::new(0.25, 0.1, 0.6)).unwrap();
+
+        // Act
+        let (differing_pixels, max_delta, image) = a.diff(&b).unwrap();
+
+        // Assert
+        assert_eq!(1, differing_pixels);
+        assert!((max_delta - 0.3).abs() < EPSILON);
+        assert_eq!(Color::new(0.05, 0.3, 0.0), *image.pixel_at(3, 2).unwrap());
+        assert_eq!(Color::black(), *image.pixel_at(0, 0).unwrap());
+    }
+
+    #[test]
+    fn given_two_canvases_of_different_sizes_when_diffing_should_return_an_error() {
+        // Arrange
+        let a = Canvas::new(4, 3);
+        let b = Canvas::new(3, 4);
+
+        // Act
+        let result = a.diff(&b);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn given_a_canvas_when_fully_desaturating_should_set_every_channel_to_the_luminance() {
+        // Arrange
+        let background = Color::new(0.2, 0.4, 0.6);
+        let magenta = Color::new(1.0, 0.0, 0.5);
+
+        let mut canvas = Canvas::new_filled(2, 2, background);
+        canvas.write_pixel(1, 0, magenta).unwrap();
+
+        // Act
+        canvas.desaturate(1.0);
+
+        // Assert
+        for (x, y, original) in [(0, 0, background), (1, 0, magenta), (1, 1, background)] {
+            let luminance = original.luminance();
+
+            assert_eq!(
+                Color::new(luminance, luminance, luminance),
+                *canvas.pixel_at(x, y).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn given_a_canvas_when_desaturating_by_zero_should_leave_it_unchanged() {
+        // Arrange
+        let mut canvas = Canvas::new_filled(2, 2, Color::new(0.2, 0.4, 0.6));
+        let original = Canvas::new_filled(2, 2, Color::new(0.2, 0.4, 0.6));
+
+        // Act
+        canvas.desaturate(0.0);
+
+        // Assert
+        let (differing_pixels, _, _) = canvas.diff(&original).unwrap();
+
+        assert_eq!(0, differing_pixels);
+    }
+
+    #[test]
+    fn given_a_ramp_of_hdr_values_when_tone_mapping_with_aces_should_stay_monotonic_and_bounded() {
+        // Arrange
+        let ramp: Vec<Color> = (0..100)
+            .map(|i| {
+                let value = i as f64 * 0.2;
+                Color::new(value, value, value)
+            })
+            .collect();
+
+        let rows = [ramp];
+
+        let mut aces = Canvas::from_rows(&rows).unwrap();
+        let mut reinhard = Canvas::from_rows(&rows).unwrap();
+
+        // Act
+        aces.tone_map_aces(1.0);
+        reinhard.tone_map_reinhard(1.0);
+
+        // Assert
+        for x in 0..aces.width() {
+            let value = aces.pixel_at(x, 0).unwrap().red;
+
+            assert!((0.0..=1.0).contains(&value));
+
+            if x > 0 {
+                assert!(value >= aces.pixel_at(x - 1, 0).unwrap().red);
+            }
+        }
+
+        // Mid-tones, i.e. values around 0.2 to 1.0
+        for x in 1..=5 {
+            assert!(aces.pixel_at(x, 0).unwrap().red > reinhard.pixel_at(x, 0).unwrap().red);
+        }
+    }
+
+    #[test]
+    fn given_a_canvas_of_known_grays_when_taking_its_luminance_histogram_should_bin_every_pixel() {
+        // Arrange
+        let gray = |value: f64| Color::new(value, value, value);
+
+        let canvas = Canvas::from_rows(&[
+            vec![gray(0.0), gray(0.1), gray(0.3)],
+            vec![gray(0.6), gray(0.9), gray(4.0)],
+        ])
+        .unwrap();
+
+        // Act
+        let histogram = canvas.luminance_histogram(4);
+        let average = canvas.average_luminance();
+
+        // Assert
+        assert_eq!(vec![2, 1, 1, 2], histogram);
+        assert_eq!(6, histogram.iter().sum::<usize>());
+        assert!((average - 5.9 / 6.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn given_a_canvas_when_applying_exposure_should_double_per_stop_up_and_halve_per_stop_down() {
+        // Arrange
+        let color = Color::new(0.1, 0.4, 0.25);
+
+        let mut brighter = Canvas::new_filled(3, 2, color);
+        let mut darker = Canvas::new_filled(3, 2, color);
+
+        // Act
+        brighter.apply_exposure(1.0);
+        darker.apply_exposure(-1.0);
+
+        // Assert
+        assert_eq!(Color::new(0.2, 0.8, 0.5), *brighter.pixel_at(2, 1).unwrap());
+        assert_eq!(
+            Color::new(0.05, 0.2, 0.125),
+            *darker.pixel_at(2, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn given_a_canvas_with_a_nan_pixel_when_finding_invalid_pixels_should_report_its_coordinates() {
+        // Arrange
+        let mut canvas = Canvas::new(4, 3);
+
+        canvas
+            .write_pixel(2, 1, Color::new(0.5, f64::NAN, 0.5))
+            .unwrap();
+        canvas
+            .write_pixel(3, 2, Color::new(f64::INFINITY, 0.0, 0.0))
+            .unwrap();
+
+        // Act
+        let result = canvas.find_invalid_pixels();
+        let blank_result = Canvas::new(4, 3).find_invalid_pixels();
+
+        // Assert
+        assert_eq!(vec![(2, 1), (3, 2)], result);
+        assert!(blank_result.is_empty());
+    }
+
+    // Pulls the RGB values back out of a PNG written by to_png, checking its chunks on the way
+    fn decode_png(png: &[u8]) -> (usize, usize, Vec<u8>) {
+        assert_eq!(
+            &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'],
+            &png[..8]
+        );
+
+        let mut header = Vec::new();
+        let mut zlib = Vec::new();
+        let mut rest = &png[8..];
+
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let chunk_type = &rest[4..8];
+            let data = &rest[8..8 + length];
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+
+            assert_eq!(Canvas::crc32(rest[4..8 + length].iter()), crc);
+
+            match chunk_type {
+                b"IHDR" => header = data.to_vec(),
+                b"IDAT" => zlib.extend_from_slice(data),
+                _ => {}
+            }
+
+            rest = &rest[12 + length..];
+        }
+
+        let width = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+        assert_eq!(&[8, 2, 0, 0, 0], &header[8..]);
+
+        // Only stored blocks need handling, since that's all to_png writes
+        let mut raw = Vec::new();
+        let mut block = &zlib[2..];
+
+        loop {
+            let is_final = block[0] & 1 == 1;
+            let length = u16::from_le_bytes([block[1], block[2]]) as usize;
+
+            raw.extend_from_slice(&block[5..5 + length]);
+            block = &block[5 + length..];
+
+            if is_final {
+                break;
+            }
+        }
+
+        // Drop the filter type from the start of every row
+        let pixels = raw
+            .chunks(1 + width * 3)
+            .flat_map(|row| {
+                assert_eq!(0, row[0]);
+                row[1..].to_vec()
+            })
+            .collect();
+
+        (width, height, pixels)
+    }
+
+    #[test]
+    fn given_a_canvas_when_writing_a_png_and_reading_it_back_should_match_within_rounding() {
+        // Arrange
+        let mut canvas = Canvas::new(3, 2);
+        canvas.write_pixel(0, 0, Color::new(1.5, 0.0, 0.0)).unwrap();
+        canvas.write_pixel(1, 0, Color::new(0.0, 0.5, 0.0)).unwrap();
+        canvas
+            .write_pixel(2, 0, Color::new(-0.5, 0.0, 1.0))
+            .unwrap();
+        canvas.write_pixel(0, 1, Color::new(0.2, 0.4, 0.6)).unwrap();
+
+        let path = std::env::temp_dir().join("raytracer_canvas_test.png");
+
+        // Act
+        canvas
+            .write_to_file(path.to_str().unwrap().to_string())
+            .unwrap();
+        let png = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let (width, height, pixels) = decode_png(&png);
+
+        // Assert
+        assert_eq!((3, 2), (width, height));
+        assert_eq!(canvas.to_png(), png);
+
+        for y in 0..height {
+            for x in 0..width {
+                let expected = canvas.pixel_at(x, y).unwrap();
+                let channels = [expected.red, expected.green, expected.blue];
+
+                for (channel, value) in channels.iter().enumerate() {
+                    let written = pixels[(y * width + x) * 3 + channel] as f64 / 255.0;
+
+                    assert!((value.clamp(0.0, 1.0) - written).abs() <= 1.0 / 255.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn given_a_canvas_too_big_for_one_stored_block_when_encoding_a_png_should_split_it_up() {
+        // Arrange
+        let mut canvas = Canvas::new_filled(200, 150, Color::new(0.25, 0.5, 0.75));
+        canvas.write_pixel(199, 149, Color::white()).unwrap();
+
+        // Act
+        let (width, height, pixels) = decode_png(&canvas.to_png());
+
+        // Assert
+        assert_eq!((200, 150), (width, height));
+        assert_eq!(200 * 150 * 3, pixels.len());
+        assert_eq!(&[255, 255, 255], &pixels[pixels.len() - 3..]);
+        assert_eq!(&[64, 128, 192], &pixels[..3]);
+    }
+
+    #[test]
+    fn given_the_empty_iend_chunk_when_checksumming_should_match_the_known_crc() {
+        // Act
+        let result = Canvas::crc32(b"IEND".iter());
+
+        // Assert
+        assert_eq!(0xae426082, result);
+    }
 }
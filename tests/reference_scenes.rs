@@ -0,0 +1,124 @@
+use std::f64::consts::PI;
+use std::fs;
+use std::sync::Arc;
+
+use raytracer::geometry::plane::Plane;
+use raytracer::geometry::shape::Shape;
+use raytracer::geometry::sphere::Sphere;
+use raytracer::materials::phong::Phong;
+use raytracer::matrices::matrix::Matrix;
+use raytracer::patterns::checker::Checker;
+use raytracer::patterns::solid::Solid;
+use raytracer::render;
+use raytracer::scene::camera::Camera;
+use raytracer::scene::world::World;
+use raytracer::tuples::color::Color;
+use raytracer::tuples::point_light::PointLight;
+use raytracer::tuples::tuple::Tuple;
+use raytracer::window::canvas::Canvas;
+
+/*
+    Renders a few small fixed scenes and checks them against the reference images committed under
+    tests/reference_images, to catch any unintended change to what the renderer draws. The renders
+    are passed through a PPM first so that they're rounded the same way as the references were.
+
+    After an intended change, set UPDATE_REFERENCE_IMAGES=1 when running the tests to write out new
+    references instead (and check them over before committing them).
+*/
+fn assert_matches_reference(name: &str, world: World, camera: Camera) {
+    let path = format!("tests/reference_images/{}.ppm", name);
+
+    let rendered = render(Arc::new(world), Arc::new(camera)).to_ppm().unwrap();
+
+    if std::env::var("UPDATE_REFERENCE_IMAGES").is_ok() {
+        fs::write(&path, &rendered).unwrap();
+        return;
+    }
+
+    let actual = Canvas::from_ppm(&rendered).unwrap();
+    let expected = Canvas::from_ppm(&fs::read_to_string(&path).unwrap()).unwrap();
+
+    let (differing_pixels, max_delta, _) = actual.diff(&expected).unwrap();
+
+    assert_eq!(
+        0, differing_pixels,
+        "{} differs from its reference image in {} pixels (by up to {})",
+        name, differing_pixels, max_delta
+    );
+}
+
+#[test]
+fn given_the_default_world_when_rendering_should_match_the_reference_image() {
+    // Arrange
+    let world = World::default();
+
+    let camera = Camera::new(
+        24,
+        24,
+        PI / 4.0,
+        Matrix::view_transform(
+            Tuple::point(0.0, 0.0, -5.0),
+            Tuple::point(0.0, 0.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ),
+    );
+
+    // Act & Assert
+    assert_matches_reference("default_world", world, camera);
+}
+
+#[test]
+fn given_a_glass_sphere_on_a_checker_floor_when_rendering_should_match_the_reference_image() {
+    // Arrange
+    let floor: Arc<dyn Shape> = Arc::new(Plane::new(
+        Arc::new(Matrix::identity(4)),
+        Arc::new(Phong::new(
+            Box::new(Checker::new(
+                Box::new(Solid::new(Color::white())),
+                Box::new(Solid::new(Color::new(0.2, 0.3, 0.6))),
+                Arc::new(Matrix::identity(4)),
+            )),
+            0.1,
+            0.9,
+            0.0,
+            200.0,
+            0.0,
+            0.0,
+            1.0,
+        )),
+        true,
+    ));
+
+    let glass: Arc<dyn Shape> = Arc::new(Sphere::new(
+        Arc::new(Matrix::translation(0.0, 1.0, 0.0)),
+        Arc::new(Phong::new(
+            Box::new(Solid::new(Color::new(0.1, 0.1, 0.1))),
+            0.0,
+            0.1,
+            0.9,
+            300.0,
+            0.9,
+            0.9,
+            1.5,
+        )),
+        true,
+    ));
+
+    let light = PointLight::new(Tuple::point(-5.0, 10.0, -10.0), Color::white());
+
+    let world = World::new(vec![floor, glass], vec![Arc::new(light)]);
+
+    let camera = Camera::new(
+        24,
+        24,
+        PI / 3.0,
+        Matrix::view_transform(
+            Tuple::point(0.0, 3.0, -6.0),
+            Tuple::point(0.0, 1.0, 0.0),
+            Tuple::vector(0.0, 1.0, 0.0),
+        ),
+    );
+
+    // Act & Assert
+    assert_matches_reference("glass_sphere_on_checker_floor", world, camera);
+}